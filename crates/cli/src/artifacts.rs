@@ -0,0 +1,320 @@
+use std::collections::BTreeMap;
+use std::io::{ErrorKind, Write};
+use std::path::{Component, Path, PathBuf};
+use std::sync::{Mutex, PoisonError};
+
+use futures::stream::{self, StreamExt, TryStreamExt};
+use jj_lib::build::BuildSelector;
+use jj_lib::glob::glob_match;
+use jj_lib::jenkins::{self, JenkinsClient};
+use serde::{Deserialize, Serialize};
+use tokio::io::AsyncWriteExt;
+
+use crate::cli::CliBuildArtifactsValid;
+use crate::error::AppError;
+use crate::output::json_list_write;
+
+// Enough to overlap request latency without hammering the controller, which
+// serves artifacts itself.
+const DOWNLOAD_CONCURRENCY: usize = 4;
+
+// Kept in the download directory, recording which version of each artifact
+// the file there holds.
+const MANIFEST_NAME: &str = ".jj-artifacts.json";
+
+enum Fetched {
+  // The whole file was downloaded.
+  Complete(u64),
+  // An earlier partial download was continued from the given offset.
+  Resumed(u64, u64),
+  // The file on disk is already the whole of this version of the artifact.
+  Current,
+}
+
+// What is known about a file in the download directory.
+#[derive(Clone, Deserialize, Serialize)]
+struct ManifestEntry {
+  url: String,
+  // The server's ETag or Last-Modified for the version being written.
+  validator: Option<String>,
+  // False while the download is under way, and after it is interrupted.
+  complete: bool,
+}
+
+// The download directory's record of its artifacts, keyed by relative path and
+// rewritten as each download starts and finishes.  Without it a file's length
+// says nothing about whether it came from the same build, or from the same
+// version of the artifact.
+struct Manifest {
+  path: PathBuf,
+  entries: Mutex<BTreeMap<String, ManifestEntry>>,
+}
+
+impl Manifest {
+  // An unreadable manifest is treated as empty, which only costs downloading
+  // files again.
+  fn load(to: &Path) -> Result<Self, AppError> {
+    let path = to.join(MANIFEST_NAME);
+    let entries = match std::fs::read(&path) {
+      Ok(contents) => serde_json::from_slice(&contents).unwrap_or_default(),
+      Err(e) if e.kind() == ErrorKind::NotFound => BTreeMap::new(),
+      Err(e) => {
+        return Err(AppError::ArtifactWrite(path.display().to_string(), e));
+      }
+    };
+    Ok(Manifest {
+      path,
+      entries: Mutex::new(entries),
+    })
+  }
+
+  fn entry(&self, relative_path: &str) -> Option<ManifestEntry> {
+    self
+      .entries
+      .lock()
+      .unwrap_or_else(PoisonError::into_inner)
+      .get(relative_path)
+      .cloned()
+  }
+
+  fn record(
+    &self,
+    relative_path: &str,
+    entry: ManifestEntry,
+  ) -> Result<(), AppError> {
+    let mut entries =
+      self.entries.lock().unwrap_or_else(PoisonError::into_inner);
+    entries.insert(relative_path.to_string(), entry);
+    std::fs::File::create(&self.path)
+      .and_then(|file| {
+        serde_json::to_writer_pretty(file, &*entries).map_err(Into::into)
+      })
+      .map_err(|e| AppError::ArtifactWrite(self.path.display().to_string(), e))
+  }
+}
+
+// One artifact in the JSON form of the listing.
+#[derive(Serialize)]
+struct ArtifactJson<'a> {
+  path: &'a str,
+  // None when the server does not say.
+  size: Option<u64>,
+}
+
+// One artifact in the JSON form of --download.
+#[derive(Serialize)]
+struct DownloadJson<'a> {
+  path: &'a str,
+  // "downloaded", "resumed", or "current" when it was already on disk.
+  status: &'static str,
+  bytes: u64,
+  #[serde(skip_serializing_if = "Option::is_none")]
+  resumed_from: Option<u64>,
+}
+
+impl<'a> DownloadJson<'a> {
+  fn new(path: &'a str, fetched: Fetched) -> Self {
+    let (status, bytes, resumed_from) = match fetched {
+      Fetched::Complete(bytes) => ("downloaded", bytes, None),
+      Fetched::Resumed(from, bytes) => ("resumed", bytes, Some(from)),
+      Fetched::Current => ("current", 0, None),
+    };
+    DownloadJson {
+      path,
+      status,
+      bytes,
+      resumed_from,
+    }
+  }
+}
+
+// Lists the build's artifacts with their sizes or, with --download, fetches
+// the ones matching the glob.
+pub async fn build_artifacts(
+  config: &CliBuildArtifactsValid,
+) -> Result<(), AppError> {
+  let build = config
+    .jenkins
+    .build_artifacts_get(&config.job, &config.build)
+    .await?;
+  // Permalinks move as builds finish, so say which build was used.
+  if let BuildSelector::Permalink(permalink) = config.build {
+    writeln!(std::io::stderr(), "{} is build #{}.", permalink, build.number)
+      .map_err(AppError::ArtifactsOutput)?;
+  }
+  let Some(pattern) = &config.download else {
+    return artifacts_list(config, &build).await;
+  };
+  let paths = build
+    .artifacts
+    .iter()
+    .map(|a| a.relative_path.as_str())
+    .filter(|path| glob_match(pattern, path))
+    .collect::<Vec<&str>>();
+  if paths.is_empty() {
+    return Err(AppError::ArtifactNoMatch(pattern.clone()));
+  }
+  let build_url = build.url.as_str();
+  let manifest = &Manifest::load(&config.to)?;
+  let downloads = stream::iter(paths)
+    .map(|path| async move {
+      let fetched =
+        artifact_fetch(&config.jenkins, manifest, build_url, path, &config.to)
+          .await?;
+      Ok::<_, AppError>((path, fetched))
+    })
+    .buffer_unordered(DOWNLOAD_CONCURRENCY);
+  if !config.output.is_text() {
+    let downloads = downloads
+      .map_ok(|(path, fetched)| DownloadJson::new(path, fetched))
+      .try_collect::<Vec<DownloadJson>>()
+      .await?;
+    return json_list_write(
+      config.output,
+      &downloads,
+      AppError::ArtifactsOutput,
+    );
+  }
+  downloads
+    .try_for_each(|(path, fetched)| async move {
+      let line = match fetched {
+        Fetched::Complete(bytes) => {
+          format!("Downloaded {} ({} bytes)", path, bytes)
+        }
+        Fetched::Resumed(from, bytes) => {
+          format!("Resumed {} at byte {} ({} bytes)", path, from, bytes)
+        }
+        Fetched::Current => format!("Already downloaded {}", path),
+      };
+      writeln!(std::io::stdout().lock(), "{}", line)
+        .map_err(AppError::ArtifactsOutput)
+    })
+    .await
+}
+
+async fn artifacts_list(
+  config: &CliBuildArtifactsValid,
+  build: &jenkins::JenkinsBuildArtifacts,
+) -> Result<(), AppError> {
+  let sizes = stream::iter(&build.artifacts)
+    .map(|artifact| async move {
+      let url = jenkins::artifact_url(&build.url, &artifact.relative_path)?;
+      Ok::<_, AppError>(config.jenkins.artifact_head(&url).await?.size)
+    })
+    .buffered(DOWNLOAD_CONCURRENCY)
+    .try_collect::<Vec<Option<u64>>>()
+    .await?;
+  if !config.output.is_text() {
+    let artifacts = build
+      .artifacts
+      .iter()
+      .zip(sizes)
+      .map(|(artifact, size)| ArtifactJson {
+        path: &artifact.relative_path,
+        size,
+      })
+      .collect::<Vec<ArtifactJson>>();
+    return json_list_write(
+      config.output,
+      &artifacts,
+      AppError::ArtifactsOutput,
+    );
+  }
+  let stdout = std::io::stdout();
+  let mut out = stdout.lock();
+  for (artifact, size) in build.artifacts.iter().zip(sizes) {
+    let size = size.map_or_else(|| "?".to_string(), |s| s.to_string());
+    writeln!(out, "{:>12}  {}", size, artifact.relative_path)
+      .map_err(AppError::ArtifactsOutput)?;
+  }
+  Ok(())
+}
+
+// Downloads one artifact to the same relative path under `to`.  A file already
+// there is only trusted when the manifest says it holds the same version of
+// the same artifact: then a whole one is skipped, and an interrupted one is
+// continued with a range request.  Anything else is downloaded from scratch.
+async fn artifact_fetch(
+  jenkins: &JenkinsClient,
+  manifest: &Manifest,
+  build_url: &str,
+  relative_path: &str,
+  to: &Path,
+) -> Result<Fetched, AppError> {
+  let dest = artifact_dest(to, relative_path)?;
+  let write_error =
+    |e: std::io::Error| AppError::ArtifactWrite(dest.display().to_string(), e);
+  let url = jenkins::artifact_url(build_url, relative_path)?;
+  let head = jenkins.artifact_head(&url).await?;
+  let existing = match tokio::fs::metadata(&dest).await {
+    Ok(metadata) => metadata.len(),
+    Err(e) if e.kind() == ErrorKind::NotFound => 0,
+    Err(e) => return Err(write_error(e)),
+  };
+  // Without a validator there is no telling whether the artifact changed.
+  let same_version = manifest.entry(relative_path).filter(|entry| {
+    entry.url == url.as_str()
+      && entry.validator.is_some()
+      && entry.validator == head.validator
+  });
+  let start = match (same_version, head.size) {
+    (Some(entry), Some(size)) if entry.complete && existing == size => {
+      return Ok(Fetched::Current);
+    }
+    (Some(entry), Some(size)) if !entry.complete && existing < size => existing,
+    _ => 0,
+  };
+  let body = jenkins
+    .artifact_get(&url, start, head.validator.as_deref())
+    .await?;
+  let resumed = start > 0 && body.partial;
+  if let Some(parent) = dest.parent() {
+    tokio::fs::create_dir_all(parent)
+      .await
+      .map_err(write_error)?;
+  }
+  let mut entry = ManifestEntry {
+    url: url.to_string(),
+    validator: body.validator.clone(),
+    complete: false,
+  };
+  manifest.record(relative_path, entry.clone())?;
+  let mut file = tokio::fs::OpenOptions::new()
+    .create(true)
+    .write(true)
+    .append(resumed)
+    .truncate(!resumed)
+    .open(&dest)
+    .await
+    .map_err(write_error)?;
+  let body = body.bytes_stream();
+  tokio::pin!(body);
+  let mut written = 0;
+  while let Some(chunk) = body.try_next().await? {
+    file.write_all(&chunk).await.map_err(write_error)?;
+    written += chunk.len() as u64;
+  }
+  file.flush().await.map_err(write_error)?;
+  entry.complete = true;
+  manifest.record(relative_path, entry)?;
+  Ok(if resumed {
+    Fetched::Resumed(start, written)
+  } else {
+    Fetched::Complete(written)
+  })
+}
+
+// Jenkins only reports paths inside the artifact root, but the path comes from
+// the server and decides where a file is written, so anything that could climb
+// out of `to` is refused.
+fn artifact_dest(to: &Path, relative_path: &str) -> Result<PathBuf, AppError> {
+  let relative = Path::new(relative_path);
+  if relative
+    .components()
+    .all(|c| matches!(c, Component::Normal(_)))
+  {
+    Ok(to.join(relative))
+  } else {
+    Err(AppError::ArtifactPath(relative_path.to_string()))
+  }
+}
@@ -1,10 +1,19 @@
-use clap::{Parser, Subcommand};
-use reqwest_middleware::{ClientBuilder, ClientWithMiddleware};
+use clap::{Parser, Subcommand, ValueEnum};
+use jj_lib::build::BuildSelector;
+use jj_lib::jenkins::JenkinsClient;
+use jj_lib::job::JobPath;
+use jj_lib::retry::RetryPolicy;
+use serde::Deserialize;
 use std::collections::HashMap;
 use std::error::Error;
+use std::path::PathBuf;
+use std::time::Duration;
 
 use crate::config;
 use crate::error;
+use crate::input::InputAnswers;
+use crate::output::OutputFormat;
+use crate::params;
 
 // Shameful rip from:
 // https://github.com/clap-rs/clap/blob/master/examples/typed-derive.rs#L24-L26
@@ -25,12 +34,26 @@ where
   Ok((s[..pos].parse()?, s[pos + 1..].parse()?))
 }
 
+// A file parameter is written as name=@path, after curl's -F, so it reads
+// differently from a -P value that happens to be a path.
+fn parse_file_param(
+  s: &str,
+) -> Result<(String, PathBuf), Box<dyn Error + Send + Sync + 'static>> {
+  let (name, path) = s
+    .split_once("=@")
+    .filter(|(name, path)| !name.is_empty() && !path.is_empty())
+    .ok_or_else(|| format!("invalid NAME=@path: expected `=@` in `{}`", s))?;
+  Ok((name.to_string(), PathBuf::from(path)))
+}
+
 #[derive(Subcommand, Debug, Clone)]
 pub enum CliCommand {
   /// Manage and run Jenkins jobs
   Job(JobArgs),
   /// Inspect Jenkins builds
   Build(BuildArgs),
+  /// Inspect and manage the build queue
+  Queue(QueueArgs),
 }
 
 #[derive(Parser, Debug, Clone)]
@@ -45,31 +68,121 @@ pub enum JobCommand {
   Run(JobRunArgs),
   /// Stream logs from active builds of a job
   Follow(JobFollowArgs),
+  /// List the parameters a job accepts
+  Params(JobParamsArgs),
 }
 
 #[derive(Parser, Debug, Clone)]
 pub struct JobRunArgs {
-  pub job: String,
+  /// Job to run, with any folders or multibranch project as a path, e.g.
+  /// `platform/deploy` or `service/main`, or a job alias from the config
+  /// file.
+  pub job: JobPath,
+  #[command(flatten)]
+  pub params: ParamArgs,
+  /// Upload a file parameter, as NAME=@path.  Repeat for each.  The file is
+  /// streamed to Jenkins rather than read into memory.
+  #[arg(
+    long = "file",
+    short = 'F',
+    value_parser = parse_file_param,
+    number_of_values = 1
+  )]
+  pub files: Vec<(String, PathBuf)>,
+  /// What to do with the queued item or running build when interrupted with
+  /// Ctrl+C [default: detach, or the job alias's on_interrupt].
+  #[arg(long, value_enum)]
+  pub on_interrupt: Option<InterruptPolicy>,
+  /// Also stream the builds this build triggers, and theirs, folding their
+  /// results into the exit code.
+  #[arg(long)]
+  pub follow_downstream: bool,
+  #[command(flatten)]
+  pub inputs: InputArgs,
+}
+
+#[derive(ValueEnum, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum InterruptPolicy {
+  /// Leave the queued item or running build alone.
+  Detach,
+  /// Cancel the item if it is still queued; leave a running build alone.
+  Cancel,
+  /// Cancel the item if queued, or abort the build if it is running.
+  Abort,
+}
+
+#[derive(Parser, Debug, Clone)]
+pub struct JobFollowArgs {
+  /// Adopt the next build and exit with its result code instead of watching
+  /// continuously.
+  #[arg(long)]
+  pub once: bool,
+  /// Job to follow, as a path through any folders or a job alias.
+  pub job: JobPath,
+  /// Also stream the builds this build triggers, and theirs, folding their
+  /// results into the exit code.
+  #[arg(long)]
+  pub follow_downstream: bool,
+  /// Also write each build's log to <job>-<number>.log in this directory,
+  /// alongside the live view.
+  #[arg(long)]
+  pub log_dir: Option<PathBuf>,
+  #[command(flatten)]
+  pub inputs: InputArgs,
+}
+
+#[derive(Parser, Debug, Clone)]
+pub struct ParamArgs {
+  /// Set a parameter.  Repeat for each.  Overrides the same parameter from
+  /// any other source.
   // number_of_values = 1 means --param must be repeated for each pair.
   // Values must be provided with an equals sign separating them.  See
   // https://github.com/clap-rs/clap_derive/blob/master/examples/keyvalue.rs
-  // for examples.
+  // for examples.  Ids are explicit since InputArgs, flattened alongside,
+  // has its own `values`.
   #[arg(
+    id = "param",
     long = "param",
     short = 'P',
     value_parser = parse_key_val::<String, String>,
     number_of_values = 1
   )]
-  pub params: Vec<(String, String)>,
+  pub values: Vec<(String, String)>,
+  /// Read parameters from a TOML (.toml), JSON (.json) or dotenv (any other
+  /// name) file.  Repeat for several; later files override earlier ones.
+  #[arg(id = "param-file", long = "param-file", number_of_values = 1)]
+  pub files: Vec<PathBuf>,
+  /// Read parameters from stdin as a JSON object.  These override parameter
+  /// files, and -P overrides these.
+  #[arg(id = "params-from-stdin", long = "params-from-stdin")]
+  pub stdin: bool,
 }
 
 #[derive(Parser, Debug, Clone)]
-pub struct JobFollowArgs {
-  /// Adopt the next build and exit with its result code instead of watching
-  /// continuously.
+pub struct InputArgs {
+  /// Proceed past any input step the build stops on instead of prompting.
   #[arg(long)]
-  pub once: bool,
-  pub job: String,
+  pub approve: bool,
+  /// Abort at the first input step the build stops on instead of prompting.
+  #[arg(long, conflicts_with = "approve")]
+  pub reject: bool,
+  /// Answer one of an input step's parameters.  Repeat for each.  Parameters
+  /// left out are prompted for, or take their defaults with --approve.
+  #[arg(
+    long = "input",
+    short = 'I',
+    value_parser = parse_key_val::<String, String>,
+    number_of_values = 1
+  )]
+  pub values: Vec<(String, String)>,
+}
+
+#[derive(Parser, Debug, Clone)]
+pub struct JobParamsArgs {
+  /// Job whose parameters to list, as a path through any folders or a job
+  /// alias.
+  pub job: JobPath,
 }
 
 #[derive(Parser, Debug, Clone)]
@@ -82,11 +195,20 @@ pub struct BuildArgs {
 pub enum BuildCommand {
   /// Show metadata and/or log for a specific build
   View(BuildViewArgs),
+  /// Abort a running build, escalating if it does not stop
+  Stop(BuildStopArgs),
+  /// Run a build again with the parameters it was run with
+  Rebuild(BuildRebuildArgs),
+  /// List a build's artifacts, or download them
+  Artifacts(BuildArtifactsArgs),
+  /// Summarize a build's test report and list its failures
+  Tests(BuildTestsArgs),
 }
 
 #[derive(Parser, Debug, Clone)]
 pub struct BuildViewArgs {
-  pub job: String,
+  /// Job the build belongs to, as a path through any folders or a job alias.
+  pub job: JobPath,
   pub build_number: u64,
   /// Show build metadata only (default: show both)
   #[arg(long)]
@@ -94,32 +216,233 @@ pub struct BuildViewArgs {
   /// Show build log only (default: show both)
   #[arg(long)]
   pub log: bool,
+  /// Also show the builds this build triggered, and theirs, and exit with the
+  /// worst result among them all.
+  #[arg(long)]
+  pub follow_downstream: bool,
+}
+
+#[derive(Parser, Debug, Clone)]
+pub struct QueueArgs {
+  #[command(subcommand)]
+  pub command: QueueCommand,
+}
+
+#[derive(Subcommand, Debug, Clone)]
+pub enum QueueCommand {
+  /// List queued items
+  List(QueueListArgs),
+  /// Cancel a queued item by id, or every queued item of a job
+  Cancel(QueueCancelArgs),
+}
+
+#[derive(Parser, Debug, Clone)]
+pub struct QueueListArgs {
+  /// Only list items for this job, as a path through any folders or a job
+  /// alias.
+  #[arg(long)]
+  pub job: Option<JobPath>,
+  /// Only list items requested by this user id.
+  #[arg(long)]
+  pub user: Option<String>,
+}
+
+#[derive(Parser, Debug, Clone)]
+pub struct QueueCancelArgs {
+  /// A queue item id, or a job path or alias whose queued items should all
+  /// be cancelled.  Numbers are always taken as ids.
+  pub target: QueueTarget,
+}
+
+#[derive(Debug, Clone)]
+pub enum QueueTarget {
+  Id(u32),
+  Job(JobPath),
+}
+
+impl std::str::FromStr for QueueTarget {
+  type Err = jj_lib::job::JobPathError;
+
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    s.parse::<u32>().map_or_else(
+      |_| s.parse().map(QueueTarget::Job),
+      |id| Ok(QueueTarget::Id(id)),
+    )
+  }
+}
+
+#[derive(Parser, Debug, Clone)]
+pub struct BuildStopArgs {
+  /// Job the build belongs to, as a path through any folders or a job alias.
+  pub job: JobPath,
+  /// The build number, or "all-running" for every build still running.
+  pub target: BuildTarget,
+  /// Seconds to wait for the build to end after each of stop, term, and kill
+  /// before escalating to the next.
+  #[arg(long, default_value_t = 10)]
+  pub timeout: u64,
+}
+
+#[derive(Parser, Debug, Clone)]
+pub struct BuildArtifactsArgs {
+  /// Job the build belongs to, as a path through any folders or a job alias.
+  pub job: JobPath,
+  /// The build number, or a permalink such as lastSuccessfulBuild.
+  pub build: BuildSelector,
+  /// Download the artifacts whose paths match this glob instead of listing
+  /// them.  `*` stays within a directory; `**` spans directories.
+  #[arg(long)]
+  pub download: Option<String>,
+  /// Directory to download into.  Artifacts keep their relative paths below
+  /// it.
+  #[arg(long, default_value = ".", requires = "download")]
+  pub to: PathBuf,
+}
+
+#[derive(Parser, Debug, Clone)]
+pub struct BuildTestsArgs {
+  /// Job the build belongs to, as a path through any folders or a job alias.
+  pub job: JobPath,
+  /// The build number, or a permalink such as lastCompletedBuild.
+  pub build: BuildSelector,
+  /// Also write the report to this file as JUnit XML.
+  #[arg(long)]
+  pub junit: Option<PathBuf>,
+}
+
+#[derive(Parser, Debug, Clone)]
+pub struct BuildRebuildArgs {
+  /// Job the build belongs to, as a path through any folders or a job alias.
+  pub job: JobPath,
+  pub build_number: u64,
+  // Overrides for the original build's parameters.
+  #[command(flatten)]
+  pub params: ParamArgs,
+  /// What to do with the queued item or running build when interrupted with
+  /// Ctrl+C [default: detach, or the job alias's on_interrupt].
+  #[arg(long, value_enum)]
+  pub on_interrupt: Option<InterruptPolicy>,
+  /// Also stream the builds this build triggers, and theirs, folding their
+  /// results into the exit code.
+  #[arg(long)]
+  pub follow_downstream: bool,
+  #[command(flatten)]
+  pub inputs: InputArgs,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum BuildTarget {
+  Number(u64),
+  AllRunning,
+}
+
+impl std::str::FromStr for BuildTarget {
+  type Err = String;
+
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    match s {
+      "all-running" => Ok(BuildTarget::AllRunning),
+      _ => s.parse().map(BuildTarget::Number).map_err(|_| {
+        format!("expected a build number or \"all-running\", got '{}'", s)
+      }),
+    }
+  }
 }
 
 #[derive(Clone)]
 pub struct CliJobRunValid {
-  pub server: config::ConfigServer,
-  pub client: ClientWithMiddleware,
-  pub job: String,
+  pub jenkins: JenkinsClient,
+  pub job: JobPath,
   pub params: HashMap<String, String>,
+  pub files: HashMap<String, PathBuf>,
+  pub on_interrupt: InterruptPolicy,
+  pub inputs: InputAnswers,
+  pub follow_downstream: bool,
+  pub output: OutputFormat,
 }
 
 #[derive(Clone)]
 pub struct CliJobFollowValid {
-  pub server: config::ConfigServer,
-  pub client: ClientWithMiddleware,
-  pub job: String,
+  pub jenkins: JenkinsClient,
+  pub job: JobPath,
   pub once: bool,
+  pub inputs: InputAnswers,
+  pub follow_downstream: bool,
+  pub output: OutputFormat,
+  pub log_dir: Option<PathBuf>,
+}
+
+#[derive(Clone)]
+pub struct CliJobParamsValid {
+  pub jenkins: JenkinsClient,
+  pub job: JobPath,
+  pub output: OutputFormat,
+}
+
+#[derive(Clone)]
+pub struct CliQueueListValid {
+  pub jenkins: JenkinsClient,
+  pub job: Option<JobPath>,
+  pub user: Option<String>,
+  pub output: OutputFormat,
+}
+
+#[derive(Clone)]
+pub struct CliQueueCancelValid {
+  pub jenkins: JenkinsClient,
+  pub target: QueueTarget,
+  pub output: OutputFormat,
+}
+
+#[derive(Clone)]
+pub struct CliBuildArtifactsValid {
+  pub jenkins: JenkinsClient,
+  pub job: JobPath,
+  pub build: BuildSelector,
+  pub download: Option<String>,
+  pub to: PathBuf,
+  pub output: OutputFormat,
+}
+
+#[derive(Clone)]
+pub struct CliBuildTestsValid {
+  pub jenkins: JenkinsClient,
+  pub job: JobPath,
+  pub build: BuildSelector,
+  pub junit: Option<PathBuf>,
+  pub output: OutputFormat,
+}
+
+#[derive(Clone)]
+pub struct CliBuildRebuildValid {
+  pub jenkins: JenkinsClient,
+  pub job: JobPath,
+  pub build_number: u64,
+  pub overrides: HashMap<String, String>,
+  pub on_interrupt: InterruptPolicy,
+  pub inputs: InputAnswers,
+  pub follow_downstream: bool,
+  pub output: OutputFormat,
+}
+
+#[derive(Clone)]
+pub struct CliBuildStopValid {
+  pub jenkins: JenkinsClient,
+  pub job: JobPath,
+  pub target: BuildTarget,
+  pub timeout: std::time::Duration,
+  pub output: OutputFormat,
 }
 
 #[derive(Clone)]
 pub struct CliBuildViewValid {
-  pub server: config::ConfigServer,
-  pub client: ClientWithMiddleware,
-  pub job: String,
+  pub jenkins: JenkinsClient,
+  pub job: JobPath,
   pub build_number: u64,
   pub show_metadata: bool,
   pub show_log: bool,
+  pub follow_downstream: bool,
+  pub output: OutputFormat,
 }
 
 fn resolve_server(
@@ -139,19 +462,99 @@ fn resolve_server(
     .ok_or(error::AppError::CliConfigServerMissing(name))
 }
 
-fn build_client() -> ClientWithMiddleware {
-  ClientBuilder::new(reqwest::Client::new()).build()
+fn jenkins_client(
+  config: &config::Config,
+  server: &config::ConfigServer,
+) -> Result<JenkinsClient, error::AppError> {
+  let client = JenkinsClient::with_retry(
+    &server.host_url,
+    &server.username,
+    &server.token,
+    RetryPolicy {
+      max_retries: config.retries,
+      initial_backoff: Duration::from_millis(config.retry_backoff_ms),
+      ..RetryPolicy::default()
+    },
+  );
+  match (&config.record, &config.replay) {
+    (Some(_), Some(_)) => Err(error::AppError::CliRecordReplay),
+    (Some(path), None) => Ok(client.record_to(path)?),
+    (None, Some(path)) => Ok(client.replay_from(path)?),
+    (None, None) => Ok(client),
+  }
+}
+
+// A job argument with any alias expanded: the server to talk to and the job's
+// full path, plus the alias's defaults when the argument was one.
+struct JobTarget<'a> {
+  server: config::ConfigServer,
+  job: JobPath,
+  alias: Option<&'a config::ConfigJob>,
+}
+
+impl JobTarget<'_> {
+  fn follow_downstream(&self, flag: bool) -> bool {
+    flag
+      || self
+        .alias
+        .and_then(|a| a.follow_downstream)
+        .unwrap_or(false)
+  }
+
+  fn on_interrupt(&self, flag: Option<InterruptPolicy>) -> InterruptPolicy {
+    flag
+      .or_else(|| self.alias.and_then(|a| a.on_interrupt))
+      .unwrap_or(InterruptPolicy::Detach)
+  }
+
+  // The alias's parameters with those given on the command line laid over
+  // them.
+  fn params(
+    &self,
+    args: &ParamArgs,
+  ) -> Result<HashMap<String, String>, error::AppError> {
+    let mut params = self.alias.map(|a| a.params.clone()).unwrap_or_default();
+    params.extend(param_args(args)?);
+    Ok(params)
+  }
+}
+
+// A single-segment job naming a `[jobs.<alias>]` table is the alias, even if
+// a top-level job has the same name.  The alias's server is used unless
+// --server names another.
+fn job_target<'a>(
+  config: &'a config::Config,
+  job: &JobPath,
+) -> Result<JobTarget<'a>, error::AppError> {
+  let alias = match job.segments() {
+    [name] => config.registry.jobs.get(name),
+    _ => None,
+  };
+  let server_name = alias
+    .and_then(|a| a.server.as_deref())
+    .filter(|_| config.server == "default")
+    .unwrap_or(&config.server);
+  Ok(JobTarget {
+    server: resolve_server(server_name, config)?,
+    job: alias.map_or_else(|| job.clone(), |a| a.job.clone()),
+    alias,
+  })
 }
 
 pub fn cli_job_run_validate(
   config: &config::Config,
   args: &JobRunArgs,
 ) -> Result<CliJobRunValid, error::AppError> {
+  let target = job_target(config, &args.job)?;
   Ok(CliJobRunValid {
-    server: resolve_server(&config.server, config)?,
-    client: build_client(),
-    job: args.job.clone(),
-    params: args.params.iter().cloned().collect(),
+    jenkins: jenkins_client(config, &target.server)?,
+    params: target.params(&args.params)?,
+    files: args.files.iter().cloned().collect(),
+    on_interrupt: target.on_interrupt(args.on_interrupt),
+    inputs: input_answers(&args.inputs),
+    follow_downstream: target.follow_downstream(args.follow_downstream),
+    output: config.output,
+    job: target.job,
   })
 }
 
@@ -159,11 +562,66 @@ pub fn cli_job_follow_validate(
   config: &config::Config,
   args: &JobFollowArgs,
 ) -> Result<CliJobFollowValid, error::AppError> {
+  let target = job_target(config, &args.job)?;
   Ok(CliJobFollowValid {
-    server: resolve_server(&config.server, config)?,
-    client: build_client(),
-    job: args.job.clone(),
+    jenkins: jenkins_client(config, &target.server)?,
     once: args.once,
+    inputs: input_answers(&args.inputs),
+    follow_downstream: target.follow_downstream(args.follow_downstream),
+    output: config.output,
+    log_dir: args.log_dir.clone(),
+    job: target.job,
+  })
+}
+
+pub fn cli_job_params_validate(
+  config: &config::Config,
+  args: &JobParamsArgs,
+) -> Result<CliJobParamsValid, error::AppError> {
+  let target = job_target(config, &args.job)?;
+  Ok(CliJobParamsValid {
+    jenkins: jenkins_client(config, &target.server)?,
+    output: config.output,
+    job: target.job,
+  })
+}
+
+pub fn cli_queue_list_validate(
+  config: &config::Config,
+  args: &QueueListArgs,
+) -> Result<CliQueueListValid, error::AppError> {
+  let (server, job) = match &args.job {
+    Some(job) => {
+      let target = job_target(config, job)?;
+      (target.server, Some(target.job))
+    }
+    None => (resolve_server(&config.server, config)?, None),
+  };
+  Ok(CliQueueListValid {
+    jenkins: jenkins_client(config, &server)?,
+    job,
+    user: args.user.clone(),
+    output: config.output,
+  })
+}
+
+pub fn cli_queue_cancel_validate(
+  config: &config::Config,
+  args: &QueueCancelArgs,
+) -> Result<CliQueueCancelValid, error::AppError> {
+  let (server, target) = match &args.target {
+    QueueTarget::Job(job) => {
+      let target = job_target(config, job)?;
+      (target.server, QueueTarget::Job(target.job))
+    }
+    QueueTarget::Id(id) => {
+      (resolve_server(&config.server, config)?, QueueTarget::Id(*id))
+    }
+  };
+  Ok(CliQueueCancelValid {
+    jenkins: jenkins_client(config, &server)?,
+    target,
+    output: config.output,
   })
 }
 
@@ -177,12 +635,98 @@ pub fn cli_build_view_validate(
   } else {
     (args.metadata, args.log)
   };
+  let target = job_target(config, &args.job)?;
   Ok(CliBuildViewValid {
-    server: resolve_server(&config.server, config)?,
-    client: build_client(),
-    job: args.job.clone(),
+    jenkins: jenkins_client(config, &target.server)?,
     build_number: args.build_number,
     show_metadata,
     show_log,
+    follow_downstream: target.follow_downstream(args.follow_downstream),
+    output: config.output,
+    job: target.job,
+  })
+}
+
+pub fn cli_build_stop_validate(
+  config: &config::Config,
+  args: &BuildStopArgs,
+) -> Result<CliBuildStopValid, error::AppError> {
+  let target = job_target(config, &args.job)?;
+  Ok(CliBuildStopValid {
+    jenkins: jenkins_client(config, &target.server)?,
+    target: args.target,
+    timeout: std::time::Duration::from_secs(args.timeout),
+    output: config.output,
+    job: target.job,
+  })
+}
+
+pub fn cli_build_rebuild_validate(
+  config: &config::Config,
+  args: &BuildRebuildArgs,
+) -> Result<CliBuildRebuildValid, error::AppError> {
+  let target = job_target(config, &args.job)?;
+  Ok(CliBuildRebuildValid {
+    jenkins: jenkins_client(config, &target.server)?,
+    build_number: args.build_number,
+    overrides: param_args(&args.params)?,
+    on_interrupt: target.on_interrupt(args.on_interrupt),
+    inputs: input_answers(&args.inputs),
+    follow_downstream: target.follow_downstream(args.follow_downstream),
+    output: config.output,
+    job: target.job,
+  })
+}
+
+// Parameters from every command line source, each overriding the ones before
+// it: --param-file in the order given, then --params-from-stdin, then -P.
+fn param_args(
+  args: &ParamArgs,
+) -> Result<HashMap<String, String>, error::AppError> {
+  let mut params = HashMap::new();
+  for path in &args.files {
+    params.extend(params::param_file_read(path)?);
+  }
+  if args.stdin {
+    params.extend(params::params_stdin_read()?);
+  }
+  params.extend(args.values.iter().cloned());
+  Ok(params)
+}
+
+fn input_answers(args: &InputArgs) -> InputAnswers {
+  InputAnswers {
+    approve: args.approve,
+    reject: args.reject,
+    values: args.values.iter().cloned().collect(),
+  }
+}
+
+pub fn cli_build_artifacts_validate(
+  config: &config::Config,
+  args: &BuildArtifactsArgs,
+) -> Result<CliBuildArtifactsValid, error::AppError> {
+  let target = job_target(config, &args.job)?;
+  Ok(CliBuildArtifactsValid {
+    jenkins: jenkins_client(config, &target.server)?,
+    build: args.build.clone(),
+    download: args.download.clone(),
+    to: args.to.clone(),
+    output: config.output,
+    job: target.job,
+  })
+}
+
+pub fn cli_build_tests_validate(
+  config: &config::Config,
+  args: &BuildTestsArgs,
+) -> Result<CliBuildTestsValid, error::AppError> {
+  let target = job_target(config, &args.job)?;
+  Ok(CliBuildTestsValid {
+    jenkins: jenkins_client(config, &target.server)?,
+    build: args.build.clone(),
+    junit: args.junit.clone(),
+    output: config.output,
+    job: target.job,
   })
 }
@@ -7,15 +7,17 @@
 //! resolver that loads the same config file foundation discovers and evaluates
 //! each server's token through the shell.
 
-use jj_lib::{LogFormat, LogLevel};
+use jj_lib::{JobPath, LogFormat, LogLevel};
 use rust_template_foundation::config::{find_config_file, load_toml};
 use rust_template_foundation::MergeConfig;
 use serde::Deserialize;
 use std::collections::HashMap;
+use std::path::PathBuf;
 use std::process::Command;
 use thiserror::Error;
 
-use crate::cli::CliCommand;
+use crate::cli::{CliCommand, InterruptPolicy};
+use crate::output::OutputFormat;
 
 #[derive(Debug, Clone, MergeConfig)]
 #[merge_config(app_name = "jj", extra_error = "ServerConfigError")]
@@ -28,6 +30,25 @@ pub struct Config {
   /// resolves to the file's `default_server`.
   #[merge_config(short, default = "\"default\".to_string()")]
   pub server: String,
+  /// Output format: text, or JSON for tooling.  Streaming commands emit one
+  /// JSON event per line under either json or ndjson.
+  #[merge_config(default = "OutputFormat::Text")]
+  pub output: OutputFormat,
+  /// How many times to retry a read that fails with a connection error or a
+  /// 429, 502, 503 or 504, and to reconnect an interrupted log stream.  0
+  /// disables retrying.
+  #[merge_config(default = "3")]
+  pub retries: u32,
+  /// Milliseconds to wait before the first retry.  Each further retry waits
+  /// twice as long, up to 30 seconds.
+  #[merge_config(default = "500")]
+  pub retry_backoff_ms: u64,
+  /// Record every request to Jenkins and its response to this file, as
+  /// newline-delimited JSON with credentials redacted.
+  pub record: Option<PathBuf>,
+  /// Answer every request from a file written by --record instead of
+  /// contacting Jenkins.
+  pub replay: Option<PathBuf>,
   /// Server registry loaded from the config file, with each server's token
   /// evaluated.  Populated by [`Config::resolve_registry`].
   #[merge_config(skip)]
@@ -52,10 +73,38 @@ impl Config {
     let raw: ServerConfigFile = load_toml(&path)?;
     Ok(ServerRegistry {
       default_server: raw.default_server,
+      jobs: raw
+        .jobs
+        .into_iter()
+        .map(|(alias, job)| {
+          let path = job
+            .job
+            .parse::<JobPath>()
+            .map_err(|e| ServerConfigError::JobPath(alias.clone(), e))?;
+          Ok((
+            alias,
+            ConfigJob {
+              server: job.server,
+              job: path,
+              params: job
+                .params
+                .into_iter()
+                .map(|(name, value)| (name, param_value(value)))
+                .collect(),
+              follow_downstream: job.follow_downstream,
+              on_interrupt: job.on_interrupt,
+            },
+          ))
+        })
+        .collect::<Result<HashMap<String, ConfigJob>, ServerConfigError>>()?,
       servers: raw
-        .servers
+        .entries
         .into_iter()
-        .map(|(name, server)| {
+        .filter(|(_, entry)| entry.is_table())
+        .map(|(name, entry)| {
+          let server = entry
+            .try_into::<ConfigServerFileRaw>()
+            .map_err(|e| ServerConfigError::Server(name.clone(), e))?;
           let username = server.username.map_or_else(
             || std::env::var("USER").map_err(ServerConfigError::UserVar),
             Ok,
@@ -76,11 +125,13 @@ impl Config {
   }
 }
 
-/// Server registry resolved from the config file.
+/// Server registry resolved from the config file, along with the job aliases
+/// that refer to its servers.
 #[derive(Clone, Debug, Default)]
 pub struct ServerRegistry {
   pub default_server: String,
   pub servers: HashMap<String, ConfigServer>,
+  pub jobs: HashMap<String, ConfigJob>,
 }
 
 /// A fully-resolved server whose token has already been evaluated.
@@ -94,14 +145,46 @@ pub struct ConfigServer {
   pub token: String,
 }
 
-/// Config-file shape for jj's server registry: `default_server` alongside a
-/// flattened set of bare `[<name>]` server tables.
+/// A job alias from a `[jobs.<alias>]` table: a short name for a job on a
+/// particular server, with defaults for running it.
+#[derive(Clone, Debug)]
+pub struct ConfigJob {
+  /// The server the job lives on, when not the default server.
+  pub server: Option<String>,
+  pub job: JobPath,
+  /// Parameter values, which `-P` overrides one at a time.
+  pub params: HashMap<String, String>,
+  pub follow_downstream: Option<bool>,
+  pub on_interrupt: Option<InterruptPolicy>,
+}
+
+/// Config-file shape for jj's server registry: `default_server` and the
+/// `[jobs.<alias>]` tables alongside a flattened set of bare `[<name>]` server
+/// tables.  Top-level settings such as `output` or `retries` share the
+/// flattened map; only tables are servers.
 #[derive(Debug, Default, Deserialize)]
 pub struct ServerConfigFile {
   #[serde(default)]
   pub default_server: String,
+  #[serde(default)]
+  pub jobs: HashMap<String, ConfigJobFileRaw>,
   #[serde(flatten)]
-  pub servers: HashMap<String, ConfigServerFileRaw>,
+  pub entries: HashMap<String, toml::Value>,
+}
+
+// Unknown keys are refused so a misspelled setting does not silently run the
+// job without it.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ConfigJobFileRaw {
+  pub server: Option<String>,
+  // The job's full path through any folders, e.g. "platform/deploy".
+  pub job: String,
+  // Values may be written as TOML strings, booleans or numbers.
+  #[serde(default)]
+  pub params: HashMap<String, toml::Value>,
+  pub follow_downstream: Option<bool>,
+  pub on_interrupt: Option<InterruptPolicy>,
 }
 
 #[derive(Clone, Debug, Deserialize)]
@@ -121,10 +204,23 @@ pub enum ServerConfigError {
   TokenEval(#[source] std::io::Error),
   #[error("Failed to read token command output: {0}")]
   TokenRead(#[source] std::string::FromUtf8Error),
+  #[error("Invalid server table [{0}]: {1}")]
+  Server(String, #[source] toml::de::Error),
+  #[error("Invalid job path in [jobs.{0}]: {1}")]
+  JobPath(String, #[source] jj_lib::job::JobPathError),
   #[error("Missing USER environment variable: {0}")]
   UserVar(#[source] std::env::VarError),
 }
 
+// Parameters travel as strings, so `true` and `3` are sent as Jenkins would
+// receive them from a form.
+fn param_value(value: toml::Value) -> String {
+  match value {
+    toml::Value::String(s) => s,
+    other => other.to_string(),
+  }
+}
+
 fn token_eval(token_code: &str) -> Result<String, ServerConfigError> {
   // Beware that sh could be a shell you don't expect in your environment.
   Command::new("sh")
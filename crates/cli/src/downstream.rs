@@ -0,0 +1,218 @@
+// Builds triggered by the build being streamed, such as those a Pipeline's
+// `build job:` steps start.  With --follow-downstream they are streamed
+// alongside their parent, each line prefixed with the chain of builds that led
+// to it, e.g. `[42 > deploy#17]` (or, as JSON, tagged with its own job and
+// build number), and their results are folded into the exit code.  Finding
+// downstream builds is a convenience: failures to look them up are logged and
+// never interrupt the streams.
+use std::collections::HashSet;
+use std::io::Write;
+use std::path::Path;
+use std::time::Duration;
+
+use futures::stream::{FuturesUnordered, StreamExt};
+use hash_color_lib::HashColorizer;
+use jj_lib::build::BuildStatus;
+use jj_lib::jenkins::JenkinsClient;
+use jj_lib::job::JobPath;
+use jj_lib::sink::{FileSink, LogSink, StdoutSink, TeeSink};
+use tokio::time;
+use tracing::*;
+
+use crate::error::AppError;
+use crate::input::InputAnswers;
+use crate::output::{event_write, BuildEvent, EventSink, OutputFormat};
+use crate::stages;
+
+const DOWNSTREAM_POLL_INTERVAL: Duration = Duration::from_secs(5);
+
+#[derive(Clone, Debug)]
+pub struct BuildNode {
+  pub job: JobPath,
+  pub number: u64,
+  pub url: String,
+  // The prefix label: the build number for the build jj was asked about, and
+  // "<parent label> > <job>#<number>" for builds downstream of it.
+  pub label: String,
+}
+
+impl BuildNode {
+  pub fn root(job: JobPath, number: u64, url: String) -> Self {
+    BuildNode {
+      label: number.to_string(),
+      job,
+      number,
+      url,
+    }
+  }
+
+  fn child(&self, jenkins: &JenkinsClient, job: JobPath, number: u64) -> Self {
+    BuildNode {
+      label: format!("{} > {}#{}", self.label, job.name(), number),
+      url: jenkins.build_url(&job, number),
+      job,
+      number,
+    }
+  }
+}
+
+// Streams the build to completion and returns its result.  With
+// `follow_downstream`, every build it triggers is streamed too, recursively,
+// and the worst of all their results is returned.
+pub async fn build_stream(
+  jenkins: &JenkinsClient,
+  root: BuildNode,
+  colorizer: &HashColorizer,
+  answers: &InputAnswers,
+  follow_downstream: bool,
+  output: OutputFormat,
+  log_dir: Option<&Path>,
+) -> Result<BuildStatus, AppError> {
+  if !follow_downstream {
+    return node_stream(jenkins, root, colorizer, answers, output, log_dir)
+      .await
+      .map(|(_, status)| status);
+  }
+  let mut seen = HashSet::from([(root.job.clone(), root.number)]);
+  let mut active = vec![root.clone()];
+  let mut streams = FuturesUnordered::new();
+  streams.push(node_stream(jenkins, root, colorizer, answers, output, log_dir));
+  let mut interval = time::interval(DOWNSTREAM_POLL_INTERVAL);
+  let mut status = BuildStatus::Success;
+  loop {
+    // Decided first and handled after, so the handlers are free to add to
+    // `streams`.
+    let finished = tokio::select! {
+      finished = streams.next() => match finished {
+        Some(finished) => Some(finished?),
+        None => break,
+      },
+      _ = interval.tick() => None,
+    };
+    let parents = match finished {
+      Some((node, node_status)) => {
+        status = status.worst(node_status);
+        active.retain(|n| n.label != node.label);
+        // A last look catches builds triggered just before the parent ended.
+        vec![node]
+      }
+      None => active.clone(),
+    };
+    for parent in parents {
+      for child in children_new(jenkins, &parent, &mut seen).await {
+        report(&parent, colorizer, &child)?;
+        active.push(child.clone());
+        streams.push(node_stream(
+          jenkins, child, colorizer, answers, output, log_dir,
+        ));
+      }
+    }
+  }
+  Ok(status)
+}
+
+// Every build downstream of `root`, recursively, parents before children.
+pub async fn build_tree(
+  jenkins: &JenkinsClient,
+  root: &BuildNode,
+) -> Vec<BuildNode> {
+  let mut seen = HashSet::from([(root.job.clone(), root.number)]);
+  let mut tree = Vec::new();
+  let mut pending = vec![root.clone()];
+  while let Some(parent) = pending.pop() {
+    let children = children_new(jenkins, &parent, &mut seen).await;
+    tree.extend(children.iter().cloned());
+    pending.extend(children.into_iter().rev());
+  }
+  tree
+}
+
+async fn node_stream(
+  jenkins: &JenkinsClient,
+  node: BuildNode,
+  colorizer: &HashColorizer,
+  answers: &InputAnswers,
+  output: OutputFormat,
+  log_dir: Option<&Path>,
+) -> Result<(BuildNode, BuildStatus), AppError> {
+  info!(label = node.label, "Streaming build log");
+  let sink: Box<dyn LogSink> = if output.is_text() {
+    let prefix = format!("[{}] ", colorizer.colorize(&node.label));
+    Box::new(StdoutSink::new(prefix))
+  } else {
+    event_write(&BuildEvent::Started {
+      job: node.job.to_string(),
+      build: node.number,
+      url: &node.url,
+    })?;
+    Box::new(EventSink::new(node.job.to_string(), node.number))
+  };
+  let mut sink: Box<dyn LogSink> = match log_dir {
+    Some(dir) => {
+      let path = dir.join(log_file_name(&node));
+      let file = FileSink::create(path.clone())
+        .map_err(|e| AppError::LogFileCreate(path.display().to_string(), e))?;
+      Box::new(TeeSink::new(sink, file))
+    }
+    None => sink,
+  };
+  stages::build_log_stream_watched(
+    jenkins,
+    &node.url,
+    &node.label,
+    colorizer,
+    answers,
+    sink.as_mut(),
+  )
+  .await?;
+  let status = jenkins.build_result_wait(&node.url).await?;
+  if !output.is_text() {
+    event_write(&BuildEvent::finished(
+      node.job.to_string(),
+      node.number,
+      &status,
+    ))?;
+  }
+  Ok((node, status))
+}
+
+// `<job>-<number>.log`, with the separators of a job inside folders written
+// as underscores so every build's log lands directly in the log directory.
+fn log_file_name(node: &BuildNode) -> String {
+  format!("{}-{}.log", node.job.segments().join("_"), node.number)
+}
+
+// The builds `parent` triggered that are not in `seen` yet, which they are
+// added to.
+async fn children_new(
+  jenkins: &JenkinsClient,
+  parent: &BuildNode,
+  seen: &mut HashSet<(JobPath, u64)>,
+) -> Vec<BuildNode> {
+  match jenkins.build_downstream(&parent.job, parent.number).await {
+    Ok(downstream) => downstream
+      .into_iter()
+      .filter(|build| seen.insert(build.clone()))
+      .map(|(job, number)| parent.child(jenkins, job, number))
+      .collect(),
+    Err(e) => {
+      warn!("Could not look up builds downstream of {}: {}", parent.label, e);
+      Vec::new()
+    }
+  }
+}
+
+fn report(
+  parent: &BuildNode,
+  colorizer: &HashColorizer,
+  child: &BuildNode,
+) -> Result<(), AppError> {
+  writeln!(
+    std::io::stderr(),
+    "[{}] Following downstream build {} #{}.",
+    colorizer.colorize(&parent.label),
+    child.job,
+    child.number,
+  )
+  .map_err(AppError::DownstreamOutput)
+}
@@ -4,40 +4,60 @@ use thiserror::Error;
 pub enum AppError {
   #[error("Server '{0}' not found in configuration")]
   CliConfigServerMissing(String),
-  #[error("Jenkins build not found in response headers")]
-  JenkinsBuildNotFound,
-  #[error("Failed to parse build text size header")]
-  JenkinsBuildParseTextSize,
-  #[error("Failed to serialize build parameters: {0}")]
-  JenkinsBuildParamSerialize(serde_url_params::Error),
-  #[error("Failed to stream build log: {0}")]
-  JenkinsBuildStream(reqwest_middleware::Error),
-  #[error("Failed to read build response body: {0}")]
-  JenkinsBuildResponseRead(reqwest::Error),
+  #[error("--record and --replay cannot be used together")]
+  CliRecordReplay,
   #[error("Failed to write build output to stdout: {0}")]
   JenkinsBuildOutput(std::io::Error),
-  #[error("Failed to deserialize Jenkins response: {0}")]
-  JenkinsDeserialize(serde_json::Error),
-  #[error("Failed to enqueue Jenkins build: {0}")]
-  JenkinsEnqueue(reqwest_middleware::Error),
-  #[error("Failed to deserialize Jenkins queue response: {0}")]
-  JenkinsEnqueueDeserialize(String),
-  #[error("Failed to parse queue wait duration: {0}")]
-  JenkinsEnqueueSecondsParse(std::num::ParseIntError),
-  #[error("Unrecognized queue wait reason: {0}")]
-  JenkinsEnqueueWait(String),
-  #[error("Failed to parse response header value: {0}")]
-  JenkinsHeader(reqwest::header::ToStrError),
-  #[error("Failed to request Jenkins job builds: {0}")]
-  JenkinsJobBuildsRequest(reqwest_middleware::Error),
-  #[error("Failed to deserialize Jenkins job builds response: {0}")]
-  JenkinsJobBuildsDeserialize(serde_json::Error),
-  #[error("Failed to request Jenkins build detail: {0}")]
-  JenkinsBuildDetailRequest(reqwest_middleware::Error),
-  #[error("Failed to deserialize Jenkins build detail: {0}")]
-  JenkinsBuildDetailDeserialize(serde_json::Error),
-  #[error("Failed to fetch Jenkins build log: {0}")]
-  JenkinsBuildLogFetch(reqwest_middleware::Error),
-  #[error("Failed to read Jenkins build log response: {0}")]
-  JenkinsBuildLogRead(reqwest::Error),
+  #[error("Failed to create build log file '{0}': {1}")]
+  LogFileCreate(String, std::io::Error),
+  #[error("Failed to serialize output as JSON: {0}")]
+  EventSerialize(serde_json::Error),
+  #[error("Failed to write an event to stdout: {0}")]
+  EventOutput(std::io::Error),
+  #[error("No queued items found for job '{0}'")]
+  JenkinsQueueJobEmpty(String),
+  #[error("Failed to write the queue to stdout: {0}")]
+  QueueOutput(std::io::Error),
+  #[error("Failed to report progress to stderr: {0}")]
+  ProgressOutput(std::io::Error),
+  #[error("Failed to write build stop results to stdout: {0}")]
+  BuildStopOutput(std::io::Error),
+  #[error("Failed to report Pipeline stage progress to stderr: {0}")]
+  StageOutput(std::io::Error),
+  #[error("Failed to prompt for a Jenkins input answer: {0}")]
+  InputPrompt(std::io::Error),
+  #[error("Failed to report downstream builds to stderr: {0}")]
+  DownstreamOutput(std::io::Error),
+  #[error("No artifacts match '{0}'")]
+  ArtifactNoMatch(String),
+  #[error(
+    "Artifact path '{0}' would be written outside the download directory"
+  )]
+  ArtifactPath(String),
+  #[error("Failed to write artifact '{0}': {1}")]
+  ArtifactWrite(String, std::io::Error),
+  #[error("Failed to report artifacts: {0}")]
+  ArtifactsOutput(std::io::Error),
+  #[error("Build {0} has no test report")]
+  TestReportMissing(String),
+  #[error("Failed to write test report summary to stdout: {0}")]
+  TestReportOutput(std::io::Error),
+  #[error("Failed to write JUnit file '{0}': {1}")]
+  TestReportJunitWrite(String, std::io::Error),
+  #[error("Failed to read parameter file '{0}': {1}")]
+  ParamFileRead(String, std::io::Error),
+  #[error("Failed to parse parameter file '{0}' as TOML: {1}")]
+  ParamFileToml(String, toml::de::Error),
+  #[error("Failed to parse parameters from {0} as a JSON object: {1}")]
+  ParamFileJson(String, serde_json::Error),
+  #[error("Failed to parse line {1} of parameter file '{0}': {2}")]
+  ParamFileDotenv(String, usize, String),
+  #[error("Parameter '{1}' in {0} must be a string, boolean or number")]
+  ParamValue(String, String),
+  #[error("Failed to read parameters from stdin: {0}")]
+  ParamsStdin(std::io::Error),
+  #[error("Failed to write job parameters to stdout: {0}")]
+  JobParamsOutput(std::io::Error),
+  #[error("{0}")]
+  Jenkins(#[from] jj_lib::error::JenkinsError),
 }
@@ -7,8 +7,8 @@ use tokio::{signal, task::JoinSet, time};
 use tracing::{error, info};
 
 use crate::cli::CliJobFollowValid;
+use crate::downstream::{self, BuildNode};
 use crate::error::AppError;
-use crate::jenkins;
 
 const POLL_INTERVAL: Duration = Duration::from_secs(5);
 
@@ -24,9 +24,7 @@ pub async fn follow_once(
 ) -> Result<BuildExitCode, AppError> {
   let colorizer = build_colorizer();
 
-  let builds =
-    jenkins::jenkins_job_builds(&config.client, &config.server, &config.job)
-      .await?;
+  let builds = config.jenkins.job_builds(&config.job).await?;
   let adopted_info = builds
     .builds
     .iter()
@@ -44,12 +42,7 @@ pub async fn follow_once(
   } else {
     loop {
       time::sleep(POLL_INTERVAL).await;
-      let new_builds = jenkins::jenkins_job_builds(
-        &config.client,
-        &config.server,
-        &config.job,
-      )
-      .await?;
+      let new_builds = config.jenkins.job_builds(&config.job).await?;
       let candidate = new_builds
         .builds
         .iter()
@@ -62,26 +55,17 @@ pub async fn follow_once(
     }
   };
 
-  info!(build_number, "Streaming build log");
-  jenkins::build_log_stream(
-    &config.client,
-    &config.server,
-    build_url,
-    0,
-    build_number,
+  downstream::build_stream(
+    &config.jenkins,
+    BuildNode::root(config.job.clone(), build_number, build_url),
     &colorizer,
+    &config.inputs,
+    config.follow_downstream,
+    config.output,
+    config.log_dir.as_deref(),
   )
-  .await?;
-
-  let final_builds =
-    jenkins::jenkins_job_builds(&config.client, &config.server, &config.job)
-      .await?;
-  let result_str = final_builds
-    .builds
-    .iter()
-    .find(|b| b.number == build_number)
-    .and_then(|b| b.result.as_deref());
-  Ok(jenkins::jenkins_result_to_status(result_str).exit_code())
+  .await
+  .map(|status| status.exit_code())
 }
 
 // Watches the job continuously, streaming logs from every active build until
@@ -95,33 +79,37 @@ pub async fn follow(config: &CliJobFollowValid) -> Result<(), AppError> {
     tokio::select! {
       _ = signal::ctrl_c() => break,
       _ = interval.tick() => {
-        match jenkins::jenkins_job_builds(
-          &config.client,
-          &config.server,
-          &config.job,
+        match config.jenkins.job_builds(&config.job,
         ).await {
           Ok(builds) => {
             for build in builds.builds {
               if build.building && !seen.contains(&build.number) {
                 seen.insert(build.number);
-                let client_clone = config.client.clone();
-                let server_clone = config.server.clone();
+                let jenkins_clone = config.jenkins.clone();
+                let inputs_clone = config.inputs.clone();
+                let follow_downstream = config.follow_downstream;
+                let output = config.output;
+                let log_dir = config.log_dir.clone();
                 let build_number = build.number;
-                let build_url = build.url.clone();
+                let root = BuildNode::root(
+                  config.job.clone(),
+                  build.number,
+                  build.url.clone(),
+                );
                 tasks.spawn(async move {
                   let col = build_colorizer();
-                  info!(build_number, "Streaming build log");
-                  match jenkins::build_log_stream(
-                    &client_clone,
-                    &server_clone,
-                    build_url,
-                    0,
-                    build_number,
+                  match downstream::build_stream(&jenkins_clone, root,
                     &col,
+                    &inputs_clone,
+                    follow_downstream,
+                    output,
+                    log_dir.as_deref(),
                   )
                   .await
                   {
-                    Ok(()) => info!(build_number, "Build stream complete"),
+                    Ok(status) => {
+                      info!(build_number, %status, "Build stream complete")
+                    }
                     Err(e) => {
                       error!(build_number, error = %e, "Build stream error")
                     }
@@ -0,0 +1,259 @@
+// Pipeline `input` steps pause a build until someone proceeds or aborts it.
+// While a build streams, jj watches for pending inputs and answers each one
+// once: from --approve, --reject and --input when given, by prompting on the
+// terminal otherwise, or, with neither available, by saying where the build is waiting
+// and leaving it for someone else.
+use std::collections::{HashMap, HashSet};
+use std::io::{IsTerminal, Write};
+use std::sync::OnceLock;
+
+use jj_lib::jenkins::{InputAnswer, JenkinsClient, JenkinsPendingInput};
+use tokio::sync::{mpsc, watch, Mutex};
+use tracing::*;
+
+use crate::error::AppError;
+
+// Answers given on the command line.
+#[derive(Clone, Debug, Default)]
+pub struct InputAnswers {
+  pub approve: bool,
+  pub reject: bool,
+  pub values: HashMap<String, String>,
+}
+
+// Builds followed side by side may ask at the same time; their prompts take
+// turns on the terminal.
+static PROMPT: Mutex<()> = Mutex::const_new(());
+
+// Lines of stdin, read by a single thread for as long as jj runs.  A prompt
+// left open when its build ends gives up its place without taking a line, so
+// the next prompt gets the next reply.
+static STDIN_LINES: OnceLock<Mutex<StdinLines>> = OnceLock::new();
+
+type StdinLines = mpsc::UnboundedReceiver<std::io::Result<String>>;
+
+pub struct InputWatcher<'a> {
+  answers: &'a InputAnswers,
+  prefix: String,
+  handled: HashSet<String>,
+  // Cleared once the build turns out not to be a Pipeline.
+  enabled: bool,
+  // Set once the build's log has ended.
+  finished: watch::Receiver<bool>,
+}
+
+impl<'a> InputWatcher<'a> {
+  pub fn new(
+    answers: &'a InputAnswers,
+    prefix: String,
+    finished: watch::Receiver<bool>,
+  ) -> Self {
+    InputWatcher {
+      answers,
+      prefix,
+      handled: HashSet::new(),
+      enabled: true,
+      finished,
+    }
+  }
+
+  pub async fn poll(
+    &mut self,
+    jenkins: &JenkinsClient,
+    build_url: &str,
+  ) -> Result<(), AppError> {
+    if !self.enabled {
+      return Ok(());
+    }
+    let pending = match jenkins.inputs_pending(build_url).await {
+      Ok(Some(pending)) => pending,
+      Ok(None) => {
+        debug!("No pending inputs API for '{}'; not watching.", build_url);
+        self.enabled = false;
+        return Ok(());
+      }
+      Err(e) => {
+        warn!("Could not read pending inputs: {}", e);
+        return Ok(());
+      }
+    };
+    for input in pending {
+      if self.handled.insert(input.id.clone()) {
+        self.answer(jenkins, build_url, &input).await?;
+      }
+    }
+    Ok(())
+  }
+
+  async fn answer(
+    &self,
+    jenkins: &JenkinsClient,
+    build_url: &str,
+    input: &JenkinsPendingInput,
+  ) -> Result<(), AppError> {
+    let answer = if self.answers.reject {
+      Some(InputAnswer::Abort)
+    } else if self.answers.approve {
+      Some(InputAnswer::Proceed(self.values_from_flags(input)?))
+    } else if std::io::stdin().is_terminal() {
+      // A prompt still open when the build ends, because the input was
+      // answered elsewhere, is left behind.
+      let mut finished = self.finished.clone();
+      tokio::select! {
+        answer = self.prompt(input) => answer?,
+        _ = finished.wait_for(|finished| *finished) => return Ok(()),
+      }
+    } else {
+      None
+    };
+    let Some(answer) = answer else {
+      return self.report(&format!(
+        "Waiting for input: {} Answer it in Jenkins, or pass --approve or \
+         --reject.",
+        input.message
+      ));
+    };
+    // Someone may have answered or aborted the input in the web UI since it
+    // was listed.  The build goes on either way, and so does the stream.
+    if let Err(e) = jenkins.input_submit(build_url, input, &answer).await {
+      return self
+        .report(&format!("Could not answer input '{}': {}", input.id, e));
+    }
+    self.report(&match answer {
+      InputAnswer::Proceed(_) => {
+        format!("Proceeded past input '{}'.", input.id)
+      }
+      InputAnswer::Abort => format!("Aborted at input '{}'.", input.id),
+    })
+  }
+
+  // Each parameter takes its --input value, falling back to its default.  A
+  // value the parameter cannot take is refused before anything is submitted.
+  // --input names this input does not declare are left for others the build
+  // may stop on.
+  fn values_from_flags(
+    &self,
+    input: &JenkinsPendingInput,
+  ) -> Result<HashMap<String, String>, AppError> {
+    let mut values = HashMap::new();
+    for parameter in &input.inputs {
+      let value = match self.answers.values.get(&parameter.name) {
+        Some(value) => {
+          parameter.value_validate(value)?;
+          value.clone()
+        }
+        None => match parameter.default_string() {
+          Some(default) => default,
+          None => continue,
+        },
+      };
+      values.insert(parameter.name.clone(), value);
+    }
+    Ok(values)
+  }
+
+  // Returns None if stdin closes before the question is answered, leaving the
+  // input pending.
+  async fn prompt(
+    &self,
+    input: &JenkinsPendingInput,
+  ) -> Result<Option<InputAnswer>, AppError> {
+    let _turn = PROMPT.lock().await;
+    self.report(&format!("Input requested: {}", input.message))?;
+    let proceed = input.proceed_text.as_deref().unwrap_or("Proceed");
+    let proceeding = loop {
+      let question = format!("{}{} or abort? [p/a]: ", self.prefix, proceed);
+      let Some(reply) = ask(&question).await? else {
+        return Ok(None);
+      };
+      match reply.to_lowercase().as_str() {
+        "p" | "proceed" | "y" | "yes" => break true,
+        "a" | "abort" | "n" | "no" => break false,
+        _ => continue,
+      }
+    };
+    if !proceeding {
+      return Ok(Some(InputAnswer::Abort));
+    }
+    let mut values = HashMap::new();
+    for parameter in &input.inputs {
+      if let Some(value) = self.answers.values.get(&parameter.name) {
+        parameter.value_validate(value)?;
+        values.insert(parameter.name.clone(), value.clone());
+        continue;
+      }
+      if let Some(description) =
+        parameter.description.as_deref().filter(|d| !d.is_empty())
+      {
+        self.report(description)?;
+      }
+      let default = parameter.default_string();
+      let question = format!(
+        "{}{} ({}{}): ",
+        self.prefix,
+        parameter.name,
+        parameter.kind_short(),
+        default
+          .as_deref()
+          .map_or_else(String::new, |d| format!(", default: {}", d)),
+      );
+      let value = loop {
+        let Some(reply) = ask(&question).await? else {
+          return Ok(None);
+        };
+        let value = if reply.is_empty() {
+          default.clone().unwrap_or_default()
+        } else {
+          reply
+        };
+        match parameter.value_validate(&value) {
+          Ok(()) => break value,
+          Err(e) => self.report(&e.to_string())?,
+        }
+      };
+      values.insert(parameter.name.clone(), value);
+    }
+    Ok(Some(InputAnswer::Proceed(values)))
+  }
+
+  fn report(&self, message: &str) -> Result<(), AppError> {
+    info!("{}", message);
+    writeln!(std::io::stderr(), "{}{}", self.prefix, message)
+      .map_err(AppError::InputPrompt)
+  }
+}
+
+// Asks on stderr and takes a trimmed line from stdin, or None at end of input.
+// Waiting for the line can be cancelled without losing it.
+async fn ask(question: &str) -> Result<Option<String>, AppError> {
+  let mut stderr = std::io::stderr();
+  write!(stderr, "{}", question).map_err(AppError::InputPrompt)?;
+  stderr.flush().map_err(AppError::InputPrompt)?;
+  stdin_lines()
+    .lock()
+    .await
+    .recv()
+    .await
+    .transpose()
+    .map(|line| line.map(|line| line.trim().to_string()))
+    .map_err(AppError::InputPrompt)
+}
+
+// Starts the reader on first use.  It reads on its own thread so that Ctrl+C
+// is still noticed while waiting; a thread left blocked on stdin does not keep
+// jj from exiting.
+fn stdin_lines() -> &'static Mutex<StdinLines> {
+  STDIN_LINES.get_or_init(|| {
+    let (sender, receiver) = mpsc::unbounded_channel();
+    std::thread::spawn(move || {
+      for line in std::io::stdin().lines() {
+        let failed = line.is_err();
+        if sender.send(line).is_err() || failed {
+          break;
+        }
+      }
+      debug!("Stopped reading input replies from stdin.");
+    });
+    Mutex::new(receiver)
+  })
+}
@@ -1,20 +1,28 @@
 //! jj — run Jenkins jobs from the command line.
 //!
 //! The `#[foundation_main]` macro handles CLI parsing, config resolution, and
-//! logging init.  This file dispatches the resolved subcommand to the Jenkins
-//! client, follow modes, and build view.
+//! logging init.  This file dispatches the resolved subcommand to job runs,
+//! follow modes, parameter listing, build view, stop, rebuild, artifacts and
+//! tests, and queue management.
 
+mod artifacts;
 mod cli;
 mod config;
+mod downstream;
 mod error;
 mod follow;
-mod jenkins;
+mod input;
+mod output;
+mod params;
+mod queue;
+mod run;
+mod stages;
+mod stop;
+mod test_report;
 mod view;
 
-use cli::{BuildCommand, CliCommand, JobCommand};
+use cli::{BuildCommand, CliCommand, JobCommand, QueueCommand};
 use config::Config;
-use futures::TryFutureExt;
-use hash_color_lib::{ColorizerOptions, HashColorizer};
 use rust_template_foundation::main as foundation_main;
 use std::process::ExitCode;
 use tracing::info;
@@ -26,28 +34,9 @@ pub async fn main(config: Config) -> Result<ExitCode, error::AppError> {
     CliCommand::Job(job_args) => match &job_args.command {
       JobCommand::Run(args) => {
         let v = cli::cli_job_run_validate(&config, args)?;
-        let colorizer = HashColorizer::new(ColorizerOptions::default());
-        // https://support.cloudbees.com/hc/en-us/articles/360028147532-Get-Build-Number-with-REST-API
-        // The above documentation states that the queue item should be around
-        // for 5 minutes.  We can use that to query to see which build it has
-        // produced, and then use that to poll/watch the build log.
-        jenkins::build_enqueue(&v.client, &v.server, &v.job, &v.params)
-          .and_then(|url| {
-            jenkins::build_queue_item_poll(&v.client, &v.server, url)
-          })
-          .and_then(|(build_url, build_number)| {
-            jenkins::build_log_stream(
-              &v.client,
-              &v.server,
-              build_url,
-              0,
-              build_number,
-              &colorizer,
-            )
-          })
-          .await?;
+        let code = run::job_run(&v).await?;
         info!("Done!");
-        Ok(ExitCode::SUCCESS)
+        Ok(ExitCode::from(code.0 as u8))
       }
       JobCommand::Follow(args) => {
         let v = cli::cli_job_follow_validate(&config, args)?;
@@ -59,11 +48,48 @@ pub async fn main(config: Config) -> Result<ExitCode, error::AppError> {
           Ok(ExitCode::SUCCESS)
         }
       }
+      JobCommand::Params(args) => {
+        let v = cli::cli_job_params_validate(&config, args)?;
+        params::view_params(&v).await?;
+        Ok(ExitCode::SUCCESS)
+      }
     },
     CliCommand::Build(build_args) => match &build_args.command {
       BuildCommand::View(args) => {
         let v = cli::cli_build_view_validate(&config, args)?;
-        view::view_build(&v).await?;
+        let status = view::view_build(&v).await?;
+        Ok(ExitCode::from(status.exit_code().0 as u8))
+      }
+      BuildCommand::Rebuild(args) => {
+        let v = cli::cli_build_rebuild_validate(&config, args)?;
+        let code = run::build_rebuild(&v).await?;
+        Ok(ExitCode::from(code.0 as u8))
+      }
+      BuildCommand::Artifacts(args) => {
+        let v = cli::cli_build_artifacts_validate(&config, args)?;
+        artifacts::build_artifacts(&v).await?;
+        Ok(ExitCode::SUCCESS)
+      }
+      BuildCommand::Tests(args) => {
+        let v = cli::cli_build_tests_validate(&config, args)?;
+        test_report::build_tests(&v).await?;
+        Ok(ExitCode::SUCCESS)
+      }
+      BuildCommand::Stop(args) => {
+        let v = cli::cli_build_stop_validate(&config, args)?;
+        let code = stop::build_stop(&v).await?;
+        Ok(ExitCode::from(code.0 as u8))
+      }
+    },
+    CliCommand::Queue(queue_args) => match &queue_args.command {
+      QueueCommand::List(args) => {
+        let v = cli::cli_queue_list_validate(&config, args)?;
+        queue::queue_list(&v).await?;
+        Ok(ExitCode::SUCCESS)
+      }
+      QueueCommand::Cancel(args) => {
+        let v = cli::cli_queue_cancel_validate(&config, args)?;
+        queue::queue_cancel(&v).await?;
         Ok(ExitCode::SUCCESS)
       }
     },
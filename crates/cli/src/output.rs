@@ -0,0 +1,148 @@
+// Machine-readable output, chosen with the global --output flag.  `build view`
+// and `build tests` print a single JSON object.  Commands that list things
+// (`job params`, `queue list`, `queue cancel`, `build artifacts` and
+// `build stop`) print a JSON array under json and one object per line under
+// ndjson.  Commands that stream builds (`job run`,
+// `job follow`, and `build rebuild`) print newline-delimited JSON events
+// instead of prefixed log lines, with every event after `queued` tagged with
+// the job and build number it belongs to.  Progress and prompts stay on stderr
+// in every format, so stdout carries nothing but the events.
+use std::io::Write;
+
+use clap::ValueEnum;
+use jj_lib::build::BuildStatus;
+use jj_lib::sink::LogSink;
+use serde::{Deserialize, Serialize};
+use tracing::info;
+
+use crate::error::AppError;
+
+#[derive(
+  Clone, Copy, Debug, Default, Deserialize, Eq, PartialEq, ValueEnum,
+)]
+#[serde(rename_all = "lowercase")]
+pub enum OutputFormat {
+  /// Human-readable text.
+  #[default]
+  Text,
+  /// A JSON object, or one JSON event per line for streaming commands.
+  Json,
+  /// One JSON value per line.
+  Ndjson,
+}
+
+impl OutputFormat {
+  pub fn is_text(self) -> bool {
+    self == OutputFormat::Text
+  }
+}
+
+#[derive(Debug, Serialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum BuildEvent<'a> {
+  // The build has no number until it leaves the queue, so the queue item
+  // stands in for it.
+  Queued {
+    job: String,
+    queue_id: u32,
+  },
+  Started {
+    job: String,
+    build: u64,
+    url: &'a str,
+  },
+  Log {
+    job: String,
+    build: u64,
+    line: &'a str,
+  },
+  Finished {
+    job: String,
+    build: u64,
+    result: String,
+    exit_code: i32,
+  },
+}
+
+impl BuildEvent<'_> {
+  pub fn finished(job: String, build: u64, status: &BuildStatus) -> Self {
+    BuildEvent::Finished {
+      job,
+      build,
+      result: status.to_string(),
+      exit_code: status.exit_code().0,
+    }
+  }
+}
+
+// Writes a command's single result: pretty-printed under json, on one line
+// under ndjson.
+pub fn json_write<T: Serialize>(
+  output: OutputFormat,
+  value: &T,
+  output_error: fn(std::io::Error) -> AppError,
+) -> Result<(), AppError> {
+  let json = if output == OutputFormat::Ndjson {
+    serde_json::to_string(value)
+  } else {
+    serde_json::to_string_pretty(value)
+  }
+  .map_err(AppError::EventSerialize)?;
+  writeln!(std::io::stdout().lock(), "{}", json).map_err(output_error)
+}
+
+// Writes a command's list of results: as one array under json, or one item
+// per line under ndjson.
+pub fn json_list_write<T: Serialize>(
+  output: OutputFormat,
+  items: &[T],
+  output_error: fn(std::io::Error) -> AppError,
+) -> Result<(), AppError> {
+  if output != OutputFormat::Ndjson {
+    return json_write(output, &items, output_error);
+  }
+  let stdout = std::io::stdout();
+  let mut out = stdout.lock();
+  items.iter().try_for_each(|item| {
+    let line = serde_json::to_string(item).map_err(AppError::EventSerialize)?;
+    writeln!(out, "{}", line).map_err(output_error)
+  })
+}
+
+// Progress is reported on stderr so it never mixes into a build log, or the
+// events standing in for one, on stdout.
+pub fn report(message: &str) -> Result<(), AppError> {
+  info!("{}", message);
+  writeln!(std::io::stderr(), "{}", message).map_err(AppError::ProgressOutput)
+}
+
+// Writes one event as a line of JSON.  The line is written under a single
+// stdout lock so events from builds streamed side by side never interleave.
+pub fn event_write(event: &BuildEvent) -> Result<(), AppError> {
+  let line = serde_json::to_string(event).map_err(AppError::EventSerialize)?;
+  writeln!(std::io::stdout().lock(), "{}", line).map_err(AppError::EventOutput)
+}
+
+// Sends a streamed build's log lines out as `log` events.
+#[derive(Clone, Debug)]
+pub struct EventSink {
+  job: String,
+  build: u64,
+}
+
+impl EventSink {
+  pub fn new(job: String, build: u64) -> Self {
+    EventSink { job, build }
+  }
+}
+
+impl LogSink for EventSink {
+  fn line_write(&mut self, line: &str) -> std::io::Result<()> {
+    let event = BuildEvent::Log {
+      job: self.job.clone(),
+      build: self.build,
+      line,
+    };
+    writeln!(std::io::stdout().lock(), "{}", serde_json::to_string(&event)?)
+  }
+}
@@ -0,0 +1,179 @@
+use std::collections::HashMap;
+use std::io::{Read, Write};
+use std::path::Path;
+
+use serde::Serialize;
+
+use crate::cli::CliJobParamsValid;
+use crate::error::AppError;
+use crate::output::json_list_write;
+
+// Prints one block per parameter: its name, type, and default on the first
+// line, then the description (if any) indented beneath.  Choice parameters list
+// their allowed values in place of the bare type.
+pub async fn view_params(config: &CliJobParamsValid) -> Result<(), AppError> {
+  let job = config.jenkins.job_get(&config.job).await?;
+  let definitions = job.parameter_definitions();
+  if !config.output.is_text() {
+    let params = definitions
+      .iter()
+      .map(|d| ParamJson {
+        name: d.name.clone(),
+        kind: d.kind_short(),
+        default: d.default_string(),
+        choices: d.choices.clone(),
+        description: d.description.clone().filter(|desc| !desc.is_empty()),
+      })
+      .collect::<Vec<ParamJson>>();
+    return json_list_write(config.output, &params, AppError::JobParamsOutput);
+  }
+  let stdout = std::io::stdout();
+  let mut out = stdout.lock();
+  if definitions.is_empty() {
+    return writeln!(out, "Job '{}' takes no parameters.", config.job)
+      .map_err(AppError::JobParamsOutput);
+  }
+  definitions.iter().try_for_each(|d| {
+    let kind = d.choices.as_ref().map_or_else(
+      || d.kind_short(),
+      |choices| format!("{}: {}", d.kind_short(), choices.join("|")),
+    );
+    let default = d
+      .default_string()
+      .map_or_else(String::new, |v| format!(", default: {}", v));
+    writeln!(out, "{} ({}{})", d.name, kind, default)
+      .map_err(AppError::JobParamsOutput)?;
+    d.description
+      .as_deref()
+      .filter(|desc| !desc.is_empty())
+      .map_or(Ok(()), |desc| {
+        writeln!(out, "  {}", desc).map_err(AppError::JobParamsOutput)
+      })
+  })
+}
+
+// One parameter in the JSON form of `job params`.
+#[derive(Serialize)]
+struct ParamJson {
+  name: String,
+  #[serde(rename = "type")]
+  kind: String,
+  default: Option<String>,
+  #[serde(skip_serializing_if = "Option::is_none")]
+  choices: Option<Vec<String>>,
+  description: Option<String>,
+}
+
+// Reads a file of parameter values, by its extension: TOML for .toml, JSON for
+// .json, and dotenv (NAME=value lines) for anything else, such as .env.
+pub fn param_file_read(
+  path: &Path,
+) -> Result<HashMap<String, String>, AppError> {
+  let name = path.display().to_string();
+  let text = std::fs::read_to_string(path)
+    .map_err(|e| AppError::ParamFileRead(name.clone(), e))?;
+  match path.extension().and_then(|e| e.to_str()) {
+    Some("toml") => toml::from_str::<HashMap<String, toml::Value>>(&text)
+      .map_err(|e| AppError::ParamFileToml(name.clone(), e))?
+      .into_iter()
+      .map(|(key, value)| match value {
+        toml::Value::String(s) => Ok((key, s)),
+        toml::Value::Boolean(_)
+        | toml::Value::Integer(_)
+        | toml::Value::Float(_) => Ok((key, value.to_string())),
+        _ => Err(AppError::ParamValue(name.clone(), key)),
+      })
+      .collect(),
+    Some("json") => params_json(&name, &text),
+    _ => params_dotenv(&name, &text),
+  }
+}
+
+// Reads a JSON object of parameter values from stdin.
+pub fn params_stdin_read() -> Result<HashMap<String, String>, AppError> {
+  let mut text = String::new();
+  std::io::stdin()
+    .read_to_string(&mut text)
+    .map_err(AppError::ParamsStdin)?;
+  params_json("stdin", &text)
+}
+
+fn params_json(
+  source: &str,
+  text: &str,
+) -> Result<HashMap<String, String>, AppError> {
+  serde_json::from_str::<HashMap<String, serde_json::Value>>(text)
+    .map_err(|e| AppError::ParamFileJson(source.to_string(), e))?
+    .into_iter()
+    .map(|(key, value)| match value {
+      serde_json::Value::String(s) => Ok((key, s)),
+      serde_json::Value::Bool(_) | serde_json::Value::Number(_) => {
+        Ok((key, value.to_string()))
+      }
+      _ => Err(AppError::ParamValue(source.to_string(), key)),
+    })
+    .collect()
+}
+
+// One NAME=value per line, optionally after `export`.  Blank lines and lines
+// starting with # are skipped.  A value in double quotes may use \n, \t, \"
+// and \\ escapes, which is how a multi-line value is written; one in single
+// quotes is taken literally; an unquoted one is trimmed.
+fn params_dotenv(
+  source: &str,
+  text: &str,
+) -> Result<HashMap<String, String>, AppError> {
+  text
+    .lines()
+    .enumerate()
+    .map(|(number, line)| (number + 1, line.trim()))
+    .filter(|(_, line)| !line.is_empty() && !line.starts_with('#'))
+    .map(|(number, line)| {
+      let error = |reason: &str| {
+        AppError::ParamFileDotenv(source.to_string(), number, reason.into())
+      };
+      let line = line.strip_prefix("export ").unwrap_or(line);
+      let (key, value) = line
+        .split_once('=')
+        .ok_or_else(|| error("expected NAME=value"))?;
+      let value = value.trim();
+      let value = value
+        .strip_prefix('"')
+        .and_then(|v| v.strip_suffix('"'))
+        .map_or_else(
+          || {
+            Ok(
+              value
+                .strip_prefix('\'')
+                .and_then(|v| v.strip_suffix('\''))
+                .unwrap_or(value)
+                .to_string(),
+            )
+          },
+          |quoted| {
+            dotenv_unescape(quoted).ok_or_else(|| error("unknown escape"))
+          },
+        )?;
+      Ok((key.trim().to_string(), value))
+    })
+    .collect()
+}
+
+fn dotenv_unescape(quoted: &str) -> Option<String> {
+  let mut unescaped = String::with_capacity(quoted.len());
+  let mut chars = quoted.chars();
+  while let Some(c) = chars.next() {
+    if c != '\\' {
+      unescaped.push(c);
+      continue;
+    }
+    unescaped.push(match chars.next()? {
+      'n' => '\n',
+      't' => '\t',
+      '"' => '"',
+      '\\' => '\\',
+      _ => return None,
+    });
+  }
+  Some(unescaped)
+}
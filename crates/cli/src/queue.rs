@@ -0,0 +1,165 @@
+use jj_lib::jenkins::JenkinsQueueItem;
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::io::Write;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use crate::cli::{CliQueueCancelValid, CliQueueListValid, QueueTarget};
+use crate::error::AppError;
+use crate::output::json_list_write;
+use crate::view::format_duration_ms;
+
+// One item in the JSON form of `queue list`.
+#[derive(Serialize)]
+struct QueueItemJson {
+  id: u32,
+  job: String,
+  in_queue_ms: u64,
+  params: BTreeMap<String, String>,
+  why: Option<String>,
+}
+
+// One item in the JSON form of `queue cancel`.
+#[derive(Serialize)]
+struct QueueCancelledJson {
+  queue_id: u32,
+}
+
+// The item's job path when it has one, otherwise whatever name its task goes
+// by, so placeholder tasks still show something recognizable.
+fn item_job_label(item: &JenkinsQueueItem) -> Result<String, AppError> {
+  item.job().map_err(AppError::Jenkins).map(|job| {
+    job.map_or_else(
+      || {
+        item
+          .task
+          .name
+          .clone()
+          .unwrap_or_else(|| item.task.class.clone())
+      },
+      |job| job.to_string(),
+    )
+  })
+}
+
+// Jenkins renders parameters as newline-separated name=value pairs with a
+// leading newline.
+fn item_params(item: &JenkinsQueueItem) -> impl Iterator<Item = &str> {
+  item.params.split('\n').filter(|p| !p.is_empty())
+}
+
+fn item_params_label(item: &JenkinsQueueItem) -> String {
+  item_params(item).collect::<Vec<&str>>().join(" ")
+}
+
+fn now_ms() -> u64 {
+  SystemTime::now()
+    .duration_since(UNIX_EPOCH)
+    .unwrap_or(Duration::ZERO)
+    .as_millis() as u64
+}
+
+fn item_matches(
+  item: &JenkinsQueueItem,
+  config: &CliQueueListValid,
+) -> Result<bool, AppError> {
+  let job_matches = match &config.job {
+    Some(job) => item.job()?.as_ref() == Some(job),
+    None => true,
+  };
+  let user_matches = config
+    .user
+    .as_ref()
+    .is_none_or(|user| item.user_ids().contains(&user.as_str()));
+  Ok(job_matches && user_matches)
+}
+
+// Prints one line per queued item: id, job, time spent in the queue,
+// parameters, and Jenkins' explanation of why it is still waiting.
+pub async fn queue_list(config: &CliQueueListValid) -> Result<(), AppError> {
+  let items = config.jenkins.queue_items().await?;
+  let now = now_ms();
+  let matching = items
+    .iter()
+    .map(|item| item_matches(item, config).map(|matches| (item, matches)))
+    .collect::<Result<Vec<_>, AppError>>()?
+    .into_iter()
+    .filter(|(_, matches)| *matches)
+    .map(|(item, _)| item)
+    .collect::<Vec<&JenkinsQueueItem>>();
+  if !config.output.is_text() {
+    let items = matching
+      .iter()
+      .map(|item| {
+        Ok(QueueItemJson {
+          id: item.id,
+          job: item_job_label(item)?,
+          in_queue_ms: now.saturating_sub(item.in_queue_since),
+          params: item_params(item)
+            .map(|p| {
+              p.split_once('=').map_or_else(
+                || (p.to_string(), String::new()),
+                |(name, value)| (name.to_string(), value.to_string()),
+              )
+            })
+            .collect(),
+          why: item.why.clone(),
+        })
+      })
+      .collect::<Result<Vec<QueueItemJson>, AppError>>()?;
+    return json_list_write(config.output, &items, AppError::QueueOutput);
+  }
+  let stdout = std::io::stdout();
+  let mut out = stdout.lock();
+  matching.into_iter().try_for_each(|item| {
+    writeln!(
+      out,
+      "{:<8} {:<30} {:>8}  {}  {}",
+      item.id,
+      item_job_label(item)?,
+      format_duration_ms(now.saturating_sub(item.in_queue_since)),
+      item_params_label(item),
+      item.why.as_deref().unwrap_or(""),
+    )
+    .map_err(AppError::QueueOutput)
+  })
+}
+
+pub async fn queue_cancel(
+  config: &CliQueueCancelValid,
+) -> Result<(), AppError> {
+  let ids = match &config.target {
+    QueueTarget::Id(id) => vec![*id],
+    QueueTarget::Job(job) => {
+      let items = config.jenkins.queue_items().await?;
+      let ids = items
+        .iter()
+        .map(|item| item.job().map(|j| (item.id, j)))
+        .collect::<Result<Vec<_>, _>>()?
+        .into_iter()
+        .filter(|(_, j)| j.as_ref() == Some(job))
+        .map(|(id, _)| id)
+        .collect::<Vec<u32>>();
+      if ids.is_empty() {
+        return Err(AppError::JenkinsQueueJobEmpty(job.to_string()));
+      }
+      ids
+    }
+  };
+  let stdout = std::io::stdout();
+  for id in &ids {
+    config.jenkins.queue_item_cancel(*id).await?;
+    if config.output.is_text() {
+      writeln!(stdout.lock(), "Cancelled queue item {}.", id)
+        .map_err(AppError::QueueOutput)?;
+    }
+  }
+  if config.output.is_text() {
+    return Ok(());
+  }
+  let cancelled = ids
+    .into_iter()
+    .map(|queue_id| QueueCancelledJson { queue_id })
+    .collect::<Vec<QueueCancelledJson>>();
+  json_list_write(config.output, &cancelled, AppError::QueueOutput)
+}
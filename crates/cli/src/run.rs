@@ -0,0 +1,167 @@
+use std::collections::HashMap;
+use std::time::Duration;
+
+use hash_color_lib::{ColorizerOptions, HashColorizer};
+use jj_lib::build::BuildExitCode;
+use jj_lib::jenkins::{self, QueueItemState};
+use tokio::signal;
+
+use crate::cli::{CliBuildRebuildValid, CliJobRunValid, InterruptPolicy};
+use crate::downstream::{self, BuildNode};
+use crate::error::AppError;
+use crate::output::{event_write, report, BuildEvent};
+use crate::stop;
+
+// 128 + SIGINT, which is how shells report a command ended by Ctrl+C.
+const INTERRUPTED: BuildExitCode = BuildExitCode(130);
+
+// How long an aborted build gets to finish after each of stop, term, and kill.
+const ABORT_ESCALATION_TIMEOUT: Duration = Duration::from_secs(10);
+
+// Enqueues the job, waits for it to leave the queue, and streams its log to
+// completion.  Returns the build's result code, or the outcome of the
+// interrupt policy if Ctrl+C arrives first.
+pub async fn job_run(
+  config: &CliJobRunValid,
+) -> Result<BuildExitCode, AppError> {
+  let colorizer = HashColorizer::new(ColorizerOptions::default());
+  let definition = config.jenkins.job_get(&config.job).await?;
+  jenkins::params_validate(
+    &config.job,
+    &definition.parameter_definitions(),
+    &config.params,
+  )?;
+  jenkins::file_params_validate(
+    &config.job,
+    &definition.parameter_definitions(),
+    &config.files,
+  )?;
+  // https://support.cloudbees.com/hc/en-us/articles/360028147532-Get-Build-Number-with-REST-API
+  // The above documentation states that the queue item should be around
+  // for 5 minutes.  We can use that to query to see which build it has
+  // produced, and then use that to poll/watch the build log.
+  let item_url = config
+    .jenkins
+    .build_enqueue(&config.job, &definition, &config.params, &config.files)
+    .await?;
+  if !config.output.is_text() {
+    event_write(&BuildEvent::Queued {
+      job: config.job.to_string(),
+      queue_id: jenkins::queue_item_id(&item_url)?,
+    })?;
+  }
+
+  // One listener for the whole run, so a Ctrl+C that lands between leaving
+  // the queue and starting the stream is not lost.
+  let ctrl_c = signal::ctrl_c();
+  tokio::pin!(ctrl_c);
+  let build = tokio::select! {
+    started = config.jenkins.build_queue_item_poll(item_url.clone()) => {
+      started?
+    }
+    _ = &mut ctrl_c => {
+      return interrupt_queued(config, &item_url).await;
+    }
+  };
+
+  tokio::select! {
+    streamed = downstream::build_stream(
+      &config.jenkins,
+      BuildNode::root(config.job.clone(), build.number, build.url.clone()),
+      &colorizer,
+      &config.inputs,
+      config.follow_downstream,
+      config.output,
+      None,
+    ) => streamed.map(|status| status.exit_code()),
+    _ = &mut ctrl_c => {
+      interrupt_running(config, &build.url, build.number).await
+    }
+  }
+}
+
+// Runs the job again with the parameters the given build ran with, overridden
+// by any the caller supplied, and otherwise exactly as job_run does.
+pub async fn build_rebuild(
+  config: &CliBuildRebuildValid,
+) -> Result<BuildExitCode, AppError> {
+  let original = config
+    .jenkins
+    .build_detail_get(&config.job, config.build_number)
+    .await?;
+  original
+    .parameters_unexported()
+    .into_iter()
+    .filter(|name| !config.overrides.contains_key(*name))
+    .try_for_each(|name| {
+      report(&format!(
+        "Build #{} did not export parameter '{}'; using the job's default.",
+        config.build_number, name
+      ))
+    })?;
+  let mut params = original.parameters();
+  params.extend(config.overrides.clone());
+  job_run(&CliJobRunValid {
+    jenkins: config.jenkins.clone(),
+    job: config.job.clone(),
+    params,
+    // Jenkins keeps no copy of an uploaded file to send again.
+    files: HashMap::new(),
+    on_interrupt: config.on_interrupt,
+    inputs: config.inputs.clone(),
+    follow_downstream: config.follow_downstream,
+    output: config.output,
+  })
+  .await
+}
+
+async fn interrupt_queued(
+  config: &CliJobRunValid,
+  item_url: &str,
+) -> Result<BuildExitCode, AppError> {
+  if config.on_interrupt == InterruptPolicy::Detach {
+    report(&format!("Detached; queue item {} is still queued.", item_url))?;
+    return Ok(INTERRUPTED);
+  }
+  let id = jenkins::queue_item_id(item_url)?;
+  config.jenkins.queue_item_cancel(id).await?;
+  // The item may have left the queue between the interrupt and the cancel, in
+  // which case the cancel was a no-op and the build is already running.
+  let item = config
+    .jenkins
+    .build_queue_item_get(item_url.to_string())
+    .await?;
+  if let QueueItemState::Started { url, number } = item.state() {
+    report(&format!(
+      "Queue item {} had already started build #{}.",
+      id, number
+    ))?;
+    return interrupt_running(config, &url, number).await;
+  }
+  report(&format!("Cancelled queue item {}.", id))?;
+  Ok(INTERRUPTED)
+}
+
+async fn interrupt_running(
+  config: &CliJobRunValid,
+  build_url: &str,
+  build_number: u64,
+) -> Result<BuildExitCode, AppError> {
+  match config.on_interrupt {
+    InterruptPolicy::Detach | InterruptPolicy::Cancel => {
+      report(&format!(
+        "Detached; build #{} is still running at {}",
+        build_number, build_url
+      ))?;
+      Ok(INTERRUPTED)
+    }
+    InterruptPolicy::Abort => stop::build_stop_reported(
+      &config.jenkins,
+      build_url,
+      build_number,
+      ABORT_ESCALATION_TIMEOUT,
+    )
+    .await
+    .map(|status| status.exit_code()),
+  }
+}
@@ -0,0 +1,167 @@
+// Pipeline stage progress, read from the Pipeline Stage View plugin's wfapi.
+// The log alone makes it hard to see where a long pipeline is, so while a
+// build streams, a line goes to stderr whenever a stage starts or finishes.
+// Stage reporting is a convenience: failing to read the stages is logged and
+// never interrupts the log stream.  The same polling drives the answering of
+// input steps (see input.rs).
+use std::collections::HashMap;
+use std::io::Write;
+use std::time::Duration;
+
+use hash_color_lib::HashColorizer;
+use jj_lib::jenkins::{
+  JenkinsClient, JenkinsWorkflowRun, JenkinsWorkflowStage,
+};
+use jj_lib::sink::LogSink;
+use tokio::sync::watch;
+use tokio::time;
+use tracing::*;
+
+use crate::error::AppError;
+use crate::input::{InputAnswers, InputWatcher};
+use crate::view::format_duration_ms;
+
+const STAGE_POLL_INTERVAL: Duration = Duration::from_secs(3);
+
+// One row of the stage table in `build view`: name, status, start offset from
+// the start of the build, and duration.
+pub fn stage_line(
+  stage: &JenkinsWorkflowStage,
+  run: &JenkinsWorkflowRun,
+) -> String {
+  format!(
+    "{:<24} {:<20} +{:<8} {}",
+    stage.name,
+    stage.status,
+    format_duration_ms(
+      stage
+        .start_time_millis
+        .saturating_sub(run.start_time_millis)
+    ),
+    format_duration_ms(stage.duration_millis),
+  )
+}
+
+// Remembers the last status seen for each stage so only transitions are
+// reported.
+struct StageTracker {
+  prefix: String,
+  statuses: HashMap<String, String>,
+  // Cleared once the build turns out not to be a Pipeline, so it is not asked
+  // again on every poll.
+  enabled: bool,
+}
+
+impl StageTracker {
+  fn new(prefix: String) -> Self {
+    StageTracker {
+      prefix,
+      statuses: HashMap::new(),
+      enabled: true,
+    }
+  }
+
+  async fn poll(
+    &mut self,
+    jenkins: &JenkinsClient,
+    build_url: &str,
+  ) -> Result<(), AppError> {
+    if !self.enabled {
+      return Ok(());
+    }
+    match jenkins.workflow_describe(build_url).await {
+      Ok(Some(run)) => self.report(&run),
+      Ok(None) => {
+        debug!("No Pipeline stages for '{}'; not tracking them.", build_url);
+        self.enabled = false;
+        Ok(())
+      }
+      Err(e) => {
+        warn!("Could not read Pipeline stages: {}", e);
+        Ok(())
+      }
+    }
+  }
+
+  fn report(&mut self, run: &JenkinsWorkflowRun) -> Result<(), AppError> {
+    let stderr = std::io::stderr();
+    let mut out = stderr.lock();
+    for stage in &run.stages {
+      let previous =
+        self.statuses.insert(stage.id.clone(), stage.status.clone());
+      if previous.as_deref() == Some(stage.status.as_str()) {
+        continue;
+      }
+      let message = if stage.is_active() {
+        if previous.is_some() {
+          // e.g. resumed after input; the start was already reported.
+          continue;
+        }
+        format!("Stage '{}' started.", stage.name)
+      } else {
+        format!(
+          "Stage '{}' finished: {} ({}).",
+          stage.name,
+          stage.status,
+          format_duration_ms(stage.duration_millis),
+        )
+      };
+      writeln!(out, "{}{}", self.prefix, message)
+        .map_err(AppError::StageOutput)?;
+    }
+    Ok(())
+  }
+}
+
+// Streams the build's log into `sink` with JenkinsClient::build_log_to,
+// reporting stage transitions on stderr alongside it and answering any input
+// steps the build stops on.  The watching runs concurrently with the stream,
+// so the log keeps flowing through a slow poll or an unanswered prompt.  When
+// the stream ends, the watching finishes the round it is in, so an answer
+// already on its way to Jenkins is still reported.
+pub async fn build_log_stream_watched(
+  jenkins: &JenkinsClient,
+  build_url: &str,
+  label: &str,
+  colorizer: &HashColorizer,
+  answers: &InputAnswers,
+  sink: &mut dyn LogSink,
+) -> Result<(), AppError> {
+  let prefix = format!("[{}] ", colorizer.colorize(label));
+  let mut tracker = StageTracker::new(prefix.clone());
+  let (finish, finished) = watch::channel(false);
+  let mut inputs = InputWatcher::new(answers, prefix, finished.clone());
+  let streamed = async {
+    jenkins.build_log_to(build_url, 0, sink).await?;
+    finish.send_replace(true);
+    Ok::<(), AppError>(())
+  };
+  tokio::try_join!(
+    streamed,
+    watch(jenkins, build_url, &mut tracker, &mut inputs, finished),
+  )?;
+  // The last stage usually finishes between the final poll and the end of the
+  // log.
+  tracker.poll(jenkins, build_url).await
+}
+
+// Polls stages and inputs until the stream it runs beside has ended.  Only an
+// error ends it sooner.
+async fn watch(
+  jenkins: &JenkinsClient,
+  build_url: &str,
+  tracker: &mut StageTracker,
+  inputs: &mut InputWatcher<'_>,
+  mut finished: watch::Receiver<bool>,
+) -> Result<(), AppError> {
+  let mut interval = time::interval(STAGE_POLL_INTERVAL);
+  loop {
+    // A stream that failed drops its sender, which ends the wait too.
+    tokio::select! {
+      _ = interval.tick() => {}
+      _ = finished.wait_for(|finished| *finished) => return Ok(()),
+    }
+    tracker.poll(jenkins, build_url).await?;
+    inputs.poll(jenkins, build_url).await?;
+  }
+}
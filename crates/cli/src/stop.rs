@@ -0,0 +1,106 @@
+use std::io::Write;
+
+use jj_lib::build::{BuildExitCode, BuildStatus};
+use jj_lib::jenkins::{BuildStopRequest, JenkinsClient};
+use serde::Serialize;
+
+use crate::cli::{BuildTarget, CliBuildStopValid};
+use crate::error::AppError;
+use crate::output::{json_list_write, report};
+
+// One stopped build in the JSON form of `build stop`.
+#[derive(Serialize)]
+struct StoppedJson {
+  job: String,
+  build: u64,
+  result: String,
+  exit_code: i32,
+}
+
+fn report_stop_request(
+  build_number: u64,
+  request: BuildStopRequest,
+) -> Result<(), AppError> {
+  report(&match request {
+    BuildStopRequest::Stop => format!("Aborting build #{}...", build_number),
+    BuildStopRequest::Term => {
+      format!("Build #{} did not stop; terminating...", build_number)
+    }
+    BuildStopRequest::Kill => {
+      format!("Build #{} did not terminate; killing...", build_number)
+    }
+  })
+}
+
+// Stops the build with stop/term/kill escalation, reporting each step and the
+// final status on stderr.
+pub async fn build_stop_reported(
+  jenkins: &JenkinsClient,
+  build_url: &str,
+  build_number: u64,
+  timeout: std::time::Duration,
+) -> Result<BuildStatus, AppError> {
+  let status = jenkins
+    .build_stop(build_url, timeout, |request| {
+      report_stop_request(build_number, request)
+    })
+    .await?;
+  report(&format!("Build #{} ended as {}.", build_number, status))?;
+  Ok(status)
+}
+
+// Stops the targeted builds one after another.  Exits with the code of the
+// worst result among them, by Jenkins' ordering of results, so a single build
+// stops with its own code and a batch reports the most notable outcome.
+pub async fn build_stop(
+  config: &CliBuildStopValid,
+) -> Result<BuildExitCode, AppError> {
+  let targets = match config.target {
+    BuildTarget::Number(number) => {
+      vec![(number, config.jenkins.build_url(&config.job, number))]
+    }
+    BuildTarget::AllRunning => config
+      .jenkins
+      .job_builds(&config.job)
+      .await?
+      .builds
+      .into_iter()
+      .filter(|b| b.building)
+      .map(|b| (b.number, b.url))
+      .collect(),
+  };
+  if targets.is_empty() {
+    report(&format!("No running builds of job '{}'.", config.job))?;
+    if !config.output.is_text() {
+      json_list_write::<StoppedJson>(
+        config.output,
+        &[],
+        AppError::BuildStopOutput,
+      )?;
+    }
+    return Ok(BuildStatus::Success.exit_code());
+  }
+  let stdout = std::io::stdout();
+  let mut worst = BuildStatus::Success;
+  let mut stopped = Vec::new();
+  for (number, url) in targets {
+    let status =
+      build_stop_reported(&config.jenkins, &url, number, config.timeout)
+        .await?;
+    if config.output.is_text() {
+      writeln!(stdout.lock(), "Build #{}     [{}]", number, status)
+        .map_err(AppError::BuildStopOutput)?;
+    }
+    stopped.push(StoppedJson {
+      job: config.job.to_string(),
+      build: number,
+      result: status.to_string(),
+      exit_code: status.exit_code().0,
+    });
+    worst = worst.worst(status);
+  }
+  if !config.output.is_text() {
+    json_list_write(config.output, &stopped, AppError::BuildStopOutput)?;
+  }
+  Ok(worst.exit_code())
+}
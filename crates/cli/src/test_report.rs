@@ -0,0 +1,193 @@
+use jj_lib::jenkins::JenkinsTestReport;
+use serde::Serialize;
+use std::io::Write;
+
+use crate::cli::CliBuildTestsValid;
+use crate::error::AppError;
+use crate::output::json_write;
+use crate::view::format_duration_ms;
+
+// The JSON form of `build tests`.
+#[derive(Serialize)]
+struct TestReportJson<'a> {
+  job: String,
+  // The build as it was asked for: a number or a permalink.
+  build: String,
+  passed: u64,
+  failed: u64,
+  skipped: u64,
+  duration_ms: u64,
+  failures: Vec<TestFailureJson<'a>>,
+}
+
+#[derive(Serialize)]
+struct TestFailureJson<'a> {
+  class_name: &'a str,
+  name: &'a str,
+  status: &'a str,
+  error_details: Option<&'a str>,
+  error_stack_trace: Option<&'a str>,
+}
+
+// Prints the build's pass/fail/skip counts and each failing case with its
+// error, and writes the report out as JUnit XML if asked to.
+pub async fn build_tests(config: &CliBuildTestsValid) -> Result<(), AppError> {
+  let report = config
+    .jenkins
+    .build_test_report_get(&config.job, &config.build)
+    .await?
+    .ok_or_else(|| {
+      AppError::TestReportMissing(format!("{} #{}", config.job, config.build))
+    })?;
+
+  if let Some(path) = &config.junit {
+    std::fs::write(path, junit_xml(&report)).map_err(|e| {
+      AppError::TestReportJunitWrite(path.display().to_string(), e)
+    })?;
+  }
+  if !config.output.is_text() {
+    return json_write(
+      config.output,
+      &TestReportJson {
+        job: config.job.to_string(),
+        build: config.build.to_string(),
+        passed: report.pass_count,
+        failed: report.fail_count,
+        skipped: report.skip_count,
+        duration_ms: seconds_to_ms(report.duration),
+        failures: report
+          .suites
+          .iter()
+          .flat_map(|s| &s.cases)
+          .filter(|case| case.is_failed())
+          .map(|case| TestFailureJson {
+            class_name: &case.class_name,
+            name: &case.name,
+            status: &case.status,
+            error_details: case.error_details.as_deref(),
+            error_stack_trace: case.error_stack_trace.as_deref(),
+          })
+          .collect(),
+      },
+      AppError::TestReportOutput,
+    );
+  }
+
+  let stdout = std::io::stdout();
+  let mut out = stdout.lock();
+  writeln!(
+    out,
+    "{} ({})",
+    summary(report.pass_count, report.fail_count, report.skip_count),
+    format_duration_ms(seconds_to_ms(report.duration)),
+  )
+  .map_err(AppError::TestReportOutput)?;
+  for case in report.suites.iter().flat_map(|s| &s.cases) {
+    if !case.is_failed() {
+      continue;
+    }
+    writeln!(out, "\n{} {}.{}", case.status, case.class_name, case.name)
+      .map_err(AppError::TestReportOutput)?;
+    for line in case.error_details.iter().flat_map(|d| d.lines()) {
+      writeln!(out, "  {}", line).map_err(AppError::TestReportOutput)?;
+    }
+  }
+  Ok(())
+}
+
+pub fn summary(passed: u64, failed: u64, skipped: u64) -> String {
+  format!("Tests: {} passed, {} failed, {} skipped", passed, failed, skipped)
+}
+
+fn seconds_to_ms(seconds: f64) -> u64 {
+  (seconds * 1000.0).round() as u64
+}
+
+// Rebuilds a JUnit XML document from Jenkins' parsed report.  Jenkins keeps
+// everything the usual consumers read: suites, cases with class names and
+// times, failure messages and stack traces, and skips.
+fn junit_xml(report: &JenkinsTestReport) -> String {
+  let mut xml = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+  xml.push_str(&format!(
+    "<testsuites tests=\"{}\" failures=\"{}\" skipped=\"{}\" time=\"{:.3}\">\n",
+    report.pass_count + report.fail_count + report.skip_count,
+    report.fail_count,
+    report.skip_count,
+    report.duration,
+  ));
+  for suite in &report.suites {
+    let failures = suite.cases.iter().filter(|c| c.is_failed()).count();
+    let skipped = suite.cases.iter().filter(|c| c.is_skipped()).count();
+    xml.push_str(&format!(
+      "  <testsuite name=\"{}\" tests=\"{}\" failures=\"{}\" skipped=\"{}\" time=\"{:.3}\">\n",
+      xml_escape(&suite.name),
+      suite.cases.len(),
+      failures,
+      skipped,
+      suite.duration,
+    ));
+    for case in &suite.cases {
+      xml.push_str(&format!(
+        "    <testcase classname=\"{}\" name=\"{}\" time=\"{:.3}\"",
+        xml_escape(&case.class_name),
+        xml_escape(&case.name),
+        case.duration,
+      ));
+      if case.is_failed() {
+        xml.push_str(&format!(
+          ">\n      <failure message=\"{}\">{}</failure>\n    </testcase>\n",
+          xml_escape(case.error_details.as_deref().unwrap_or("")),
+          xml_escape(case.error_stack_trace.as_deref().unwrap_or("")),
+        ));
+      } else if case.is_skipped() {
+        xml.push_str(&format!(
+          ">\n      <skipped message=\"{}\"/>\n    </testcase>\n",
+          xml_escape(case.skipped_message.as_deref().unwrap_or("")),
+        ));
+      } else {
+        xml.push_str("/>\n");
+      }
+    }
+    xml.push_str("  </testsuite>\n");
+  }
+  xml.push_str("</testsuites>\n");
+  xml
+}
+
+// Escapes text for an attribute or element.  Test output often carries
+// terminal colour codes and other control characters, which XML 1.0 does not
+// allow even escaped, so colour codes are dropped and any other character
+// outside XML's Char production becomes U+FFFD.
+fn xml_escape(text: &str) -> String {
+  let mut escaped = String::with_capacity(text.len());
+  let mut chars = text.chars().peekable();
+  while let Some(c) = chars.next() {
+    match c {
+      // A CSI sequence such as "\x1b[31m": parameters and intermediates up to
+      // a final byte in @ through ~.
+      '\x1b' if chars.peek() == Some(&'[') => {
+        chars.next();
+        for c in chars.by_ref() {
+          if ('@'..='~').contains(&c) {
+            break;
+          }
+        }
+      }
+      '&' => escaped.push_str("&amp;"),
+      '<' => escaped.push_str("&lt;"),
+      '>' => escaped.push_str("&gt;"),
+      '"' => escaped.push_str("&quot;"),
+      '\'' => escaped.push_str("&apos;"),
+      c if is_xml_char(c) => escaped.push(c),
+      _ => escaped.push(char::REPLACEMENT_CHARACTER),
+    }
+  }
+  escaped
+}
+
+// XML 1.0's Char production.  Surrogates cannot occur in a Rust char.
+fn is_xml_char(c: char) -> bool {
+  matches!(c, '\t' | '\n' | '\r')
+    || ('\u{20}'..='\u{FFFD}').contains(&c)
+    || c >= '\u{10000}'
+}
@@ -1,8 +1,18 @@
+use std::collections::BTreeMap;
 use std::io::Write;
 
+use jj_lib::build::BuildStatus;
+use jj_lib::jenkins::{
+  jenkins_result_to_status, JenkinsBuildDetail, JenkinsWorkflowRun,
+};
+use serde::Serialize;
+
 use crate::cli::CliBuildViewValid;
+use crate::downstream::{self, BuildNode};
 use crate::error::AppError;
-use crate::jenkins;
+use crate::output::json_write;
+use crate::stages;
+use crate::test_report;
 
 // Converts a days-since-Unix-epoch count to (year, month, day) using the
 // Gregorian calendar algorithm from Howard Hinnant's date library.
@@ -39,7 +49,7 @@ fn format_timestamp_ms(ms: u64) -> String {
   )
 }
 
-fn format_duration_ms(ms: u64) -> String {
+pub fn format_duration_ms(ms: u64) -> String {
   let total_secs = ms / 1000;
   let mins = total_secs / 60;
   let secs = total_secs % 60;
@@ -50,48 +60,118 @@ fn format_duration_ms(ms: u64) -> String {
   }
 }
 
-pub async fn view_build(config: &CliBuildViewValid) -> Result<(), AppError> {
-  if config.show_metadata {
-    let detail = jenkins::build_detail_get(
-      &config.client,
-      &config.server,
-      &config.job,
+// The build's status as shown: its result, or BUILDING/UNKNOWN before it has
+// one.
+fn status_label(result: Option<&str>, building: bool) -> &str {
+  result.unwrap_or(if building { "BUILDING" } else { "UNKNOWN" })
+}
+
+fn build_cause(detail: &JenkinsBuildDetail) -> Option<&str> {
+  detail
+    .actions
+    .iter()
+    .filter_map(|a| a.causes.as_ref())
+    .flatten()
+    .next()
+    .and_then(|c| c.short_description.as_deref())
+}
+
+// The commit the build checked out and the branch it was on.
+fn build_commit(detail: &JenkinsBuildDetail) -> Option<(&str, &str)> {
+  detail
+    .actions
+    .iter()
+    .filter_map(|a| a.last_built_revision.as_ref())
+    .next()
+    .map(|r| {
+      let sha = r.sha1.as_deref().unwrap_or("unknown");
+      let branch = r
+        .branch
+        .as_ref()
+        .and_then(|bs| bs.first())
+        .and_then(|b| b.name.as_deref())
+        .unwrap_or("unknown");
+      (sha, branch)
+    })
+}
+
+// Passed, failed, and skipped counts from the build's test report summary.
+fn build_tests(detail: &JenkinsBuildDetail) -> Option<(u64, u64, u64)> {
+  detail.actions.iter().find_map(|a| {
+    a.total_count.map(|total| {
+      let failed = a.fail_count.unwrap_or(0);
+      let skipped = a.skip_count.unwrap_or(0);
+      (total.saturating_sub(failed + skipped), failed, skipped)
+    })
+  })
+}
+
+// The build's Pipeline stages, if it has any.  Like the stage lines of a
+// streamed build, they are extra detail: if they cannot be read, that is said
+// on stderr and the view goes on without them.
+async fn build_workflow(
+  config: &CliBuildViewValid,
+  build_url: &str,
+) -> Result<Option<JenkinsWorkflowRun>, AppError> {
+  match config.jenkins.workflow_describe(build_url).await {
+    Ok(workflow) => Ok(workflow),
+    Err(e) => {
+      writeln!(std::io::stderr(), "Could not read Pipeline stages: {}", e)
+        .map_err(AppError::JenkinsBuildOutput)?;
+      Ok(None)
+    }
+  }
+}
+
+// With --follow-downstream, the worst result of the build and every build
+// downstream of it, which `build view` exits with as `job run` would.  A view
+// of the build alone always succeeds.
+async fn tree_status(
+  config: &CliBuildViewValid,
+  downstream: &[BuildNode],
+) -> Result<BuildStatus, AppError> {
+  if !config.follow_downstream {
+    return Ok(BuildStatus::Success);
+  }
+  let root = config.jenkins.build_url(&config.job, config.build_number);
+  let mut status = BuildStatus::Success;
+  for url in std::iter::once(&root).chain(downstream.iter().map(|n| &n.url)) {
+    let result = config.jenkins.build_result_get(url).await?;
+    status = status.worst(jenkins_result_to_status(result.result.as_deref()));
+  }
+  Ok(status)
+}
+
+pub async fn view_build(
+  config: &CliBuildViewValid,
+) -> Result<BuildStatus, AppError> {
+  let downstream = if config.follow_downstream {
+    let root = BuildNode::root(
+      config.job.clone(),
       config.build_number,
-    )
-    .await?;
+      config.jenkins.build_url(&config.job, config.build_number),
+    );
+    downstream::build_tree(&config.jenkins, &root).await
+  } else {
+    Vec::new()
+  };
+  if !config.output.is_text() {
+    view_build_json(config, &downstream).await?;
+    return tree_status(config, &downstream).await;
+  }
 
-    let status_str = detail.result.as_deref().unwrap_or(if detail.building {
-      "BUILDING"
-    } else {
-      "UNKNOWN"
-    });
+  if config.show_metadata {
+    let detail = config
+      .jenkins
+      .build_detail_get(&config.job, config.build_number)
+      .await?;
 
-    let cause = detail
-      .actions
-      .iter()
-      .filter_map(|a| a.causes.as_ref())
-      .flatten()
-      .next()
-      .and_then(|c| c.short_description.as_deref())
-      .unwrap_or("unknown");
-
-    let revision = detail
-      .actions
-      .iter()
-      .filter_map(|a| a.last_built_revision.as_ref())
-      .next();
-
-    let commit_str = revision.map_or_else(
+    let status_str = status_label(detail.result.as_deref(), detail.building);
+    let cause = build_cause(&detail).unwrap_or("unknown");
+    let commit_str = build_commit(&detail).map_or_else(
       || "unknown".to_string(),
-      |r| {
-        let sha = r.sha1.as_deref().unwrap_or("unknown");
+      |(sha, branch)| {
         let short_sha = if sha.len() >= 8 { &sha[..8] } else { sha };
-        let branch = r
-          .branch
-          .as_ref()
-          .and_then(|bs| bs.first())
-          .and_then(|b| b.name.as_deref())
-          .unwrap_or("unknown");
         format!("{} on {}", short_sha, branch)
       },
     );
@@ -110,21 +190,209 @@ pub async fn view_build(config: &CliBuildViewValid) -> Result<(), AppError> {
       .map_err(AppError::JenkinsBuildOutput)?;
     writeln!(out, "URL:       {}", detail.url)
       .map_err(AppError::JenkinsBuildOutput)?;
+    if let Some((passed, failed, skipped)) = build_tests(&detail) {
+      writeln!(out, "{}", test_report::summary(passed, failed, skipped))
+        .map_err(AppError::JenkinsBuildOutput)?;
+    }
+    let workflow = build_workflow(config, &detail.url).await?;
+    if let Some(run) = workflow.filter(|run| !run.stages.is_empty()) {
+      writeln!(out, "Stages:").map_err(AppError::JenkinsBuildOutput)?;
+      for stage in &run.stages {
+        writeln!(out, "  {}", stages::stage_line(stage, &run))
+          .map_err(AppError::JenkinsBuildOutput)?;
+      }
+    }
+    if !downstream.is_empty() {
+      writeln!(out, "Downstream:").map_err(AppError::JenkinsBuildOutput)?;
+    }
+    for node in &downstream {
+      let result = config.jenkins.build_result_get(&node.url).await?;
+      writeln!(
+        out,
+        "  {}     [{}]",
+        node.label,
+        status_label(result.result.as_deref(), result.building)
+      )
+      .map_err(AppError::JenkinsBuildOutput)?;
+    }
     if config.show_log {
       writeln!(out).map_err(AppError::JenkinsBuildOutput)?;
     }
   }
 
   if config.show_log {
-    let log = jenkins::build_log_fetch(
-      &config.client,
-      &config.server,
-      &config.job,
-      config.build_number,
-    )
-    .await?;
+    let log = config
+      .jenkins
+      .build_log_fetch(&config.job, config.build_number)
+      .await?;
     print!("{}", log);
+    // Downstream logs follow the build's own, each line prefixed like a
+    // streamed downstream build's.
+    for node in &downstream {
+      let log = config
+        .jenkins
+        .build_log_fetch(&node.job, node.number)
+        .await?;
+      let stdout = std::io::stdout();
+      let mut out = stdout.lock();
+      for line in log.lines() {
+        writeln!(out, "[{}] {}", node.label, line)
+          .map_err(AppError::JenkinsBuildOutput)?;
+      }
+    }
   }
 
-  Ok(())
+  tree_status(config, &downstream).await
+}
+
+// The JSON form of `build view`.  Field names are part of jj's output
+// contract, so they are spelled out here rather than passed through from the
+// Jenkins API.
+#[derive(Serialize)]
+struct BuildViewJson {
+  job: String,
+  build: u64,
+  #[serde(flatten)]
+  metadata: Option<BuildMetadataJson>,
+  #[serde(skip_serializing_if = "Option::is_none")]
+  log: Option<String>,
+  #[serde(skip_serializing_if = "Vec::is_empty")]
+  downstream: Vec<DownstreamJson>,
+}
+
+#[derive(Serialize)]
+struct BuildMetadataJson {
+  url: String,
+  building: bool,
+  result: Option<String>,
+  display_name: Option<String>,
+  // Milliseconds since the Unix epoch.
+  timestamp: u64,
+  duration_ms: u64,
+  cause: Option<String>,
+  commit: Option<CommitJson>,
+  parameters: BTreeMap<String, String>,
+  tests: Option<TestsJson>,
+  stages: Vec<StageJson>,
+}
+
+#[derive(Serialize)]
+struct CommitJson {
+  sha: String,
+  branch: String,
+}
+
+#[derive(Serialize)]
+struct TestsJson {
+  passed: u64,
+  failed: u64,
+  skipped: u64,
+}
+
+#[derive(Serialize)]
+struct StageJson {
+  name: String,
+  status: String,
+  // From the start of the build.
+  start_offset_ms: u64,
+  duration_ms: u64,
+}
+
+#[derive(Serialize)]
+struct DownstreamJson {
+  job: String,
+  build: u64,
+  label: String,
+  result: Option<String>,
+  building: bool,
+  #[serde(skip_serializing_if = "Option::is_none")]
+  log: Option<String>,
+}
+
+async fn view_build_json(
+  config: &CliBuildViewValid,
+  downstream: &[BuildNode],
+) -> Result<(), AppError> {
+  let metadata = if config.show_metadata {
+    let detail = config
+      .jenkins
+      .build_detail_get(&config.job, config.build_number)
+      .await?;
+    let workflow = build_workflow(config, &detail.url).await?;
+    Some(BuildMetadataJson {
+      building: detail.building,
+      result: detail.result.clone(),
+      display_name: detail.display_name.clone(),
+      timestamp: detail.timestamp,
+      duration_ms: detail.duration,
+      cause: build_cause(&detail).map(str::to_string),
+      commit: build_commit(&detail).map(|(sha, branch)| CommitJson {
+        sha: sha.to_string(),
+        branch: branch.to_string(),
+      }),
+      parameters: detail.parameters().into_iter().collect(),
+      tests: build_tests(&detail).map(|(passed, failed, skipped)| TestsJson {
+        passed,
+        failed,
+        skipped,
+      }),
+      stages: workflow.map_or_else(Vec::new, |run| {
+        run
+          .stages
+          .iter()
+          .map(|stage| StageJson {
+            name: stage.name.clone(),
+            status: stage.status.clone(),
+            start_offset_ms: stage
+              .start_time_millis
+              .saturating_sub(run.start_time_millis),
+            duration_ms: stage.duration_millis,
+          })
+          .collect()
+      }),
+      url: detail.url,
+    })
+  } else {
+    None
+  };
+  let log = if config.show_log {
+    Some(
+      config
+        .jenkins
+        .build_log_fetch(&config.job, config.build_number)
+        .await?,
+    )
+  } else {
+    None
+  };
+  let mut downstream_json = Vec::new();
+  for node in downstream {
+    let result = config.jenkins.build_result_get(&node.url).await?;
+    let log = if config.show_log {
+      Some(
+        config
+          .jenkins
+          .build_log_fetch(&node.job, node.number)
+          .await?,
+      )
+    } else {
+      None
+    };
+    downstream_json.push(DownstreamJson {
+      job: node.job.to_string(),
+      build: node.number,
+      label: node.label.clone(),
+      result: result.result,
+      building: result.building,
+      log,
+    });
+  }
+  let view = BuildViewJson {
+    job: config.job.to_string(),
+    build: config.build_number,
+    metadata,
+    log,
+    downstream: downstream_json,
+  };
+  json_write(config.output, &view, AppError::JenkinsBuildOutput)
 }
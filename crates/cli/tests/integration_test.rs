@@ -7,21 +7,33 @@
 
 //! Integration tests for the jj CLI.
 //!
-//! These tests require a live Jenkins instance.  Configure it by setting:
+//! By default these run against jj-lib's in-process fake Jenkins
+//! (jj_lib::testing::FakeJenkins), stocked with stand-ins for the jobs under
+//! jenkins/jobs, so they need no server.  To run them against a live Jenkins
+//! instead, set:
 //!
 //!   JENKINS_URL   — e.g. `http://localhost:11990`
 //!   JENKINS_USER  — Jenkins username
 //!   JENKINS_TOKEN — API token or password
 //!
-//! Tests skip (return without failure) when these variables are absent.
+//! A password exercises the CSRF crumb handling, since Jenkins only exempts
+//! API-token requests from crumb checks.  The fake requires crumbs only in the
+//! tests that ask it to.
+//!
 //! Run serially to prevent concurrent builds from confusing `--follow-next`:
 //!
 //!   cargo test --test integration_test -- --test-threads=1
 
 use assert_cmd::cargo::CommandCargoExt;
 use assert_cmd::Command;
+use jj_lib::testing::{
+  FakeFault, FakeInput, FakeJenkins, FakeJob, FakeParameter, FakeScript,
+  FakeTestCase,
+};
+use jj_lib::JobPath;
 use predicates::prelude::*;
 use serial_test::serial;
+use std::collections::HashMap;
 use std::process::Command as StdCommand;
 use std::time::Duration;
 use tempfile::TempDir;
@@ -29,6 +41,8 @@ use tempfile::TempDir;
 struct JenkinsTest {
   // Owns the temp directory; dropping this struct removes it.
   _dir: TempDir,
+  // The fake server, when no live one is configured.  Dropping it stops it.
+  fake: Option<FakeJenkins>,
   home: String,
   // The temp .config dir, exported as XDG_CONFIG_HOME so jj's config discovery
   // finds the test config regardless of the runner's own XDG_CONFIG_HOME.
@@ -36,12 +50,23 @@ struct JenkinsTest {
 }
 
 impl JenkinsTest {
-  // Returns None when the required environment variables are absent,
+  // Points jj at the live Jenkins named by the environment, or else at a
+  // fresh fake one.  Returns None only if the test's files cannot be set up,
   // causing the calling test to return without failure (skip).
   fn setup() -> Option<Self> {
-    let url = std::env::var("JENKINS_URL").ok()?;
-    let user = std::env::var("JENKINS_USER").ok()?;
-    let token = std::env::var("JENKINS_TOKEN").ok()?;
+    let live = std::env::var("JENKINS_URL").ok().and_then(|url| {
+      let user = std::env::var("JENKINS_USER").ok()?;
+      let token = std::env::var("JENKINS_TOKEN").ok()?;
+      Some((url, user, token))
+    });
+    let (fake, (url, user, token)) = live.map_or_else(
+      || {
+        let fake = fake_jenkins().expect("start fake Jenkins");
+        let server = (fake.url().to_string(), "admin".into(), "fake".into());
+        (Some(fake), server)
+      },
+      |live| (None, live),
+    );
 
     let dir = tempfile::tempdir().ok()?;
     let config_home = dir.path().join(".config");
@@ -67,11 +92,20 @@ impl JenkinsTest {
     let config_home = config_home.to_str()?.to_string();
     Some(JenkinsTest {
       _dir: dir,
+      fake,
       home,
       config_home,
     })
   }
 
+  // Adds settings, such as job aliases, to the test's config file.
+  fn config_append(&self, toml: &str) {
+    let path = std::path::Path::new(&self.config_home).join("jj/config.toml");
+    let config = std::fs::read_to_string(&path).expect("read config");
+    std::fs::write(&path, format!("{}\n{}", config, toml))
+      .expect("write config");
+  }
+
   // assert_cmd Command for assertions on exit code and stdout.
   fn cmd(&self) -> Command {
     let mut cmd = Command::cargo_bin("jj").unwrap();
@@ -90,6 +124,145 @@ impl JenkinsTest {
   }
 }
 
+// A fake Jenkins serving stand-ins for the Pipelines under jenkins/jobs, with
+// the same parameters and much the same output.
+fn fake_jenkins() -> std::io::Result<FakeJenkins> {
+  let fake = FakeJenkins::start()?;
+  let job = |path: &str| path.parse::<JobPath>().unwrap();
+  let seconds = |params: &HashMap<String, String>| {
+    params["duration"].parse::<u64>().unwrap_or(0)
+  };
+  let duration = |default: &str| FakeParameter::string("duration", default);
+
+  fake.job_add(
+    job("sleep-job"),
+    FakeJob::new(move |params| {
+      let secs = seconds(params);
+      (1..=secs).fold(FakeScript::new().stage("Sleep"), |script, i| {
+        script
+          .log(&format!("tick {}/{}", i, secs))
+          .sleep(Duration::from_secs(1))
+      })
+    })
+    .parameter(duration("15")),
+  );
+  fake.job_add(
+    job("fail-job"),
+    FakeJob::new(move |params| {
+      FakeScript::new()
+        .stage("Fail")
+        .sleep(Duration::from_secs(seconds(params)))
+        .log("ERROR: This build always fails intentionally.")
+        .result("FAILURE")
+    })
+    .parameter(duration("10")),
+  );
+  fake.job_add(
+    job("unstable-job"),
+    FakeJob::new(move |params| {
+      FakeScript::new()
+        .stage("Mark Unstable")
+        .sleep(Duration::from_secs(seconds(params)))
+        .result("UNSTABLE")
+    })
+    .parameter(duration("10")),
+  );
+  fake.job_add(
+    job("build-with-parameters-test"),
+    FakeJob::new(|params| {
+      FakeScript::new()
+        .stage("Echo")
+        .log(&format!("foo={}", params["foo"]))
+        .log(&format!("bar={}", params["bar"]))
+        .log(&format!("baz={}", params["baz"]))
+    })
+    .parameter(FakeParameter::string("foo", "default-foo"))
+    .parameter(FakeParameter::boolean("bar", false))
+    .parameter(FakeParameter::choice("baz", &["one", "two"])),
+  );
+  fake.job_add(
+    job("file-param-job"),
+    FakeJob::new(|params| {
+      FakeScript::new()
+        .stage("Echo")
+        .log(&format!("label={}", params["label"]))
+        .log(&format!("bundle={}", params["bundle"].trim()))
+    })
+    .parameter(FakeParameter::file("bundle"))
+    .parameter(FakeParameter::string("label", "default-label")),
+  );
+  // Jenkins echoes nothing of an answer itself; the fake's name=value lines
+  // stand in for the Pipeline's echo of the input's result.
+  fake.job_add(
+    job("input-job"),
+    FakeJob::new(|_| {
+      FakeScript::new().stage("Approve").input(
+        FakeInput::new("Approve", "Deploy?")
+          .parameter(FakeParameter::string("target", "staging")),
+      )
+    }),
+  );
+  fake.job_add(
+    job("no-params-job"),
+    FakeJob::new(|_| {
+      FakeScript::new().stage("Echo").log("no parameters needed")
+    }),
+  );
+  // The real `build job:` step waits for the child; sleeping a while gives it
+  // the same chance to run before the parent finishes.
+  fake.job_add(
+    job("downstream-parent"),
+    FakeJob::new(move |_| {
+      FakeScript::new()
+        .stage("Trigger")
+        .log("Scheduling project: no-params-job")
+        .trigger(job("no-params-job"))
+        .sleep(Duration::from_secs(3))
+    }),
+  );
+  fake.job_add(
+    job("quiet-job"),
+    FakeJob::new(|_| FakeScript::new().stage("Echo").log("quiet period over"))
+      .quiet_period(Duration::from_secs(3)),
+  );
+  fake.job_add(
+    job("artifact-job"),
+    FakeJob::new(|_| {
+      FakeScript::new()
+        .stage("Archive")
+        .artifact("dist/app.txt", b"app contents\n")
+        .artifact("dist/nested/notes.txt", b"nested notes\n")
+        .artifact("dist/skip.log", b"not matched\n")
+    }),
+  );
+  fake.job_add(
+    job("test-report-job"),
+    FakeJob::new(|_| {
+      FakeScript::new().stage("Test").tests("unit", unit_tests())
+    }),
+  );
+  fake.job_add(
+    job("log-framing-job"),
+    FakeJob::new(|_| {
+      FakeScript::new()
+        .stage("Echo")
+        .log("before blank\n\nafter blank")
+        .log("multi-byte: ünïcödé ✓ 日本語")
+        .log(&"x".repeat(20000))
+    }),
+  );
+  fake.job_add(
+    job("folder-test/nested-job"),
+    FakeJob::new(|params| {
+      FakeScript::new()
+        .stage("Greet")
+        .log(&format!("nested says {}", params["greeting"]))
+    })
+    .parameter(FakeParameter::string("greeting", "hello")),
+  );
+  Ok(fake)
+}
+
 // --- --follow-next ---
 
 // Trigger a long-running build so --follow-next finds it already in-flight,
@@ -125,6 +298,49 @@ fn follow_next_success() {
   let _ = background.wait();
 }
 
+// --log-dir keeps a copy of each followed build's log on disk while still
+// streaming it to stdout.
+#[test]
+#[serial]
+fn follow_log_dir_writes_build_log() {
+  let Some(jt) = JenkinsTest::setup() else {
+    return;
+  };
+
+  let mut background = jt
+    .std_cmd()
+    .args(["job", "run", "sleep-job", "-P", "duration=12"])
+    .stdout(std::process::Stdio::null())
+    .stderr(std::process::Stdio::null())
+    .spawn()
+    .expect("spawn background sleep-job");
+
+  std::thread::sleep(Duration::from_secs(5));
+
+  let logs = tempfile::tempdir().expect("log directory");
+  jt.cmd()
+    .args(["job", "follow", "sleep-job", "--once", "--log-dir"])
+    .arg(logs.path())
+    .timeout(Duration::from_secs(60))
+    .assert()
+    .success()
+    .stdout(predicate::str::contains("tick"));
+
+  let log = std::fs::read_dir(logs.path())
+    .expect("read log directory")
+    .filter_map(Result::ok)
+    .find(|entry| {
+      let name = entry.file_name().to_string_lossy().to_string();
+      name.starts_with("sleep-job-") && name.ends_with(".log")
+    })
+    .expect("a sleep-job-<number>.log file");
+  let text = std::fs::read_to_string(log.path()).expect("read build log");
+  assert!(text.contains("tick"), "log file lacks build output: {}", text);
+
+  let _ = background.kill();
+  let _ = background.wait();
+}
+
 // Start --follow-next before the build exists so it enters its waiting loop,
 // then trigger fail-job concurrently.  Verifies exit code 1 for FAILURE.
 #[test]
@@ -234,3 +450,2043 @@ fn follow_exits_on_sigint() {
   let status = child.wait().expect("wait for --follow child");
   assert!(status.success(), "--follow should exit 0 after SIGINT");
 }
+
+// --- job run exit codes ---
+
+// `job run` waits for the build's final result and exits with its code, so it
+// can gate scripts just like `follow --once`.
+#[test]
+#[serial]
+fn run_exits_with_failure_code() {
+  let Some(jt) = JenkinsTest::setup() else {
+    return;
+  };
+
+  jt.cmd()
+    .args(["job", "run", "fail-job", "-P", "duration=0"])
+    .timeout(Duration::from_secs(60))
+    .assert()
+    .code(1);
+}
+
+// unstable-job sets its result while the pipeline is still running, which must
+// not be mistaken for the final result.
+#[test]
+#[serial]
+fn run_exits_with_unstable_code() {
+  let Some(jt) = JenkinsTest::setup() else {
+    return;
+  };
+
+  jt.cmd()
+    .args(["job", "run", "unstable-job", "-P", "duration=0"])
+    .timeout(Duration::from_secs(60))
+    .assert()
+    .code(3);
+}
+
+// A build Jenkins refuses to enqueue fails the run with the status and the
+// reason Jenkins gave.  Needs the fake for a disabled job.
+#[test]
+#[serial]
+fn run_reports_refused_enqueue() {
+  let Some(jt) = JenkinsTest::setup() else {
+    return;
+  };
+  let Some(fake) = jt.fake.as_ref() else {
+    return;
+  };
+  fake.job_add(
+    "disabled-job".parse().unwrap(),
+    FakeJob::new(|_| FakeScript::new().log("never runs")).disabled(),
+  );
+
+  jt.cmd()
+    .args(["job", "run", "disabled-job"])
+    .timeout(Duration::from_secs(60))
+    .assert()
+    .failure()
+    .stdout(predicate::str::contains("never runs").not())
+    .stderr(predicate::str::contains("status 409 Conflict"))
+    .stderr(predicate::str::contains("disabled-job is not buildable"));
+}
+
+// --- queue ---
+
+// Occupy both of the test server's executors so the next build sits in the
+// queue as a BuildableItem ("Waiting for next available executor"), which jj
+// must wait out rather than reject.
+#[test]
+#[serial]
+fn run_waits_for_executor() {
+  let Some(jt) = JenkinsTest::setup() else {
+    return;
+  };
+
+  let mut busy = (0..2)
+    .map(|_| {
+      jt.std_cmd()
+        .args(["job", "run", "sleep-job", "-P", "duration=10"])
+        .stdout(std::process::Stdio::null())
+        .stderr(std::process::Stdio::null())
+        .spawn()
+        .expect("spawn executor-occupying sleep-job")
+    })
+    .collect::<Vec<_>>();
+
+  std::thread::sleep(Duration::from_secs(3));
+
+  jt.cmd()
+    .args(["job", "run", "sleep-job", "-P", "duration=1"])
+    .timeout(Duration::from_secs(90))
+    .assert()
+    .success()
+    .stdout(predicate::str::contains("tick 1/1"));
+
+  busy.iter_mut().for_each(|child| {
+    let _ = child.wait();
+  });
+}
+
+// A job with a quiet period waits in the queue as a WaitingItem, which `queue
+// list` explains and `job run` waits out.
+#[test]
+#[serial]
+fn run_waits_out_quiet_period() {
+  let Some(jt) = JenkinsTest::setup() else {
+    return;
+  };
+
+  let run = jt
+    .std_cmd()
+    .args(["job", "run", "quiet-job"])
+    .stdout(std::process::Stdio::piped())
+    .stderr(std::process::Stdio::null())
+    .spawn()
+    .expect("spawn quiet-job");
+
+  std::thread::sleep(Duration::from_secs(1));
+
+  jt.cmd()
+    .args(["queue", "list", "--job", "quiet-job"])
+    .assert()
+    .success()
+    .stdout(predicate::str::contains("quiet period"));
+
+  let output = run.wait_with_output().expect("wait for quiet-job");
+  assert!(output.status.success());
+  assert!(String::from_utf8_lossy(&output.stdout).contains("quiet period over"));
+}
+
+// --- interrupt policy ---
+
+// Ctrl+C during a running build with --on-interrupt=abort stops the build and
+// exits with the ABORTED code.
+#[test]
+#[serial]
+#[cfg(unix)]
+fn run_abort_on_interrupt() {
+  let Some(jt) = JenkinsTest::setup() else {
+    return;
+  };
+
+  let child = jt
+    .std_cmd()
+    .args([
+      "job",
+      "run",
+      "sleep-job",
+      "-P",
+      "duration=60",
+      "--on-interrupt=abort",
+    ])
+    .stdout(std::process::Stdio::null())
+    .stderr(std::process::Stdio::piped())
+    .spawn()
+    .expect("spawn job run");
+
+  // Long enough for the build to leave the queue and start streaming.
+  std::thread::sleep(Duration::from_secs(8));
+
+  // Safety: sending SIGINT to our own child process is well-defined behavior.
+  unsafe {
+    libc::kill(child.id() as libc::pid_t, libc::SIGINT);
+  }
+
+  let output = child.wait_with_output().expect("wait for job run child");
+  assert_eq!(output.status.code(), Some(2), "ABORTED should exit 2");
+  assert!(String::from_utf8_lossy(&output.stderr).contains("Aborting build"));
+}
+
+// With both executors busy the new item stays queued, so Ctrl+C under
+// --on-interrupt=cancel removes it from the queue.
+#[test]
+#[serial]
+#[cfg(unix)]
+fn run_cancel_on_interrupt() {
+  let Some(jt) = JenkinsTest::setup() else {
+    return;
+  };
+
+  let mut busy = (0..2)
+    .map(|_| {
+      jt.std_cmd()
+        .args(["job", "run", "sleep-job", "-P", "duration=15"])
+        .stdout(std::process::Stdio::null())
+        .stderr(std::process::Stdio::null())
+        .spawn()
+        .expect("spawn executor-occupying sleep-job")
+    })
+    .collect::<Vec<_>>();
+
+  std::thread::sleep(Duration::from_secs(3));
+
+  let child = jt
+    .std_cmd()
+    .args([
+      "job",
+      "run",
+      "sleep-job",
+      "-P",
+      "duration=1",
+      "--on-interrupt=cancel",
+    ])
+    .stdout(std::process::Stdio::null())
+    .stderr(std::process::Stdio::piped())
+    .spawn()
+    .expect("spawn job run");
+
+  std::thread::sleep(Duration::from_secs(3));
+
+  // Safety: sending SIGINT to our own child process is well-defined behavior.
+  unsafe {
+    libc::kill(child.id() as libc::pid_t, libc::SIGINT);
+  }
+
+  let output = child.wait_with_output().expect("wait for job run child");
+  assert_eq!(output.status.code(), Some(130));
+  assert!(
+    String::from_utf8_lossy(&output.stderr).contains("Cancelled queue item")
+  );
+
+  busy.iter_mut().for_each(|child| {
+    let _ = child.wait();
+  });
+}
+
+// With both executors busy a third build waits in the queue, where `queue
+// list` shows it and `queue cancel <job>` removes it.
+#[test]
+#[serial]
+fn queue_list_and_cancel_by_job() {
+  let Some(jt) = JenkinsTest::setup() else {
+    return;
+  };
+
+  let mut busy = (0..3)
+    .map(|i| {
+      jt.std_cmd()
+        .args([
+          "job",
+          "run",
+          "sleep-job",
+          "-P",
+          &format!("duration={}", 15 + i),
+        ])
+        .stdout(std::process::Stdio::null())
+        .stderr(std::process::Stdio::null())
+        .spawn()
+        .expect("spawn sleep-job")
+    })
+    .collect::<Vec<_>>();
+
+  std::thread::sleep(Duration::from_secs(4));
+
+  jt.cmd()
+    .args(["queue", "list", "--job", "sleep-job"])
+    .assert()
+    .success()
+    .stdout(predicate::str::contains("sleep-job"));
+
+  jt.cmd()
+    .args(["queue", "cancel", "sleep-job"])
+    .assert()
+    .success()
+    .stdout(predicate::str::contains("Cancelled queue item"));
+
+  busy.iter_mut().for_each(|child| {
+    let _ = child.wait();
+  });
+}
+
+// `build stop all-running` aborts every running build of the job and exits
+// with ABORTED's code, while the detached `job run` sees the same result.
+#[test]
+#[serial]
+fn build_stop_all_running() {
+  let Some(jt) = JenkinsTest::setup() else {
+    return;
+  };
+
+  let mut running = (0..2)
+    .map(|i| {
+      jt.std_cmd()
+        .args([
+          "job",
+          "run",
+          "sleep-job",
+          "-P",
+          &format!("duration={}", 60 + i),
+        ])
+        .stdout(std::process::Stdio::null())
+        .stderr(std::process::Stdio::null())
+        .spawn()
+        .expect("spawn sleep-job")
+    })
+    .collect::<Vec<_>>();
+
+  std::thread::sleep(Duration::from_secs(8));
+
+  jt.cmd()
+    .args(["build", "stop", "sleep-job", "all-running"])
+    .timeout(Duration::from_secs(60))
+    .assert()
+    .code(2)
+    .stdout(predicate::str::contains("[ABORTED]"));
+
+  running.iter_mut().for_each(|child| {
+    let status = child.wait().expect("wait for sleep-job");
+    assert_eq!(status.code(), Some(2), "ABORTED should exit 2");
+  });
+}
+
+// --- input steps ---
+
+// --approve proceeds past the input with the --input values.
+#[test]
+#[serial]
+fn run_approves_input() {
+  let Some(jt) = JenkinsTest::setup() else {
+    return;
+  };
+
+  jt.cmd()
+    .args(["job", "run", "input-job", "--approve", "-I", "target=prod"])
+    .timeout(Duration::from_secs(60))
+    .assert()
+    .success()
+    .stdout(predicate::str::contains("target=prod"))
+    .stderr(predicate::str::contains("Proceeded past input 'Approve'."));
+}
+
+// --reject aborts at the input, which ends the run as ABORTED.
+#[test]
+#[serial]
+fn run_rejects_input() {
+  let Some(jt) = JenkinsTest::setup() else {
+    return;
+  };
+
+  jt.cmd()
+    .args(["job", "run", "input-job", "--reject"])
+    .timeout(Duration::from_secs(60))
+    .assert()
+    .code(2)
+    .stdout(predicate::str::contains("target=").not())
+    .stderr(predicate::str::contains("Aborted at input 'Approve'."));
+}
+
+// An --input value the input's parameter cannot take is refused before jj
+// answers, leaving the input pending.
+#[test]
+#[serial]
+fn run_refuses_invalid_input_value() {
+  let Some(jt) = JenkinsTest::setup() else {
+    return;
+  };
+  let Some(fake) = jt.fake.as_ref() else {
+    return;
+  };
+  fake.job_add(
+    "choice-input-job".parse().unwrap(),
+    FakeJob::new(|_| {
+      FakeScript::new().stage("Approve").input(
+        FakeInput::new("Approve", "Deploy where?")
+          .parameter(FakeParameter::choice("region", &["east", "west"])),
+      )
+    }),
+  );
+
+  jt.cmd()
+    .args([
+      "job",
+      "run",
+      "choice-input-job",
+      "--approve",
+      "-I",
+      "region=north",
+    ])
+    .timeout(Duration::from_secs(60))
+    .assert()
+    .failure()
+    .stderr(predicate::str::contains(
+      "Parameter 'region' must be one of [east, west], got 'north'",
+    ))
+    .stderr(predicate::str::contains("Proceeded past input").not());
+  assert_eq!(fake.requests_count("/input/Approve/submit"), 0);
+}
+
+// Without --approve or a terminal, jj says where the build is waiting and
+// keeps streaming until someone answers in Jenkins; an abort there ends the
+// run as ABORTED.  Needs the fake to answer in place of the web UI.
+#[test]
+#[serial]
+fn run_follows_input_aborted_elsewhere() {
+  let Some(jt) = JenkinsTest::setup() else {
+    return;
+  };
+  let Some(fake) = jt.fake.as_ref() else {
+    return;
+  };
+
+  let job = "input-job".parse::<JobPath>().unwrap();
+  std::thread::scope(|scope| {
+    scope.spawn(|| {
+      for _ in 0..600 {
+        if fake.input_answer(&job, 1, false) {
+          return;
+        }
+        std::thread::sleep(Duration::from_millis(100));
+      }
+      panic!("input-job never waited on its input");
+    });
+    jt.cmd()
+      .args(["job", "run", "input-job"])
+      .timeout(Duration::from_secs(60))
+      .assert()
+      .code(2)
+      .stdout(predicate::str::contains("Rejected by someone-else"))
+      .stderr(predicate::str::contains("Waiting for input: Deploy?"));
+  });
+}
+
+// An input answered in the web UI just before jj submits its own answer makes
+// the submit fail.  jj reports that and goes on streaming the build.
+#[test]
+#[serial]
+fn run_survives_input_answered_elsewhere() {
+  let Some(jt) = JenkinsTest::setup() else {
+    return;
+  };
+  let Some(fake) = jt.fake.as_ref() else {
+    return;
+  };
+  fake.input_race_set(true);
+
+  jt.cmd()
+    .args(["job", "run", "input-job", "--approve", "-I", "target=prod"])
+    .timeout(Duration::from_secs(60))
+    .assert()
+    .success()
+    .stdout(predicate::str::contains("Approved by someone-else"))
+    .stdout(predicate::str::contains("target=staging"))
+    .stderr(predicate::str::contains("Could not answer input 'Approve'"));
+}
+
+// --- downstream builds ---
+
+// The parent's `build job:` step starts no-params-job, whose log is streamed
+// under the parent's with a nested prefix.
+#[test]
+#[serial]
+fn run_follows_downstream() {
+  let Some(jt) = JenkinsTest::setup() else {
+    return;
+  };
+
+  jt.cmd()
+    .args(["job", "run", "downstream-parent", "--follow-downstream"])
+    .timeout(Duration::from_secs(90))
+    .assert()
+    .success()
+    .stdout(predicate::str::contains("no-params-job#"))
+    .stdout(predicate::str::contains("no parameters needed"));
+}
+
+// A multibranch branch build starts another project the way a freestyle
+// job's "Build other projects" does, so the downstream build is found by its
+// upstream cause, which names the branch as Jenkins does, percent-encoded.
+// The failed downstream build decides the exit code of the run and of the
+// view.  Needs the fake for the multibranch project.
+#[test]
+#[serial]
+fn downstream_of_multibranch_branch() {
+  let Some(jt) = JenkinsTest::setup() else {
+    return;
+  };
+  let Some(fake) = jt.fake.as_ref() else {
+    return;
+  };
+  let branch = "service/feature%2Fx";
+  let deploy = "deploy-job".parse::<JobPath>().unwrap();
+  fake.job_add(
+    deploy.clone(),
+    FakeJob::new(|_| FakeScript::new().log("deploy failed").result("FAILURE")),
+  );
+  fake.job_add(
+    branch.parse().unwrap(),
+    FakeJob::new(|_| FakeScript::new().log("branch built"))
+      .downstream_project(deploy),
+  );
+
+  let output = jt
+    .cmd()
+    .args(["job", "run", branch, "--follow-downstream"])
+    .timeout(Duration::from_secs(90))
+    .output()
+    .unwrap();
+  let stdout = String::from_utf8_lossy(&output.stdout);
+  assert_eq!(output.status.code(), Some(1), "{}", stdout);
+  assert!(stdout.contains("deploy-job#1"), "{}", stdout);
+  assert!(stdout.contains("deploy failed"), "{}", stdout);
+  let build = build_number_of(&stdout)
+    .expect("build number in log")
+    .to_string();
+
+  jt.cmd()
+    .args(["build", "view", branch, &build, "--follow-downstream"])
+    .timeout(Duration::from_secs(60))
+    .assert()
+    .code(1)
+    .stdout(predicate::str::contains("deploy-job#1     [FAILURE]"));
+
+  jt.cmd()
+    .args(["build", "view", branch, &build])
+    .timeout(Duration::from_secs(60))
+    .assert()
+    .success();
+}
+
+// --- retries ---
+//
+// Faults are injected into the fake, so these tests do not run against a
+// live server.
+
+// A read answered with 502s and 503s is retried up to --retries times, then
+// fails with the last response.  --retries 0 makes one attempt only.
+#[test]
+#[serial]
+fn retries_transient_failures() {
+  let Some(jt) = JenkinsTest::setup() else {
+    return;
+  };
+  let Some(fake) = jt.fake.as_ref() else {
+    return;
+  };
+  let job_json = "/job/build-with-parameters-test/api/json";
+  let params = |retries: &str| {
+    let mut cmd = jt.cmd();
+    cmd.args(["--retries", retries, "--retry-backoff-ms", "10"]);
+    cmd.args(["job", "params", "build-with-parameters-test"]);
+    cmd
+  };
+
+  fake.fault_inject(job_json, FakeFault::BadGateway, 1);
+  fake.fault_inject(job_json, FakeFault::Unavailable, 1);
+  params("2")
+    .assert()
+    .success()
+    .stdout(predicate::str::contains("foo"));
+  assert_eq!(fake.requests_count(job_json), 3);
+
+  fake.fault_inject(job_json, FakeFault::Unavailable, 3);
+  params("2").assert().failure();
+  assert_eq!(fake.requests_count(job_json), 6);
+
+  fake.fault_inject(job_json, FakeFault::BadGateway, 1);
+  params("0").assert().failure();
+  assert_eq!(fake.requests_count(job_json), 7);
+}
+
+// Each retry waits --retry-backoff-ms, doubled for every retry before it.
+#[test]
+#[serial]
+fn retries_back_off() {
+  let Some(jt) = JenkinsTest::setup() else {
+    return;
+  };
+  let Some(fake) = jt.fake.as_ref() else {
+    return;
+  };
+  let job_json = "/job/build-with-parameters-test/api/json";
+  fake.fault_inject(job_json, FakeFault::Unavailable, 2);
+
+  let started = std::time::Instant::now();
+  jt.cmd()
+    .args(["--retries", "2", "--retry-backoff-ms", "400"])
+    .args(["job", "params", "build-with-parameters-test"])
+    .assert()
+    .success();
+  assert!(
+    started.elapsed() >= Duration::from_millis(1200),
+    "retried after {:?}",
+    started.elapsed(),
+  );
+}
+
+// A log stream cut off mid-poll, or refused by a proxy, reconnects from the
+// last complete poll: every line arrives, and none twice.
+#[test]
+#[serial]
+fn run_reconnects_log_stream() {
+  let Some(jt) = JenkinsTest::setup() else {
+    return;
+  };
+  let Some(fake) = jt.fake.as_ref() else {
+    return;
+  };
+  let progressive = "logText/progressiveText";
+  fake.fault_inject(progressive, FakeFault::Dropped, 2);
+  fake.fault_inject(progressive, FakeFault::BadGateway, 1);
+  fake.fault_inject(progressive, FakeFault::Dropped, 1);
+
+  let output = jt
+    .cmd()
+    .args(["--retries", "3", "--retry-backoff-ms", "10"])
+    .args(["job", "run", "sleep-job", "-P", "duration=4"])
+    .timeout(Duration::from_secs(60))
+    .output()
+    .unwrap();
+  assert!(output.status.success());
+  let stdout = String::from_utf8_lossy(&output.stdout);
+  for i in 1..=4 {
+    let tick = format!("tick {}/4", i);
+    assert_eq!(
+      stdout.lines().filter(|line| line.ends_with(&tick)).count(),
+      1,
+      "{} in:\n{}",
+      tick,
+      stdout,
+    );
+  }
+  assert_eq!(
+    stdout
+      .lines()
+      .filter(|line| line.ends_with("Finished: SUCCESS"))
+      .count(),
+    1,
+  );
+}
+
+// --- CSRF crumbs ---
+//
+// The fake stands in for a server that requires crumbs, so these tests do not
+// run against a live one, which may accept the token without.
+
+const CRUMB_ISSUER: &str = "/crumbIssuer/api/json";
+
+// A run enqueues with the crumb and session cookie the issuer handed out,
+// fetching a fresh pair when the first has expired.  A recording keeps the
+// crumb out, yet still replays.
+#[test]
+#[serial]
+fn crumbs_guard_enqueue() {
+  let Some(mut jt) = JenkinsTest::setup() else {
+    return;
+  };
+  let Some(fake) = jt.fake.as_ref() else {
+    return;
+  };
+  fake.crumbs_require();
+
+  jt.cmd()
+    .args(["job", "run", "no-params-job"])
+    .timeout(Duration::from_secs(60))
+    .assert()
+    .success()
+    .stdout(predicate::str::contains("no parameters needed"));
+  assert_eq!(fake.requests_count(CRUMB_ISSUER), 1);
+
+  let dir = tempfile::tempdir().expect("recording directory");
+  let recording = dir.path().join("session.ndjson");
+  let run = ["job", "run", "no-params-job"];
+  fake.crumb_expire();
+  jt.cmd()
+    .arg("--record")
+    .arg(&recording)
+    .args(run)
+    .timeout(Duration::from_secs(60))
+    .assert()
+    .success()
+    .stdout(predicate::str::contains("no parameters needed"));
+  assert_eq!(fake.requests_count(CRUMB_ISSUER), 3);
+
+  let text = std::fs::read_to_string(&recording).expect("read recording");
+  assert!(text.contains(CRUMB_ISSUER));
+  assert!(!text.contains("fake-crumb-"), "crumb recorded: {}", text);
+  assert!(!text.contains("fake-session-"), "session recorded: {}", text);
+
+  drop(jt.fake.take());
+  jt.cmd()
+    .arg("--replay")
+    .arg(&recording)
+    .args(run)
+    .timeout(Duration::from_secs(60))
+    .assert()
+    .success()
+    .stdout(predicate::str::contains("no parameters needed"));
+}
+
+// `build stop` refreshes an expired crumb and stops the build.
+#[test]
+#[serial]
+fn crumbs_guard_stop() {
+  let Some(jt) = JenkinsTest::setup() else {
+    return;
+  };
+  let Some(fake) = jt.fake.as_ref() else {
+    return;
+  };
+  fake.crumbs_require();
+
+  let mut running = jt
+    .std_cmd()
+    .args(["job", "run", "sleep-job", "-P", "duration=30"])
+    .stdout(std::process::Stdio::null())
+    .stderr(std::process::Stdio::null())
+    .spawn()
+    .expect("spawn sleep-job");
+  std::thread::sleep(Duration::from_secs(3));
+  let issued = fake.requests_count(CRUMB_ISSUER);
+
+  fake.crumb_expire();
+  jt.cmd()
+    .args(["build", "stop", "sleep-job", "all-running"])
+    .timeout(Duration::from_secs(60))
+    .assert()
+    .code(2)
+    .stdout(predicate::str::contains("[ABORTED]"));
+  assert_eq!(fake.requests_count(CRUMB_ISSUER), issued + 2);
+
+  let status = running.wait().expect("wait for sleep-job");
+  assert_eq!(status.code(), Some(2));
+}
+
+// `queue cancel` refreshes an expired crumb and cancels the queued build.
+#[test]
+#[serial]
+fn crumbs_guard_cancel() {
+  let Some(jt) = JenkinsTest::setup() else {
+    return;
+  };
+  let Some(fake) = jt.fake.as_ref() else {
+    return;
+  };
+  fake.crumbs_require();
+  fake.executors_set(0);
+
+  let mut queued = jt
+    .std_cmd()
+    .args(["job", "run", "no-params-job"])
+    .stdout(std::process::Stdio::null())
+    .stderr(std::process::Stdio::null())
+    .spawn()
+    .expect("spawn no-params-job");
+  std::thread::sleep(Duration::from_secs(3));
+  let issued = fake.requests_count(CRUMB_ISSUER);
+
+  fake.crumb_expire();
+  jt.cmd()
+    .args(["queue", "cancel", "no-params-job"])
+    .assert()
+    .success()
+    .stdout(predicate::str::contains("Cancelled queue item"));
+  assert_eq!(fake.requests_count(CRUMB_ISSUER), issued + 2);
+
+  let status = queued.wait().expect("wait for no-params-job");
+  assert!(!status.success());
+}
+
+// A file upload goes out with the crumb like any other POST.  Its body is
+// streamed, so it cannot be resent if the crumb turns out to have expired;
+// that is said plainly, and the next run fetches a fresh crumb.
+#[test]
+#[serial]
+fn crumbs_guard_file_upload() {
+  let Some(jt) = JenkinsTest::setup() else {
+    return;
+  };
+  let Some(fake) = jt.fake.as_ref() else {
+    return;
+  };
+  fake.crumbs_require();
+
+  let dir = tempfile::tempdir().expect("file parameter directory");
+  let bundle = dir.path().join("bundle.txt");
+  std::fs::write(&bundle, "bundle contents\n").unwrap();
+  let upload = || {
+    let mut cmd = jt.cmd();
+    cmd
+      .args(["job", "run", "file-param-job", "-F"])
+      .arg(format!("bundle=@{}", bundle.display()))
+      .timeout(Duration::from_secs(60));
+    cmd
+  };
+
+  upload()
+    .assert()
+    .success()
+    .stdout(predicate::str::contains("bundle=bundle contents"));
+  assert_eq!(fake.requests_count(CRUMB_ISSUER), 1);
+
+  fake.crumb_expire();
+  upload()
+    .assert()
+    .failure()
+    .stderr(predicate::str::contains("cannot be resent with a fresh one"));
+
+  upload()
+    .assert()
+    .success()
+    .stdout(predicate::str::contains("bundle=bundle contents"));
+}
+
+// --- log framing ---
+
+// Lines reach stdout whole and prefixed once, however the log was chunked:
+// blank lines survive, multi-byte characters are not mangled at chunk
+// boundaries, and a line longer than a chunk is not broken up.
+#[test]
+#[serial]
+fn run_streams_whole_lines() {
+  let Some(jt) = JenkinsTest::setup() else {
+    return;
+  };
+
+  let long_line = format!("] {}\n", "x".repeat(20000));
+  jt.cmd()
+    .args(["job", "run", "log-framing-job"])
+    .timeout(Duration::from_secs(60))
+    .assert()
+    .success()
+    .stdout(predicate::str::contains("before blank"))
+    .stdout(predicate::str::contains("] \n"))
+    .stdout(predicate::str::contains("ünïcödé ✓ 日本語"))
+    .stdout(predicate::str::contains("\u{FFFD}").not())
+    .stdout(predicate::str::contains(long_line));
+}
+
+// --- build view ---
+
+// Stages are reported as a build streams, and listed by `build view` in text
+// and in JSON.
+#[test]
+#[serial]
+fn view_shows_stages() {
+  let Some(jt) = JenkinsTest::setup() else {
+    return;
+  };
+
+  let output = jt
+    .cmd()
+    .args(["job", "run", "fail-job", "-P", "duration=0"])
+    .timeout(Duration::from_secs(60))
+    .output()
+    .unwrap();
+  assert_eq!(output.status.code(), Some(1));
+  assert!(String::from_utf8_lossy(&output.stderr)
+    .contains("Stage 'Fail' finished: FAILED"));
+  let build = build_number_of(&String::from_utf8_lossy(&output.stdout))
+    .expect("build number in log")
+    .to_string();
+
+  let view = jt
+    .cmd()
+    .args(["build", "view", "fail-job", &build, "--metadata"])
+    .output()
+    .unwrap();
+  assert!(view.status.success());
+  let stdout = String::from_utf8_lossy(&view.stdout);
+  let stages = stdout
+    .split_once("Stages:\n")
+    .map(|(_, stages)| stages)
+    .expect("stage table");
+  let fail = stages.lines().next().unwrap_or_default();
+  assert!(
+    fail.trim_start().starts_with("Fail") && fail.contains("FAILED"),
+    "{}",
+    stdout,
+  );
+
+  let view = jt
+    .cmd()
+    .args(["--output", "json", "build", "view", "fail-job", &build])
+    .arg("--metadata")
+    .output()
+    .unwrap();
+  assert!(view.status.success());
+  let view: serde_json::Value = serde_json::from_slice(&view.stdout).unwrap();
+  let stages = view["stages"].as_array().unwrap();
+  assert_eq!(stages.len(), 1);
+  assert_eq!(stages[0]["name"], "Fail");
+  assert_eq!(stages[0]["status"], "FAILED");
+}
+
+// A build view goes on without stages it cannot read, saying so on stderr.
+// Needs the fake to fail the stage request.
+#[test]
+#[serial]
+fn view_without_readable_stages() {
+  let Some(jt) = JenkinsTest::setup() else {
+    return;
+  };
+  let Some(fake) = jt.fake.as_ref() else {
+    return;
+  };
+
+  let output = jt
+    .cmd()
+    .args(["job", "run", "no-params-job"])
+    .timeout(Duration::from_secs(60))
+    .output()
+    .unwrap();
+  assert!(output.status.success());
+  let build = build_number_of(&String::from_utf8_lossy(&output.stdout))
+    .expect("build number in log")
+    .to_string();
+  let view = |output: &str| {
+    let mut cmd = jt.cmd();
+    cmd.args(["--retries", "0", "--output", output]);
+    cmd.args(["build", "view", "no-params-job", &build, "--metadata"]);
+    cmd
+  };
+
+  fake.fault_inject("wfapi/describe", FakeFault::BadGateway, 1);
+  view("text")
+    .assert()
+    .success()
+    .stdout(predicate::str::contains(format!("Build #{}", build)))
+    .stdout(predicate::str::contains("Stages:").not())
+    .stderr(predicate::str::contains("Could not read Pipeline stages"));
+
+  fake.fault_inject("wfapi/describe", FakeFault::BadGateway, 1);
+  let output = view("json").output().unwrap();
+  assert!(output.status.success());
+  assert!(String::from_utf8_lossy(&output.stderr)
+    .contains("Could not read Pipeline stages"));
+  let view: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+  assert_eq!(view["result"], "SUCCESS");
+  assert_eq!(view["stages"], serde_json::json!([]));
+}
+
+// --- JSON output ---
+
+// Every line of a run is a JSON event, from queued through finished, and the
+// finished event carries the build's result.
+#[test]
+#[serial]
+fn run_emits_ndjson_events() {
+  let Some(jt) = JenkinsTest::setup() else {
+    return;
+  };
+
+  let output = jt
+    .cmd()
+    .args(["--output", "ndjson", "job", "run", "no-params-job"])
+    .timeout(Duration::from_secs(60))
+    .output()
+    .unwrap();
+  assert!(output.status.success());
+  let events = String::from_utf8(output.stdout)
+    .unwrap()
+    .lines()
+    .map(|line| serde_json::from_str::<serde_json::Value>(line).unwrap())
+    .collect::<Vec<_>>();
+  let kinds = events
+    .iter()
+    .map(|e| e["event"].as_str().unwrap().to_string())
+    .collect::<Vec<_>>();
+  assert_eq!(kinds.first().map(String::as_str), Some("queued"));
+  assert_eq!(kinds.last().map(String::as_str), Some("finished"));
+  assert!(events.iter().any(|e| e["event"] == "log"
+    && e["line"].as_str().unwrap().contains("no parameters needed")));
+  let finished = events.last().unwrap();
+  assert_eq!(finished["result"], "SUCCESS");
+
+  let build = finished["build"].as_u64().unwrap().to_string();
+  let view = jt
+    .cmd()
+    .args(["--output", "json", "build", "view", "no-params-job", &build])
+    .arg("--metadata")
+    .output()
+    .unwrap();
+  assert!(view.status.success());
+  let view: serde_json::Value = serde_json::from_slice(&view.stdout).unwrap();
+  assert_eq!(view["result"], "SUCCESS");
+  assert_eq!(view["build"].as_u64(), finished["build"].as_u64());
+}
+
+// Parses every line of a command's stdout as JSON.
+fn json_lines(stdout: &[u8]) -> Vec<serde_json::Value> {
+  String::from_utf8_lossy(stdout)
+    .lines()
+    .map(|line| serde_json::from_str::<serde_json::Value>(line).unwrap())
+    .collect()
+}
+
+// `job params` lists definitions as an array under json, and one per line
+// under ndjson.
+#[test]
+#[serial]
+fn params_emit_json() {
+  let Some(jt) = JenkinsTest::setup() else {
+    return;
+  };
+
+  let output = jt
+    .cmd()
+    .args([
+      "--output",
+      "json",
+      "job",
+      "params",
+      "build-with-parameters-test",
+    ])
+    .output()
+    .unwrap();
+  assert!(output.status.success());
+  let params: serde_json::Value =
+    serde_json::from_slice(&output.stdout).unwrap();
+  assert_eq!(params[0]["name"], "foo");
+  assert_eq!(params[0]["type"], "string");
+  assert_eq!(params[0]["default"], "default-foo");
+  assert_eq!(params[2]["choices"], serde_json::json!(["one", "two"]));
+
+  let output = jt
+    .cmd()
+    .args([
+      "--output",
+      "ndjson",
+      "job",
+      "params",
+      "build-with-parameters-test",
+    ])
+    .output()
+    .unwrap();
+  assert!(output.status.success());
+  let names = json_lines(&output.stdout)
+    .iter()
+    .map(|p| p["name"].as_str().unwrap().to_string())
+    .collect::<Vec<_>>();
+  assert_eq!(names, ["foo", "bar", "baz"]);
+}
+
+// `queue list` and `queue cancel` describe queue items as JSON.
+#[test]
+#[serial]
+fn queue_emits_json() {
+  let Some(jt) = JenkinsTest::setup() else {
+    return;
+  };
+
+  let run = jt
+    .std_cmd()
+    .args(["job", "run", "quiet-job"])
+    .stdout(std::process::Stdio::null())
+    .stderr(std::process::Stdio::null())
+    .spawn()
+    .expect("spawn quiet-job");
+
+  std::thread::sleep(Duration::from_secs(1));
+
+  let output = jt
+    .cmd()
+    .args(["--output", "json", "queue", "list", "--job", "quiet-job"])
+    .output()
+    .unwrap();
+  assert!(output.status.success());
+  let items: serde_json::Value =
+    serde_json::from_slice(&output.stdout).unwrap();
+  let items = items.as_array().unwrap();
+  assert_eq!(items.len(), 1);
+  assert_eq!(items[0]["job"], "quiet-job");
+  assert!(items[0]["why"].as_str().unwrap().contains("quiet period"));
+
+  let output = jt
+    .cmd()
+    .args(["--output", "ndjson", "queue", "cancel", "quiet-job"])
+    .output()
+    .unwrap();
+  assert!(output.status.success());
+  let cancelled = json_lines(&output.stdout);
+  assert_eq!(cancelled.len(), 1);
+  assert_eq!(cancelled[0]["queue_id"], items[0]["id"]);
+
+  run.wait_with_output().expect("wait for quiet-job");
+}
+
+// `build artifacts` lists and downloads as JSON.
+#[test]
+#[serial]
+fn artifacts_emit_json() {
+  let Some(jt) = JenkinsTest::setup() else {
+    return;
+  };
+
+  jt.cmd()
+    .args(["job", "run", "artifact-job"])
+    .timeout(Duration::from_secs(60))
+    .assert()
+    .success();
+
+  let output = jt
+    .cmd()
+    .args(["--output", "json", "build", "artifacts", "artifact-job"])
+    .arg("lastSuccessfulBuild")
+    .output()
+    .unwrap();
+  assert!(output.status.success());
+  let artifacts: serde_json::Value =
+    serde_json::from_slice(&output.stdout).unwrap();
+  let app = artifacts
+    .as_array()
+    .unwrap()
+    .iter()
+    .find(|a| a["path"] == "dist/app.txt")
+    .expect("dist/app.txt listed");
+  assert_eq!(app["size"], "app contents\n".len());
+
+  let to = tempfile::tempdir().expect("download directory");
+  let output = jt
+    .cmd()
+    .args(["--output", "ndjson", "build", "artifacts", "artifact-job"])
+    .args(["lastSuccessfulBuild", "--download", "dist/app.txt", "--to"])
+    .arg(to.path())
+    .output()
+    .unwrap();
+  assert!(output.status.success());
+  let downloads = json_lines(&output.stdout);
+  assert_eq!(downloads.len(), 1);
+  assert_eq!(downloads[0]["path"], "dist/app.txt");
+  assert_eq!(downloads[0]["status"], "downloaded");
+  assert_eq!(downloads[0]["bytes"], "app contents\n".len());
+}
+
+// `build stop` reports each stopped build's result as JSON.
+#[test]
+#[serial]
+fn stop_emits_json() {
+  let Some(jt) = JenkinsTest::setup() else {
+    return;
+  };
+
+  let mut run = jt
+    .std_cmd()
+    .args(["job", "run", "sleep-job", "-P", "duration=30"])
+    .stdout(std::process::Stdio::null())
+    .stderr(std::process::Stdio::null())
+    .spawn()
+    .expect("spawn sleep-job");
+
+  std::thread::sleep(Duration::from_secs(3));
+
+  let output = jt
+    .cmd()
+    .args([
+      "--output",
+      "json",
+      "build",
+      "stop",
+      "sleep-job",
+      "all-running",
+    ])
+    .timeout(Duration::from_secs(60))
+    .output()
+    .unwrap();
+  assert_eq!(output.status.code(), Some(2));
+  let stopped: serde_json::Value =
+    serde_json::from_slice(&output.stdout).unwrap();
+  assert_eq!(stopped[0]["job"], "sleep-job");
+  assert_eq!(stopped[0]["result"], "ABORTED");
+  assert_eq!(stopped[0]["exit_code"], 2);
+
+  let _ = run.wait();
+}
+
+// --- artifacts ---
+
+// Lists artifact-job's archived files, downloads the .txt ones keeping their
+// directories, and recognises them as already downloaded on a second run.
+#[test]
+#[serial]
+fn artifacts_list_and_download() {
+  let Some(jt) = JenkinsTest::setup() else {
+    return;
+  };
+
+  jt.cmd()
+    .args(["job", "run", "artifact-job"])
+    .timeout(Duration::from_secs(60))
+    .assert()
+    .success();
+
+  jt.cmd()
+    .args(["build", "artifacts", "artifact-job", "lastSuccessfulBuild"])
+    .assert()
+    .success()
+    .stdout(predicate::str::contains("dist/app.txt"))
+    .stdout(predicate::str::contains("dist/nested/notes.txt"));
+
+  let to = tempfile::tempdir().expect("download directory");
+  let to_arg = to.path().to_str().expect("UTF-8 temp path");
+  let download = [
+    "build",
+    "artifacts",
+    "artifact-job",
+    "lastSuccessfulBuild",
+    "--download",
+    "dist/**/*.txt",
+    "--to",
+    to_arg,
+  ];
+  jt.cmd()
+    .args(download)
+    .assert()
+    .success()
+    .stdout(predicate::str::contains("Downloaded dist/app.txt"));
+  assert_eq!(
+    std::fs::read_to_string(to.path().join("dist/nested/notes.txt")).unwrap(),
+    "nested notes\n"
+  );
+  assert!(!to.path().join("dist/skip.log").exists());
+
+  jt.cmd()
+    .args(download)
+    .assert()
+    .success()
+    .stdout(predicate::str::contains("Already downloaded dist/app.txt"));
+
+  jt.cmd()
+    .args([
+      "build",
+      "artifacts",
+      "artifact-job",
+      "lastSuccessfulBuild",
+      "--download",
+      "missing/*",
+    ])
+    .assert()
+    .failure()
+    .stderr(predicate::str::contains("No artifacts match"));
+}
+
+// A file already on disk is only skipped or resumed when it holds the same
+// version of the same artifact; equal or shorter lengths prove nothing.
+#[test]
+#[serial]
+fn artifacts_download_checks_version() {
+  let Some(jt) = JenkinsTest::setup() else {
+    return;
+  };
+  let Some(fake) = jt.fake.as_ref() else {
+    return;
+  };
+  // The sleep keeps the two builds' start times, which date their artifacts,
+  // at least a second apart.
+  fake.job_add(
+    "versioned-job".parse().unwrap(),
+    FakeJob::new(|params| {
+      FakeScript::new()
+        .stage("Archive")
+        .sleep(Duration::from_secs(1))
+        .artifact(
+          "out.txt",
+          format!("version {}\n", params["version"]).as_bytes(),
+        )
+    })
+    .parameter(FakeParameter::string("version", "one")),
+  );
+  for version in ["one", "two"] {
+    jt.cmd()
+      .args([
+        "job",
+        "run",
+        "versioned-job",
+        "-P",
+        &format!("version={}", version),
+      ])
+      .timeout(Duration::from_secs(60))
+      .assert()
+      .success();
+  }
+
+  let to = tempfile::tempdir().expect("download directory");
+  let to_arg = to.path().to_str().expect("UTF-8 temp path");
+  let download = |build: &str| {
+    let mut cmd = jt.cmd();
+    cmd
+      .args(["build", "artifacts", "versioned-job", build])
+      .args(["--download", "out.txt", "--to", to_arg]);
+    cmd
+  };
+  let contents = || std::fs::read_to_string(to.path().join("out.txt")).unwrap();
+
+  download("1")
+    .assert()
+    .success()
+    .stdout(predicate::str::contains("Downloaded out.txt"));
+  download("1")
+    .assert()
+    .success()
+    .stdout(predicate::str::contains("Already downloaded out.txt"));
+  // The same length, but another build's file.
+  download("2")
+    .assert()
+    .success()
+    .stdout(predicate::str::contains("Downloaded out.txt"));
+  assert_eq!(contents(), "version two\n");
+
+  // A partial file from build 1 is not continued with build 2's bytes.
+  fake.fault_inject("out.txt", FakeFault::Dropped, 1);
+  download("1").assert().failure();
+  download("2")
+    .assert()
+    .success()
+    .stdout(predicate::str::contains("Downloaded out.txt"));
+  assert_eq!(contents(), "version two\n");
+
+  fake.fault_inject("out.txt", FakeFault::Dropped, 1);
+  download("1").assert().failure();
+  download("1")
+    .assert()
+    .success()
+    .stdout(predicate::str::contains("Resumed out.txt at byte"));
+  assert_eq!(contents(), "version one\n");
+}
+
+// --- test reports ---
+
+// test-report-job's suite, as its JUnit report describes it.
+fn unit_tests() -> Vec<FakeTestCase> {
+  vec![
+    FakeTestCase::passed("com.example.MathTest", "adds")
+      .duration(Duration::from_millis(500)),
+    FakeTestCase::failed(
+      "com.example.MathTest",
+      "divides",
+      "\x1b[31mexpected 2 but was 3\x1b[0m",
+      "java.lang.AssertionError: expected 2 but was 3\x07\n\tat com.example.MathTest.divides",
+    )
+    .duration(Duration::from_millis(250)),
+    FakeTestCase::skipped("com.example.MathTest", "rounds", "not ready"),
+  ]
+}
+
+// Summarises test-report-job's report, lists its failure, and writes it out
+// as JUnit XML that XML 1.0 parsers accept despite the colour codes and BEL
+// in the failure.
+#[test]
+#[serial]
+fn tests_summarise_and_write_junit() {
+  let Some(jt) = JenkinsTest::setup() else {
+    return;
+  };
+
+  // The failed case leaves the build UNSTABLE.
+  jt.cmd()
+    .args(["job", "run", "test-report-job"])
+    .timeout(Duration::from_secs(60))
+    .assert()
+    .code(3);
+
+  let junit = tempfile::NamedTempFile::new().expect("JUnit file");
+  jt.cmd()
+    .args(["build", "tests", "test-report-job", "lastBuild", "--junit"])
+    .arg(junit.path())
+    .assert()
+    .success()
+    .stdout(predicate::str::contains("Tests: 1 passed, 1 failed, 1 skipped"))
+    .stdout(predicate::str::contains("FAILED com.example.MathTest.divides"))
+    .stdout(predicate::str::contains("expected 2 but was 3"));
+
+  let xml = std::fs::read_to_string(junit.path()).unwrap();
+  let illegal = xml.chars().find(|&c| {
+    (c < ' ' && !matches!(c, '\t' | '\n' | '\r')) || c == '\u{FFFE}'
+  });
+  assert_eq!(illegal, None, "character outside XML 1.0 in:\n{}", xml);
+  assert!(xml.contains(
+    "<testsuites tests=\"3\" failures=\"1\" skipped=\"1\" time=\"0.750\">"
+  ));
+  assert!(xml.contains("<failure message=\"expected 2 but was 3\">"));
+  assert!(xml.contains("expected 2 but was 3\u{FFFD}\n"));
+  assert!(xml.contains("<skipped message=\"not ready\"/>"));
+}
+
+// `build tests` reports counts and failures as JSON.
+#[test]
+#[serial]
+fn tests_emit_json() {
+  let Some(jt) = JenkinsTest::setup() else {
+    return;
+  };
+
+  jt.cmd()
+    .args(["job", "run", "test-report-job"])
+    .timeout(Duration::from_secs(60))
+    .assert()
+    .code(3);
+
+  let output = jt
+    .cmd()
+    .args(["--output", "json", "build", "tests", "test-report-job"])
+    .arg("lastBuild")
+    .output()
+    .unwrap();
+  assert!(output.status.success());
+  let report: serde_json::Value =
+    serde_json::from_slice(&output.stdout).unwrap();
+  assert_eq!(report["job"], "test-report-job");
+  assert_eq!(report["build"], "lastBuild");
+  assert_eq!(report["passed"], 1);
+  assert_eq!(report["failed"], 1);
+  assert_eq!(report["skipped"], 1);
+  assert_eq!(report["duration_ms"], 750);
+  let failures = report["failures"].as_array().unwrap();
+  assert_eq!(failures.len(), 1);
+  assert_eq!(failures[0]["class_name"], "com.example.MathTest");
+  assert_eq!(failures[0]["name"], "divides");
+}
+
+// A matrix job's report, aggregated over its configurations, has no pass
+// count of its own.  The fake only: the live server has no matrix jobs.
+#[test]
+#[serial]
+fn tests_summarise_aggregated_report() {
+  let Some(jt) = JenkinsTest::setup() else {
+    return;
+  };
+  let Some(fake) = jt.fake.as_ref() else {
+    return;
+  };
+  fake.job_add(
+    "matrix-test-job".parse().unwrap(),
+    FakeJob::new(|_| {
+      FakeScript::new().tests("unit", unit_tests()).tests(
+        "integration",
+        vec![FakeTestCase::passed("com.example.ApiTest", "serves")
+          .duration(Duration::from_millis(250))],
+      )
+    })
+    .tests_aggregated(),
+  );
+
+  jt.cmd()
+    .args(["job", "run", "matrix-test-job"])
+    .timeout(Duration::from_secs(60))
+    .assert()
+    .code(3);
+
+  let junit = tempfile::NamedTempFile::new().expect("JUnit file");
+  jt.cmd()
+    .args(["build", "tests", "matrix-test-job", "lastBuild", "--junit"])
+    .arg(junit.path())
+    .assert()
+    .success()
+    .stdout(predicate::str::contains(
+      "Tests: 2 passed, 1 failed, 1 skipped (1s)",
+    ))
+    .stdout(predicate::str::contains("FAILED com.example.MathTest.divides"));
+
+  let xml = std::fs::read_to_string(junit.path()).unwrap();
+  assert!(xml.contains("<testsuite name=\"unit\""));
+  assert!(xml.contains("<testsuite name=\"integration\""));
+}
+
+// --- record and replay ---
+
+// A recorded run plays back to the same output with the server gone, and the
+// recording holds no credentials.
+#[test]
+#[serial]
+fn run_replays_recording() {
+  let Some(mut jt) = JenkinsTest::setup() else {
+    return;
+  };
+
+  let dir = tempfile::tempdir().expect("recording directory");
+  let recording = dir.path().join("session.ndjson");
+  let run = [
+    "job",
+    "run",
+    "build-with-parameters-test",
+    "-P",
+    "foo=recorded",
+  ];
+  let live = jt
+    .cmd()
+    .arg("--record")
+    .arg(&recording)
+    .args(run)
+    .timeout(Duration::from_secs(60))
+    .output()
+    .unwrap();
+  assert!(live.status.success());
+  assert!(String::from_utf8_lossy(&live.stdout).contains("foo=recorded"));
+
+  let text = std::fs::read_to_string(&recording).expect("read recording");
+  assert!(text.contains("[redacted]"), "no redacted headers: {}", text);
+  assert!(!text.contains("Basic "), "credentials recorded: {}", text);
+
+  drop(jt.fake.take());
+  let replayed = jt
+    .cmd()
+    .arg("--replay")
+    .arg(&recording)
+    .args(run)
+    .timeout(Duration::from_secs(60))
+    .output()
+    .unwrap();
+  assert!(replayed.status.success());
+  assert_eq!(
+    String::from_utf8_lossy(&replayed.stdout),
+    String::from_utf8_lossy(&live.stdout)
+  );
+}
+
+// Parameters are hashed into a different order by each process, so a run with
+// several of them replays only if the query string is order-independent.
+// Replaying a few times gives a regression every chance to show.
+#[test]
+#[serial]
+fn run_replays_recording_with_several_params() {
+  let Some(mut jt) = JenkinsTest::setup() else {
+    return;
+  };
+
+  let dir = tempfile::tempdir().expect("recording directory");
+  let recording = dir.path().join("session.ndjson");
+  let run = [
+    "job",
+    "run",
+    "build-with-parameters-test",
+    "-P",
+    "foo=recorded",
+    "-P",
+    "bar=true",
+    "-P",
+    "baz=two",
+  ];
+  let live = jt
+    .cmd()
+    .arg("--record")
+    .arg(&recording)
+    .args(run)
+    .timeout(Duration::from_secs(60))
+    .output()
+    .unwrap();
+  assert!(live.status.success());
+
+  drop(jt.fake.take());
+  for _ in 0..5 {
+    let replayed = jt
+      .cmd()
+      .arg("--replay")
+      .arg(&recording)
+      .args(run)
+      .timeout(Duration::from_secs(60))
+      .output()
+      .unwrap();
+    assert!(
+      replayed.status.success(),
+      "replay failed: {}",
+      String::from_utf8_lossy(&replayed.stderr)
+    );
+    assert_eq!(
+      String::from_utf8_lossy(&replayed.stdout),
+      String::from_utf8_lossy(&live.stdout)
+    );
+  }
+}
+
+// A request the recording has no answer for fails rather than reaching the
+// server.
+#[test]
+#[serial]
+fn replay_rejects_unrecorded_request() {
+  let Some(jt) = JenkinsTest::setup() else {
+    return;
+  };
+
+  let dir = tempfile::tempdir().expect("recording directory");
+  let recording = dir.path().join("session.ndjson");
+  jt.cmd()
+    .arg("--record")
+    .arg(&recording)
+    .args(["job", "params", "no-params-job"])
+    .assert()
+    .success();
+
+  jt.cmd()
+    .arg("--replay")
+    .arg(&recording)
+    .args(["job", "params", "build-with-parameters-test"])
+    .assert()
+    .failure()
+    .stderr(predicate::str::contains("recording has no response for GET"));
+}
+
+// --- job aliases ---
+
+// An alias expands to its server, job path and parameters, and -P overrides
+// the alias's parameters one at a time.
+#[test]
+#[serial]
+fn run_job_alias() {
+  let Some(jt) = JenkinsTest::setup() else {
+    return;
+  };
+  jt.config_append(
+    "[jobs.params-alias]\n\
+     server = \"test\"\n\
+     job = \"build-with-parameters-test\"\n\
+     params = { foo = \"from-alias\", bar = true }\n\
+     \n\
+     [jobs.nested]\n\
+     job = \"folder-test/nested-job\"\n\
+     params = { greeting = \"aliased\" }\n",
+  );
+
+  jt.cmd()
+    .args(["job", "run", "params-alias", "-P", "baz=two"])
+    .timeout(Duration::from_secs(60))
+    .assert()
+    .success()
+    .stdout(predicate::str::contains("foo=from-alias"))
+    .stdout(predicate::str::contains("bar=true"))
+    .stdout(predicate::str::contains("baz=two"));
+
+  jt.cmd()
+    .args(["job", "run", "params-alias", "-P", "foo=from-cli"])
+    .timeout(Duration::from_secs(60))
+    .assert()
+    .success()
+    .stdout(predicate::str::contains("foo=from-cli"))
+    .stdout(predicate::str::contains("bar=true"));
+
+  jt.cmd()
+    .args(["job", "run", "nested"])
+    .timeout(Duration::from_secs(60))
+    .assert()
+    .success()
+    .stdout(predicate::str::contains("nested says aliased"));
+}
+
+// An alias's follow_downstream applies without the flag.
+#[test]
+#[serial]
+fn run_job_alias_follows_downstream() {
+  let Some(jt) = JenkinsTest::setup() else {
+    return;
+  };
+  jt.config_append(
+    "[jobs.parent]\n\
+     job = \"downstream-parent\"\n\
+     follow_downstream = true\n",
+  );
+
+  jt.cmd()
+    .args(["job", "run", "parent"])
+    .timeout(Duration::from_secs(90))
+    .assert()
+    .success()
+    .stdout(predicate::str::contains("no parameters needed"));
+}
+
+// `build view` of an alias shows the aliased job, taking the alias's
+// follow_downstream without the flag.
+#[test]
+#[serial]
+fn view_job_alias_follows_downstream() {
+  let Some(jt) = JenkinsTest::setup() else {
+    return;
+  };
+  jt.config_append(
+    "[jobs.parent]\n\
+     job = \"downstream-parent\"\n\
+     follow_downstream = true\n",
+  );
+
+  let output = jt
+    .cmd()
+    .args(["job", "run", "downstream-parent"])
+    .timeout(Duration::from_secs(90))
+    .output()
+    .unwrap();
+  assert!(output.status.success());
+  let build = build_number_of(&String::from_utf8_lossy(&output.stdout))
+    .expect("build number in log")
+    .to_string();
+
+  jt.cmd()
+    .args(["build", "view", "parent", &build])
+    .timeout(Duration::from_secs(60))
+    .assert()
+    .success()
+    .stdout(predicate::str::contains(format!("Build #{}", build)))
+    .stdout(predicate::str::contains("Downstream:"))
+    .stdout(predicate::str::contains("no parameters needed"));
+}
+
+// `queue list --job` and `queue cancel` take an alias for the job it names.
+// Needs the fake to hold the build in the queue.
+#[test]
+#[serial]
+fn queue_job_alias() {
+  let Some(jt) = JenkinsTest::setup() else {
+    return;
+  };
+  let Some(fake) = jt.fake.as_ref() else {
+    return;
+  };
+  jt.config_append(
+    "[jobs.quiet]\n\
+     job = \"no-params-job\"\n",
+  );
+  fake.executors_set(0);
+
+  let mut queued = jt
+    .std_cmd()
+    .args(["job", "run", "quiet"])
+    .stdout(std::process::Stdio::null())
+    .stderr(std::process::Stdio::null())
+    .spawn()
+    .expect("spawn no-params-job");
+  std::thread::sleep(Duration::from_secs(3));
+
+  jt.cmd()
+    .args(["queue", "list", "--job", "quiet"])
+    .assert()
+    .success()
+    .stdout(predicate::str::contains("no-params-job"));
+
+  jt.cmd()
+    .args(["queue", "cancel", "quiet"])
+    .assert()
+    .success()
+    .stdout(predicate::str::contains("Cancelled queue item"));
+
+  let status = queued.wait().expect("wait for no-params-job");
+  assert!(!status.success());
+}
+
+// A misspelled setting in an alias is refused rather than ignored.
+#[test]
+#[serial]
+fn job_alias_rejects_unknown_setting() {
+  let Some(jt) = JenkinsTest::setup() else {
+    return;
+  };
+  jt.config_append(
+    "[jobs.typo]\n\
+     job = \"no-params-job\"\n\
+     parms = { foo = \"x\" }\n",
+  );
+
+  jt.cmd()
+    .args(["job", "run", "typo"])
+    .assert()
+    .failure()
+    .stderr(predicate::str::contains("parms"));
+}
+
+// --- job paths ---
+
+// Run a job that lives inside a folder, addressing it by its full path, and
+// confirm both the enqueue and the log stream resolve the nested URL.
+#[test]
+#[serial]
+fn run_job_in_folder() {
+  let Some(jt) = JenkinsTest::setup() else {
+    return;
+  };
+
+  jt.cmd()
+    .args(["job", "run", "folder-test/nested-job", "-P", "greeting=hi"])
+    .timeout(Duration::from_secs(60))
+    .assert()
+    .success()
+    .stdout(predicate::str::contains("nested says hi"));
+}
+
+// --- parameters ---
+
+#[test]
+#[serial]
+fn run_job_without_params() {
+  let Some(jt) = JenkinsTest::setup() else {
+    return;
+  };
+
+  jt.cmd()
+    .args(["job", "run", "no-params-job"])
+    .timeout(Duration::from_secs(60))
+    .assert()
+    .success()
+    .stdout(predicate::str::contains("no parameters needed"));
+}
+
+// Parameter files of each format merge in the order given, and -P overrides
+// them all.
+#[test]
+#[serial]
+fn run_with_param_files() {
+  let Some(jt) = JenkinsTest::setup() else {
+    return;
+  };
+
+  let dir = tempfile::tempdir().expect("parameter file directory");
+  let toml = dir.path().join("params.toml");
+  std::fs::write(&toml, "foo = \"from-toml\"\nbar = true\n").unwrap();
+  let env = dir.path().join("params.env");
+  std::fs::write(&env, "# overrides foo\nexport foo=\"from-env\"\nbaz=two\n")
+    .unwrap();
+  let json = dir.path().join("params.json");
+  std::fs::write(&json, r#"{ "baz": "one" }"#).unwrap();
+
+  jt.cmd()
+    .args(["job", "run", "build-with-parameters-test", "--param-file"])
+    .arg(&toml)
+    .arg("--param-file")
+    .arg(&env)
+    .timeout(Duration::from_secs(60))
+    .assert()
+    .success()
+    .stdout(predicate::str::contains("foo=from-env"))
+    .stdout(predicate::str::contains("bar=true"))
+    .stdout(predicate::str::contains("baz=two"));
+
+  jt.cmd()
+    .args(["job", "run", "build-with-parameters-test", "--param-file"])
+    .arg(&env)
+    .arg("--param-file")
+    .arg(&json)
+    .args(["-P", "foo=from-cli"])
+    .timeout(Duration::from_secs(60))
+    .assert()
+    .success()
+    .stdout(predicate::str::contains("foo=from-cli"))
+    .stdout(predicate::str::contains("baz=one"));
+}
+
+// A JSON object on stdin can carry values -P cannot, such as multi-line text.
+#[test]
+#[serial]
+fn run_with_params_from_stdin() {
+  let Some(jt) = JenkinsTest::setup() else {
+    return;
+  };
+
+  jt.cmd()
+    .args([
+      "job",
+      "run",
+      "build-with-parameters-test",
+      "--params-from-stdin",
+    ])
+    .args(["-P", "bar=true"])
+    .write_stdin(r#"{ "foo": "line one\nline two", "bar": false }"#)
+    .timeout(Duration::from_secs(60))
+    .assert()
+    .success()
+    .stdout(predicate::str::contains("foo=line one\n"))
+    .stdout(predicate::str::contains("] line two\n"))
+    .stdout(predicate::str::contains("bar=true"));
+
+  jt.cmd()
+    .args([
+      "job",
+      "run",
+      "build-with-parameters-test",
+      "--params-from-stdin",
+    ])
+    .write_stdin(r#"{ "foo": ["not", "a", "string"] }"#)
+    .assert()
+    .failure()
+    .stderr(predicate::str::contains(
+      "Parameter 'foo' in stdin must be a string, boolean or number",
+    ));
+}
+
+// -F uploads a file as multipart form data, carrying -P values alongside it.
+#[test]
+#[serial]
+fn run_with_file_param() {
+  let Some(jt) = JenkinsTest::setup() else {
+    return;
+  };
+
+  let dir = tempfile::tempdir().expect("file parameter directory");
+  let bundle = dir.path().join("bundle.txt");
+  std::fs::write(&bundle, "bundle contents\n").unwrap();
+
+  jt.cmd()
+    .args(["job", "run", "file-param-job", "-P", "label=uploaded", "-F"])
+    .arg(format!("bundle=@{}", bundle.display()))
+    .timeout(Duration::from_secs(60))
+    .assert()
+    .success()
+    .stdout(predicate::str::contains("label=uploaded"))
+    .stdout(predicate::str::contains("bundle=bundle contents"));
+}
+
+// A file sent to anything but a file parameter would be dropped by Jenkins, so
+// jj refuses it before enqueueing.
+#[test]
+#[serial]
+fn run_rejects_non_file_param_upload() {
+  let Some(jt) = JenkinsTest::setup() else {
+    return;
+  };
+
+  let dir = tempfile::tempdir().expect("file parameter directory");
+  let bundle = dir.path().join("bundle.txt");
+  std::fs::write(&bundle, "bundle contents\n").unwrap();
+
+  jt.cmd()
+    .args(["job", "run", "file-param-job", "-F"])
+    .arg(format!("label=@{}", bundle.display()))
+    .assert()
+    .failure()
+    .stderr(predicate::str::contains(
+      "Job 'file-param-job' has no file parameter named 'label'; file parameters: [bundle]",
+    ));
+
+  jt.cmd()
+    .args(["job", "run", "file-param-job", "-F"])
+    .arg(format!("bundle={}", bundle.display()))
+    .assert()
+    .failure()
+    .stderr(predicate::str::contains("invalid NAME=@path"));
+}
+
+#[test]
+#[serial]
+fn params_lists_definitions() {
+  let Some(jt) = JenkinsTest::setup() else {
+    return;
+  };
+
+  jt.cmd()
+    .args(["job", "params", "build-with-parameters-test"])
+    .assert()
+    .success()
+    .stdout(predicate::str::contains("foo (string, default: default-foo)"))
+    .stdout(predicate::str::contains("bar (boolean, default: false)"))
+    .stdout(predicate::str::contains("baz (choice: one|two, default: one)"));
+}
+
+// A misspelled parameter must be rejected before anything is enqueued, rather
+// than silently running the build with its defaults.
+#[test]
+#[serial]
+fn run_rejects_unknown_param() {
+  let Some(jt) = JenkinsTest::setup() else {
+    return;
+  };
+
+  jt.cmd()
+    .args(["job", "run", "build-with-parameters-test", "-P", "fooo=x"])
+    .assert()
+    .failure()
+    .stderr(predicate::str::contains("no parameter named 'fooo'"));
+}
+
+#[test]
+#[serial]
+fn run_rejects_invalid_choice_and_boolean() {
+  let Some(jt) = JenkinsTest::setup() else {
+    return;
+  };
+
+  jt.cmd()
+    .args([
+      "job",
+      "run",
+      "build-with-parameters-test",
+      "-P",
+      "baz=three",
+    ])
+    .assert()
+    .failure()
+    .stderr(predicate::str::contains("must be one of [one, two]"));
+
+  jt.cmd()
+    .args(["job", "run", "build-with-parameters-test", "-P", "bar=yes"])
+    .assert()
+    .failure()
+    .stderr(predicate::str::contains("expected true or false"));
+}
+
+// A rebuild carries the original build's parameters over, with -P overriding
+// individual ones.
+#[test]
+#[serial]
+fn rebuild_reuses_params() {
+  let Some(jt) = JenkinsTest::setup() else {
+    return;
+  };
+
+  let output = jt
+    .cmd()
+    .args([
+      "job",
+      "run",
+      "build-with-parameters-test",
+      "-P",
+      "foo=original",
+      "-P",
+      "baz=two",
+    ])
+    .timeout(Duration::from_secs(60))
+    .output()
+    .expect("run build-with-parameters-test");
+  assert!(output.status.success());
+  let number = build_number_of(&String::from_utf8_lossy(&output.stdout))
+    .expect("build number prefix in log");
+
+  jt.cmd()
+    .args([
+      "build",
+      "rebuild",
+      "build-with-parameters-test",
+      &number.to_string(),
+      "-P",
+      "foo=override",
+    ])
+    .timeout(Duration::from_secs(60))
+    .assert()
+    .success()
+    .stdout(predicate::str::contains("foo=override"))
+    .stdout(predicate::str::contains("baz=two"));
+}
+
+// Reads the build number from the "[N] " prefix jj puts on streamed log lines,
+// skipping the ANSI color codes around it.
+fn build_number_of(log: &str) -> Option<u64> {
+  let mut chunks = log.split('\u{1b}');
+  // Only what follows an escape character starts with a color code.
+  let plain = chunks
+    .next()
+    .into_iter()
+    .chain(chunks.map(|chunk| {
+      chunk
+        .strip_prefix('[')
+        .and_then(|rest| rest.find('m').map(|end| &rest[end + 1..]))
+        .unwrap_or(chunk)
+    }))
+    .collect::<String>();
+  plain
+    .strip_prefix('[')
+    .and_then(|rest| rest.split(']').next())
+    .and_then(|n| n.parse().ok())
+}
@@ -1,8 +1,63 @@
+use std::fmt;
+use std::str::FromStr;
+use thiserror::Error;
+
 pub struct BuildRef {
   pub number: u64,
   pub url: String,
 }
 
+/// Names a build of a job, either by number or by one of the permalinks
+/// Jenkins keeps for every job, such as `lastSuccessfulBuild`.  Displays as
+/// the URL segment Jenkins serves the build under.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum BuildSelector {
+  Number(u64),
+  Permalink(&'static str),
+}
+
+#[derive(Debug, Error)]
+#[error(
+  "'{0}' is neither a build number nor one of: {permalinks}",
+  permalinks = BuildSelector::PERMALINKS.join(", ")
+)]
+pub struct BuildSelectorError(String);
+
+impl BuildSelector {
+  pub const PERMALINKS: [&'static str; 6] = [
+    "lastBuild",
+    "lastCompletedBuild",
+    "lastSuccessfulBuild",
+    "lastStableBuild",
+    "lastFailedBuild",
+    "lastUnsuccessfulBuild",
+  ];
+}
+
+impl FromStr for BuildSelector {
+  type Err = BuildSelectorError;
+
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    if let Ok(number) = s.parse() {
+      return Ok(BuildSelector::Number(number));
+    }
+    BuildSelector::PERMALINKS
+      .iter()
+      .find(|p| **p == s)
+      .map(|p| BuildSelector::Permalink(p))
+      .ok_or_else(|| BuildSelectorError(s.to_string()))
+  }
+}
+
+impl fmt::Display for BuildSelector {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      BuildSelector::Number(number) => write!(f, "{}", number),
+      BuildSelector::Permalink(permalink) => write!(f, "{}", permalink),
+    }
+  }
+}
+
 pub enum BuildStatus {
   Running,
   Success,
@@ -26,4 +81,39 @@ impl BuildStatus {
       BuildStatus::Unknown(_) => BuildExitCode(4),
     }
   }
+
+  // Of two results, the one that says more about what went wrong, following
+  // Jenkins' own ordering: SUCCESS < UNSTABLE < FAILURE < NOT_BUILT < ABORTED.
+  // Used to fold the results of several builds into one.
+  pub fn worst(self, other: BuildStatus) -> BuildStatus {
+    if other.severity() > self.severity() {
+      other
+    } else {
+      self
+    }
+  }
+
+  fn severity(&self) -> u8 {
+    match self {
+      BuildStatus::Success => 0,
+      BuildStatus::Unstable => 1,
+      BuildStatus::Failure | BuildStatus::Running => 2,
+      BuildStatus::Unknown(_) => 3,
+      BuildStatus::Aborted => 4,
+    }
+  }
+}
+
+impl fmt::Display for BuildStatus {
+  // Matches the result strings Jenkins itself reports.
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      BuildStatus::Running => write!(f, "RUNNING"),
+      BuildStatus::Success => write!(f, "SUCCESS"),
+      BuildStatus::Failure => write!(f, "FAILURE"),
+      BuildStatus::Aborted => write!(f, "ABORTED"),
+      BuildStatus::Unstable => write!(f, "UNSTABLE"),
+      BuildStatus::Unknown(other) => write!(f, "{}", other),
+    }
+  }
 }
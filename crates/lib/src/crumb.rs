@@ -0,0 +1,203 @@
+// Jenkins guards mutating requests against CSRF with a "crumb": a token issued
+// by /crumbIssuer/api/json that must be echoed back in a request header.
+// Servers accept API-token authentication without one, but users who log in
+// with a password are refused with a 403 unless the crumb is present.  The
+// crumb is bound to the HTTP session it was issued in, so the session cookie
+// from the issuer's response has to travel with it.
+//
+// This lives in middleware so every request path gets it for free; callers of
+// JenkinsClient::request never see crumbs at all.
+use async_trait::async_trait;
+use http::Extensions;
+use reqwest::header::{HeaderName, HeaderValue, AUTHORIZATION, COOKIE};
+use reqwest::{Method, Request, Response, StatusCode};
+use reqwest_middleware::{Middleware, Next};
+use serde::Deserialize;
+use tokio::sync::Mutex;
+use tracing::*;
+
+use crate::error::JenkinsError;
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct JenkinsCrumb {
+  crumb: String,
+  crumb_request_field: String,
+}
+
+#[derive(Clone, Debug)]
+enum CrumbState {
+  // The server has no crumb issuer (it answers 404), so no header is needed.
+  Disabled,
+  Issued {
+    header: HeaderName,
+    value: HeaderValue,
+    // The issuer's session cookie(s), ready to send as a Cookie header.
+    cookie: Option<HeaderValue>,
+  },
+}
+
+pub struct CrumbMiddleware {
+  host_url: String,
+  // None until the first mutating request asks for a crumb.  A tokio Mutex so
+  // the lock can be held across the issuer fetch, keeping concurrent requests
+  // from each fetching their own crumb.
+  state: Mutex<Option<CrumbState>>,
+}
+
+impl CrumbMiddleware {
+  pub fn new(host_url: &str) -> Self {
+    CrumbMiddleware {
+      host_url: host_url.to_string(),
+      state: Mutex::new(None),
+    }
+  }
+
+  async fn crumb(
+    &self,
+    authorization: Option<&HeaderValue>,
+    extensions: &mut Extensions,
+    next: Next<'_>,
+  ) -> reqwest_middleware::Result<CrumbState> {
+    let mut state = self.state.lock().await;
+    if let Some(crumb) = state.as_ref() {
+      return Ok(crumb.clone());
+    }
+    let crumb = self.crumb_fetch(authorization, extensions, next).await?;
+    *state = Some(crumb.clone());
+    Ok(crumb)
+  }
+
+  async fn crumb_fetch(
+    &self,
+    authorization: Option<&HeaderValue>,
+    extensions: &mut Extensions,
+    next: Next<'_>,
+  ) -> reqwest_middleware::Result<CrumbState> {
+    let url = format!("{}/crumbIssuer/api/json", self.host_url);
+    debug!("Fetching crumb from '{}'", url);
+    let mut request = Request::new(
+      Method::GET,
+      url
+        .parse()
+        .map_err(|e| crumb_error(JenkinsError::CrumbUrl(url.clone(), e)))?,
+    );
+    if let Some(auth) = authorization {
+      request.headers_mut().insert(AUTHORIZATION, auth.clone());
+    }
+    let response = next.run(request, extensions).await?;
+    match response.status() {
+      StatusCode::NOT_FOUND => {
+        debug!("Server has no crumb issuer; sending requests without one.");
+        Ok(CrumbState::Disabled)
+      }
+      status if status.is_success() => {
+        let cookie = session_cookie(&response).map_err(crumb_error)?;
+        let text = response.text().await?;
+        let crumb: JenkinsCrumb = serde_json::from_str(&text)
+          .map_err(|e| crumb_error(JenkinsError::CrumbDeserialize(e)))?;
+        Ok(CrumbState::Issued {
+          header: HeaderName::from_bytes(crumb.crumb_request_field.as_bytes())
+            .map_err(|e| crumb_error(JenkinsError::CrumbHeaderName(e)))?,
+          value: HeaderValue::from_str(&crumb.crumb)
+            .map_err(|e| crumb_error(JenkinsError::CrumbHeaderValue(e)))?,
+          cookie,
+        })
+      }
+      status => Err(crumb_error(JenkinsError::CrumbIssuer(url, status))),
+    }
+  }
+}
+
+#[async_trait]
+impl Middleware for CrumbMiddleware {
+  async fn handle(
+    &self,
+    mut request: Request,
+    extensions: &mut Extensions,
+    next: Next<'_>,
+  ) -> reqwest_middleware::Result<Response> {
+    if !is_mutating(request.method()) {
+      return next.run(request, extensions).await;
+    }
+    let authorization = request.headers().get(AUTHORIZATION).cloned();
+    let crumb = self
+      .crumb(authorization.as_ref(), extensions, next.clone())
+      .await?;
+    // Kept before the crumb is attached so a retry can carry a fresh one.
+    let retry = request.try_clone();
+    let url = request.url().to_string();
+    crumb_apply(&mut request, &crumb);
+    let response = next.clone().run(request, extensions).await?;
+    match (response.status(), retry) {
+      // Jenkins answers an expired or missing crumb with a plain 403, which is
+      // indistinguishable by status from a permission failure.  Refreshing and
+      // retrying once covers the former; the latter simply 403s again.
+      (StatusCode::FORBIDDEN, Some(mut retry)) => {
+        debug!("Mutating request was refused; refreshing crumb and retrying.");
+        *self.state.lock().await = None;
+        let crumb = self
+          .crumb(authorization.as_ref(), extensions, next.clone())
+          .await?;
+        crumb_apply(&mut retry, &crumb);
+        next.run(retry, extensions).await
+      }
+      // A streamed body, such as a file parameter's upload, is gone once sent,
+      // so there is nothing to retry with.  Passing the 403 on would leave the
+      // caller to report a bare refusal.
+      (StatusCode::FORBIDDEN, None)
+        if matches!(crumb, CrumbState::Issued { .. }) =>
+      {
+        Err(crumb_error(JenkinsError::CrumbRetryBody(url)))
+      }
+      _ => Ok(response),
+    }
+  }
+}
+
+fn is_mutating(method: &Method) -> bool {
+  !matches!(*method, Method::GET | Method::HEAD | Method::OPTIONS)
+}
+
+fn crumb_apply(request: &mut Request, crumb: &CrumbState) {
+  if let CrumbState::Issued {
+    header,
+    value,
+    cookie,
+  } = crumb
+  {
+    request.headers_mut().insert(header.clone(), value.clone());
+    if let Some(cookie) = cookie {
+      request.headers_mut().insert(COOKIE, cookie.clone());
+    }
+  }
+}
+
+// Reduces each Set-Cookie header to its name=value pair, dropping attributes
+// such as Path and HttpOnly, and joins them into a single Cookie header.
+fn session_cookie(
+  response: &Response,
+) -> Result<Option<HeaderValue>, JenkinsError> {
+  let pairs = response
+    .headers()
+    .get_all(reqwest::header::SET_COOKIE)
+    .iter()
+    .map(|v| v.to_str().map_err(JenkinsError::Header))
+    .collect::<Result<Vec<&str>, JenkinsError>>()?
+    .into_iter()
+    .filter_map(|v| v.split(';').next())
+    .map(str::trim)
+    .filter(|pair| !pair.is_empty())
+    .collect::<Vec<&str>>();
+  if pairs.is_empty() {
+    Ok(None)
+  } else {
+    HeaderValue::from_str(&pairs.join("; "))
+      .map(Some)
+      .map_err(JenkinsError::CrumbHeaderValue)
+  }
+}
+
+fn crumb_error(error: JenkinsError) -> reqwest_middleware::Error {
+  reqwest_middleware::Error::middleware(error)
+}
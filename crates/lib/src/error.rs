@@ -0,0 +1,162 @@
+use thiserror::Error;
+
+/// Failures talking to Jenkins, named for what was being attempted.
+#[derive(Debug, Error)]
+pub enum JenkinsError {
+  #[error("Jenkins build not found in response headers")]
+  BuildNotFound,
+  #[error("Failed to parse build text size header")]
+  BuildParseTextSize,
+  #[error("Failed to serialize build parameters: {0}")]
+  BuildParamSerialize(serde_url_params::Error),
+  #[error("Failed to stream build log: {0}")]
+  BuildStream(reqwest_middleware::Error),
+  #[error("Failed to read build response body: {0}")]
+  BuildResponseRead(reqwest::Error),
+  #[error("Failed to deserialize Jenkins response: {0}")]
+  Deserialize(serde_json::Error),
+  #[error("Failed to enqueue Jenkins build: {0}")]
+  Enqueue(reqwest_middleware::Error),
+  #[error("Jenkins refused to enqueue the build with status {0}: {1}")]
+  EnqueueStatus(reqwest::StatusCode, String),
+  #[error("Queue item {0} was cancelled before it started a build")]
+  QueueItemCancelled(u32),
+  #[error("Failed to request the Jenkins queue: {0}")]
+  QueueRequest(reqwest_middleware::Error),
+  #[error("Failed to deserialize the Jenkins queue: {0}")]
+  QueueDeserialize(serde_json::Error),
+  #[error("Failed to identify a queue item's job: {0}")]
+  QueueItemJob(crate::job::JobPathError),
+  #[error("Failed to parse a queue item id from '{0}': {1}")]
+  QueueItemId(String, std::num::ParseIntError),
+  #[error("Failed to cancel Jenkins queue item: {0}")]
+  QueueCancel(reqwest_middleware::Error),
+  #[error("Jenkins refused to cancel queue item {0}: {1}")]
+  QueueCancelStatus(u32, reqwest::StatusCode),
+  #[error("Failed to stop Jenkins build: {0}")]
+  BuildStop(reqwest_middleware::Error),
+  #[error("Jenkins refused to {0} build at '{1}': {2}")]
+  BuildStopStatus(&'static str, String, reqwest::StatusCode),
+  #[error("Build at '{0}' was still running after stop, term, and kill")]
+  BuildStopTimeout(String),
+  #[error("Failed to parse response header value: {0}")]
+  Header(reqwest::header::ToStrError),
+  #[error("Failed to request Jenkins job builds: {0}")]
+  JobBuildsRequest(reqwest_middleware::Error),
+  #[error("Failed to deserialize Jenkins job builds response: {0}")]
+  JobBuildsDeserialize(serde_json::Error),
+  #[error("Failed to request Jenkins build detail: {0}")]
+  BuildDetailRequest(reqwest_middleware::Error),
+  #[error("Failed to deserialize Jenkins build detail: {0}")]
+  BuildDetailDeserialize(serde_json::Error),
+  #[error("Failed to fetch Jenkins build log: {0}")]
+  BuildLogFetch(reqwest_middleware::Error),
+  #[error("Failed to read Jenkins build log response: {0}")]
+  BuildLogRead(reqwest::Error),
+  #[error("Failed to write build log: {0}")]
+  BuildLogWrite(std::io::Error),
+  #[error("Failed to request Jenkins build result: {0}")]
+  BuildResultRequest(reqwest_middleware::Error),
+  #[error("Failed to deserialize Jenkins build result: {0}")]
+  BuildResultDeserialize(serde_json::Error),
+  #[error("Failed to request Jenkins Pipeline stages: {0}")]
+  WorkflowRequest(reqwest_middleware::Error),
+  #[error("Failed to deserialize Jenkins Pipeline stages: {0}")]
+  WorkflowDeserialize(serde_json::Error),
+  #[error("Failed to request the Jenkins build's pending inputs: {0}")]
+  InputRequest(reqwest_middleware::Error),
+  #[error("Failed to deserialize the Jenkins build's pending inputs: {0}")]
+  InputDeserialize(serde_json::Error),
+  #[error("Failed to answer a Jenkins input: {0}")]
+  InputSubmit(reqwest_middleware::Error),
+  #[error("Jenkins refused the answer to input '{0}' with status {1}")]
+  InputSubmitStatus(String, reqwest::StatusCode),
+  #[error("Failed to request Jenkins downstream builds: {0}")]
+  DownstreamRequest(reqwest_middleware::Error),
+  #[error("Failed to deserialize Jenkins downstream builds: {0}")]
+  DownstreamDeserialize(serde_json::Error),
+  #[error("Jenkins reported a downstream build of an unusable job: {0}")]
+  DownstreamJob(crate::job::JobPathError),
+  #[error("Failed to request Jenkins build artifacts: {0}")]
+  ArtifactsRequest(reqwest_middleware::Error),
+  #[error("Failed to deserialize Jenkins build artifacts: {0}")]
+  ArtifactsDeserialize(serde_json::Error),
+  #[error("Failed to form an artifact URL from build URL '{0}': {1}")]
+  ArtifactUrl(String, url::ParseError),
+  #[error("Failed to request artifact '{0}': {1}")]
+  ArtifactRequest(String, reqwest_middleware::Error),
+  #[error("Artifact not found (404): {0}")]
+  ArtifactNotFound(String),
+  #[error("Artifact request '{0}' failed with status {1}")]
+  ArtifactStatus(String, reqwest::StatusCode),
+  #[error("Artifact '{0}' reported an invalid size: {1}")]
+  ArtifactSize(String, std::num::ParseIntError),
+  #[error("Failed to read artifact '{0}': {1}")]
+  ArtifactRead(String, reqwest::Error),
+  #[error("Failed to request Jenkins test report: {0}")]
+  TestReportRequest(reqwest_middleware::Error),
+  #[error("Failed to deserialize Jenkins test report: {0}")]
+  TestReportDeserialize(serde_json::Error),
+  #[error("Failed to request Jenkins job: {0}")]
+  JobRequest(reqwest_middleware::Error),
+  #[error("Failed to deserialize Jenkins job: {0}")]
+  JobDeserialize(serde_json::Error),
+  #[error(
+    "Job '{job}' has no parameter named '{name}'; known parameters: [{known}]"
+  )]
+  ParamUnknown {
+    job: String,
+    name: String,
+    known: String,
+  },
+  #[error(
+    "Parameter '{name}' is a boolean; expected true or false, got '{value}'"
+  )]
+  ParamBoolean { name: String, value: String },
+  #[error("Parameter '{name}' must be one of [{choices}], got '{value}'")]
+  ParamChoice {
+    name: String,
+    value: String,
+    choices: String,
+  },
+  #[error(
+    "Job '{job}' has no file parameter named '{name}'; file parameters: [{known}]"
+  )]
+  FileParamUnknown {
+    job: String,
+    name: String,
+    known: String,
+  },
+  #[error("Failed to open file parameter '{0}': {1}")]
+  FileParamOpen(String, std::io::Error),
+  #[error("Failed to create recording '{0}': {1}")]
+  RecordCreate(String, std::io::Error),
+  #[error("Failed to serialize an exchange for the recording: {0}")]
+  RecordSerialize(serde_json::Error),
+  #[error("Failed to write to the recording: {0}")]
+  RecordWrite(std::io::Error),
+  #[error("Failed to read recording '{0}': {1}")]
+  ReplayOpen(String, std::io::Error),
+  #[error("Failed to parse line {1} of recording '{0}': {2}")]
+  ReplayParse(String, usize, serde_json::Error),
+  #[error("Recording holds an invalid URL '{0}': {1}")]
+  ReplayUrl(String, url::ParseError),
+  #[error("The recording has no response for {0}")]
+  ReplayMissing(String),
+  #[error("Failed to rebuild a recorded response: {0}")]
+  ResponseBuild(http::Error),
+  #[error("Invalid crumb issuer URL '{0}': {1}")]
+  CrumbUrl(String, url::ParseError),
+  #[error("Crumb issuer at '{0}' responded with {1}")]
+  CrumbIssuer(String, reqwest::StatusCode),
+  #[error("Failed to deserialize Jenkins crumb issuer response: {0}")]
+  CrumbDeserialize(serde_json::Error),
+  #[error("Crumb issuer returned an invalid header name: {0}")]
+  CrumbHeaderName(reqwest::header::InvalidHeaderName),
+  #[error("Crumb issuer returned an invalid header value: {0}")]
+  CrumbHeaderValue(reqwest::header::InvalidHeaderValue),
+  #[error(
+    "Jenkins refused '{0}' with 403 Forbidden, perhaps for an expired crumb, and its streamed body cannot be resent with a fresh one"
+  )]
+  CrumbRetryBody(String),
+}
@@ -0,0 +1,49 @@
+/// Matches a slash-separated path against a shell-style glob.
+///
+/// `*` matches any run of characters within one path segment, `?` matches a
+/// single character other than `/`, and a `**` segment matches any number of
+/// whole segments, including none.  So `dist/*.tar.gz` matches
+/// `dist/app.tar.gz` but not `dist/nested/app.tar.gz`, which `dist/**/*.tar.gz`
+/// matches along with the former.  There are no character classes or escapes;
+/// artifact paths have not needed them.
+pub fn glob_match(pattern: &str, path: &str) -> bool {
+  let pattern = pattern.split('/').collect::<Vec<&str>>();
+  let path = path.split('/').collect::<Vec<&str>>();
+  segments_match(&pattern, &path)
+}
+
+fn segments_match(pattern: &[&str], path: &[&str]) -> bool {
+  match (pattern.split_first(), path.split_first()) {
+    (None, None) => true,
+    (Some((&"**", rest)), _) => {
+      segments_match(rest, path)
+        || path
+          .split_first()
+          .is_some_and(|(_, path_rest)| segments_match(pattern, path_rest))
+    }
+    (Some((segment, rest)), Some((name, path_rest))) => {
+      segment_match(
+        &segment.chars().collect::<Vec<char>>(),
+        &name.chars().collect::<Vec<char>>(),
+      ) && segments_match(rest, path_rest)
+    }
+    _ => false,
+  }
+}
+
+fn segment_match(pattern: &[char], name: &[char]) -> bool {
+  match (pattern.split_first(), name.split_first()) {
+    (None, None) => true,
+    (Some(('*', rest)), _) => {
+      segment_match(rest, name)
+        || name
+          .split_first()
+          .is_some_and(|(_, name_rest)| segment_match(pattern, name_rest))
+    }
+    (Some(('?', rest)), Some((_, name_rest))) => segment_match(rest, name_rest),
+    (Some((p, rest)), Some((n, name_rest))) => {
+      p == n && segment_match(rest, name_rest)
+    }
+    _ => false,
+  }
+}
@@ -0,0 +1,1855 @@
+// When starting a build for a Jenkins job, this immediately puts the job into
+// the build queue.  The build queue might be consumed very quickly, and so
+// there's a challenge involved in picking up the associated job that came with
+// it.  Creating a job from the API isn't enough to get this information, and
+// documentation is very slim.
+//
+// The abstract documentation is here:
+// https://www.jenkins.io/doc/book/using/remote-access-api/
+//
+// Viewing specific documentation is much more difficult.  While there is
+// documentation to be found on the Jenkins server in question, the
+// documentation there doesn't lend itself well to API consumers who are looking
+// for strict contracts, documented edge cases, etc.  In absence of this hard
+// documentation, we will assume a defensive posture with Jenkins.
+use bytes::Bytes;
+use futures::stream::{self, Stream, TryStreamExt};
+use reqwest::multipart::{Form, Part};
+use reqwest_middleware::{ClientBuilder, ClientWithMiddleware, Middleware};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::path::{Path, PathBuf};
+use tracing::*;
+
+use crate::build::{BuildRef, BuildSelector, BuildStatus};
+use crate::crumb::CrumbMiddleware;
+use crate::error::JenkinsError;
+use crate::job::JobPath;
+use crate::lines::LineDecoder;
+use crate::retry::{is_transient_error, RetryMiddleware, RetryPolicy};
+use crate::session::{RecordMiddleware, ReplayMiddleware};
+use crate::sink::LogSink;
+
+/// A connection to one Jenkins server, authenticating every request as the
+/// given user.  Cloning is cheap and shares the underlying connection pool and
+/// CSRF crumb.
+#[derive(Clone)]
+pub struct JenkinsClient {
+  client: ClientWithMiddleware,
+  retry: RetryPolicy,
+  host_url: String,
+  username: String,
+  token: String,
+}
+
+impl JenkinsClient {
+  pub fn new(host_url: &str, username: &str, token: &str) -> Self {
+    JenkinsClient::with_retry(host_url, username, token, RetryPolicy::default())
+  }
+
+  // Like new, but retrying idempotent requests and log polls as `retry` says
+  // rather than by the default policy.
+  pub fn with_retry(
+    host_url: &str,
+    username: &str,
+    token: &str,
+    retry: RetryPolicy,
+  ) -> Self {
+    JenkinsClient {
+      client: middleware_chain(host_url, retry).build(),
+      retry,
+      host_url: host_url.to_string(),
+      username: username.to_string(),
+      token: token.to_string(),
+    }
+  }
+
+  /// Records every request to the server, and its response, to the file at
+  /// `path`, with credentials redacted.  Replaces any earlier recording or
+  /// replay on this client.
+  pub fn record_to(self, path: &Path) -> Result<Self, JenkinsError> {
+    let recorder = RecordMiddleware::create(path)?;
+    Ok(self.session_with(recorder))
+  }
+
+  /// Answers every request from the recording at `path` instead of the
+  /// server.  A request the recording has no answer for fails.  Replaces any
+  /// earlier recording or replay on this client.
+  pub fn replay_from(self, path: &Path) -> Result<Self, JenkinsError> {
+    let replayer = ReplayMiddleware::open(path)?;
+    Ok(self.session_with(replayer))
+  }
+
+  fn session_with<M: Middleware>(mut self, session: M) -> Self {
+    self.client = middleware_chain(&self.host_url, self.retry)
+      .with(session)
+      .build();
+    self
+  }
+
+  pub fn host_url(&self) -> &str {
+    &self.host_url
+  }
+}
+
+// Writes are never retried, so the order is not about them.  Crumbs are
+// outermost because the crumb middleware fetches its crumb through the rest of
+// the chain: beneath it, the retries cover that fetch like any other GET, so a
+// 502 from the issuer does not fail the write.  Anything added after this
+// chain sits beneath the retries, so a recording holds every attempt.
+fn middleware_chain(host_url: &str, retry: RetryPolicy) -> ClientBuilder {
+  ClientBuilder::new(reqwest::Client::new())
+    .with(CrumbMiddleware::new(host_url))
+    .with(RetryMiddleware::new(retry))
+}
+
+// Responses are consumed the moment you read in something like its body.  If
+// easily toggleable debugging is desired, reqwest::Response is not the way to
+// go - you will enter borrow-checker hell, from which there is no escape or
+// respite.  This is proven science.
+#[derive(Debug)]
+struct BufferedResponse {
+  headers: reqwest::header::HeaderMap,
+  status: reqwest::StatusCode,
+  text: String,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct JenkinsQueueItem {
+  #[serde(alias = "_class")]
+  pub class: String,
+  pub actions: Vec<JenkinsQueueItemAction>,
+  pub blocked: bool,
+  pub buildable: bool,
+  pub executable: Option<JenkinsQueueItemExecutable>,
+  pub id: u32,
+  pub in_queue_since: u64,
+  pub params: String,
+  pub task: JenkinsQueueItemTask,
+  pub url: String,
+  pub why: Option<String>,
+  // Only on waiting items: when the quiet period ends, in epoch milliseconds.
+  pub timestamp: Option<u64>,
+  // Only on items that have left the queue.
+  pub cancelled: Option<bool>,
+}
+
+/// Where a queue item is in its lifecycle.  Jenkins reports this through the
+/// item's `_class`: `hudson.model.Queue$WaitingItem`, `$BlockedItem`,
+/// `$BuildableItem`, or `$LeftItem`.
+#[derive(Debug, PartialEq)]
+pub enum QueueItemState {
+  /// In the quiet period, until the given epoch milliseconds if known.
+  Waiting { until: Option<u64> },
+  /// Held back, e.g. because another build of the job is in progress.
+  Blocked,
+  /// Ready to run and waiting for an executor.
+  Buildable,
+  /// Left the queue but not yet assigned a build.
+  Leaving,
+  /// Left the queue as the given build.
+  Started { url: String, number: u64 },
+  /// Left the queue without running.
+  Cancelled,
+}
+
+impl JenkinsQueueItem {
+  pub fn state(&self) -> QueueItemState {
+    match (self.class.rsplit('$').next(), &self.executable, self.cancelled) {
+      (_, Some(ex), _) => QueueItemState::Started {
+        url: ex.url.clone(),
+        number: ex.number,
+      },
+      (_, None, Some(true)) => QueueItemState::Cancelled,
+      (Some("LeftItem"), None, _) => QueueItemState::Leaving,
+      (Some("BlockedItem"), _, _) => QueueItemState::Blocked,
+      (Some("BuildableItem"), _, _) => QueueItemState::Buildable,
+      (Some("WaitingItem"), _, _) => QueueItemState::Waiting {
+        until: self.timestamp,
+      },
+      // Defensively fall back to the flags for item classes we have not seen,
+      // such as those contributed by plugins.
+      _ if self.blocked => QueueItemState::Blocked,
+      _ if self.buildable => QueueItemState::Buildable,
+      _ => QueueItemState::Waiting {
+        until: self.timestamp,
+      },
+    }
+  }
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct JenkinsQueueItemAction {
+  // Actions that export no data are serialized as an empty object.
+  #[serde(alias = "_class")]
+  pub class: Option<String>,
+  pub causes: Option<Vec<JenkinsQueueItemActionCause>>,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct JenkinsQueueItemExecutable {
+  #[serde(alias = "_class")]
+  pub class: String,
+  pub number: u64,
+  pub url: String,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct JenkinsQueueItemActionCause {
+  // This value is rather important.  One example is
+  // "hudson.model.Cause$UserIdCause" to indicate a user started the build
+  // manually.
+  #[serde(alias = "_class")]
+  pub class: String,
+  pub short_description: String,
+  // Only user-started causes carry these; timer, SCM, and upstream causes
+  // do not.
+  pub user_id: Option<String>,
+  pub user_name: Option<String>,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct JenkinsQueueItemTask {
+  #[serde(alias = "_class")]
+  pub class: String,
+  // Absent on the placeholder tasks a running Pipeline queues for its node
+  // blocks.
+  pub name: Option<String>,
+  pub url: Option<String>,
+  // Color is indicative of the build's status.
+  pub color: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct JenkinsQueue {
+  pub items: Vec<JenkinsQueueItem>,
+}
+
+impl JenkinsQueueItem {
+  // The job this item will build, when its task has a URL at all.
+  pub fn job(&self) -> Result<Option<JobPath>, JenkinsError> {
+    self
+      .task
+      .url
+      .as_deref()
+      .map(JobPath::from_url)
+      .transpose()
+      .map_err(JenkinsError::QueueItemJob)
+  }
+
+  // The ids of the users who requested this item.
+  pub fn user_ids(&self) -> Vec<&str> {
+    self
+      .actions
+      .iter()
+      .filter_map(|a| a.causes.as_ref())
+      .flatten()
+      .filter_map(|c| c.user_id.as_deref())
+      .collect()
+  }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct JenkinsBuildSummary {
+  pub number: u64,
+  pub url: String,
+  pub building: bool,
+  // "SUCCESS" | "FAILURE" | "ABORTED" | "UNSTABLE" | null while building.
+  // A finished build's result is better settled by build_result_wait, which
+  // handles the window where this is still null.
+  pub result: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct JenkinsBuildResult {
+  pub building: bool,
+  pub result: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct JenkinsJobBuilds {
+  pub builds: Vec<JenkinsBuildSummary>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct JenkinsJob {
+  // Job properties are heterogeneous; the one carrying parameterDefinitions is
+  // "hudson.model.ParametersDefinitionProperty".
+  #[serde(default)]
+  pub property: Vec<JenkinsJobProperty>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct JenkinsJobProperty {
+  pub parameter_definitions: Option<Vec<JenkinsParameterDefinition>>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct JenkinsParameterDefinition {
+  pub name: String,
+  // The definition's simple class name, e.g. "StringParameterDefinition" or
+  // "ChoiceParameterDefinition".  Plugins contribute their own.
+  #[serde(rename = "type")]
+  pub kind: String,
+  pub description: Option<String>,
+  pub default_parameter_value: Option<JenkinsParameterValue>,
+  // Only present on choice parameters.
+  pub choices: Option<Vec<String>>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct JenkinsParameterValue {
+  // Strings, booleans, and numbers all appear here depending on the type.
+  pub value: Option<serde_json::Value>,
+}
+
+impl JenkinsJob {
+  pub fn parameter_definitions(&self) -> Vec<&JenkinsParameterDefinition> {
+    self
+      .property
+      .iter()
+      .filter_map(|p| p.parameter_definitions.as_ref())
+      .flatten()
+      .collect()
+  }
+
+  pub fn is_parameterized(&self) -> bool {
+    !self.parameter_definitions().is_empty()
+  }
+}
+
+impl JenkinsParameterDefinition {
+  // "StringParameterDefinition" becomes "string", and so on.  Types that do
+  // not follow the naming convention are shown as Jenkins reports them.
+  pub fn kind_short(&self) -> String {
+    parameter_kind_short(&self.kind)
+  }
+
+  pub fn default_string(&self) -> Option<String> {
+    self
+      .default_parameter_value
+      .as_ref()
+      .and_then(|d| d.value.as_ref())
+      .map(parameter_value_string)
+  }
+
+  // Core's "FileParameterDefinition" and the file-parameters plugin's
+  // "StashedFileParameterDefinition" and "Base64FileParameterDefinition" all
+  // take an upload under the parameter's name.
+  pub fn is_file(&self) -> bool {
+    self.kind.ends_with("FileParameterDefinition")
+  }
+}
+
+fn parameter_kind_short(kind: &str) -> String {
+  kind
+    .strip_suffix("ParameterDefinition")
+    .map_or_else(|| kind.to_string(), str::to_lowercase)
+}
+
+// The form a parameter value takes on the query string: strings as they are,
+// booleans and numbers as their JSON text.
+fn parameter_value_string(value: &serde_json::Value) -> String {
+  match value {
+    serde_json::Value::String(s) => s.clone(),
+    other => other.to_string(),
+  }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+// Mirrors the Jenkins build-detail API response.
+pub struct JenkinsBuildDetail {
+  pub number: u64,
+  pub url: String,
+  pub building: bool,
+  pub result: Option<String>,
+  pub timestamp: u64,
+  pub duration: u64,
+  pub display_name: Option<String>,
+  pub actions: Vec<JenkinsBuildDetailAction>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+// Mirrors the Jenkins API.
+pub struct JenkinsBuildDetailAction {
+  #[serde(alias = "_class")]
+  pub class: Option<String>,
+  pub causes: Option<Vec<JenkinsBuildDetailCause>>,
+  pub last_built_revision: Option<JenkinsBuildDetailRevision>,
+  pub remote_urls: Option<Vec<String>>,
+  // Only present on "hudson.model.ParametersAction".
+  pub parameters: Option<Vec<JenkinsBuildParameter>>,
+  // Only present on the Pipeline build step's DownstreamBuildAction.
+  pub downstream_builds: Option<Vec<JenkinsDownstreamBuild>>,
+  // Only present on "hudson.tasks.junit.TestResultAction", the summary of the
+  // build's test report.
+  pub fail_count: Option<u64>,
+  pub skip_count: Option<u64>,
+  pub total_count: Option<u64>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct JenkinsDownstreamBuild {
+  pub job_full_name: String,
+  // Null until the triggered build leaves the queue.
+  pub build_number: Option<u64>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct JenkinsBuildParameter {
+  pub name: String,
+  // Absent for parameters Jenkins will not export, such as passwords and
+  // files.
+  pub value: Option<serde_json::Value>,
+}
+
+impl JenkinsBuildDetail {
+  // The values the build ran with.  Parameters without an exported value are
+  // left out, so a rebuild falls back to the job's default for them.
+  pub fn parameters(&self) -> HashMap<String, String> {
+    self
+      .actions
+      .iter()
+      .filter_map(|a| a.parameters.as_ref())
+      .flatten()
+      .filter_map(|p| {
+        p.value
+          .as_ref()
+          .map(|v| (p.name.clone(), parameter_value_string(v)))
+      })
+      .collect()
+  }
+
+  // Parameters the build had but whose values Jenkins did not export.
+  pub fn parameters_unexported(&self) -> Vec<&str> {
+    self
+      .actions
+      .iter()
+      .filter_map(|a| a.parameters.as_ref())
+      .flatten()
+      .filter(|p| p.value.is_none())
+      .map(|p| p.name.as_str())
+      .collect()
+  }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+// Mirrors the Jenkins API.
+pub struct JenkinsBuildDetailCause {
+  #[serde(alias = "_class")]
+  pub class: Option<String>,
+  pub short_description: Option<String>,
+  pub user_id: Option<String>,
+  pub user_name: Option<String>,
+  // Only present on "hudson.model.Cause$UpstreamCause".
+  pub upstream_project: Option<String>,
+  pub upstream_build: Option<u64>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct JenkinsBuildDetailRevision {
+  pub sha1: Option<String>,
+  pub branch: Option<Vec<JenkinsBuildDetailBranch>>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+// Mirrors the Jenkins API.
+pub struct JenkinsBuildDetailBranch {
+  pub name: Option<String>,
+  pub sha1: Option<String>,
+}
+
+async fn to_buffered_response(
+  r: reqwest::Response,
+) -> Result<BufferedResponse, JenkinsError> {
+  Ok(BufferedResponse {
+    headers: r.headers().clone(),
+    status: r.status(),
+    text: r
+      .text()
+      .await
+      .map_err(|e| JenkinsError::Enqueue(e.into()))?,
+  })
+}
+
+// Parameters are encoded in name order, so the same parameters always make the
+// same URL, which a replayed recording depends on.
+pub fn params_to_query_params(
+  params: &HashMap<String, String>,
+) -> Result<String, JenkinsError> {
+  serde_url_params::to_string(&params.iter().collect::<BTreeMap<_, _>>())
+    .map_err(JenkinsError::BuildParamSerialize)
+}
+
+// Each file is streamed from disk as the request is sent, so a large bundle is
+// never held in memory.  Its length is taken up front so the request still
+// carries a Content-Length rather than going out chunked.
+async fn params_form(
+  params: &HashMap<String, String>,
+  files: &HashMap<String, PathBuf>,
+) -> Result<Form, JenkinsError> {
+  let mut form = params.iter().fold(Form::new(), |form, (name, value)| {
+    form.text(name.clone(), value.clone())
+  });
+  for (name, path) in files {
+    let open_error =
+      |e| JenkinsError::FileParamOpen(path.display().to_string(), e);
+    let file = tokio::fs::File::open(path).await.map_err(open_error)?;
+    let length = file.metadata().await.map_err(open_error)?.len();
+    let file_name = path
+      .file_name()
+      .map_or_else(|| name.clone(), |f| f.to_string_lossy().into_owned());
+    form = form.part(
+      name.clone(),
+      Part::stream_with_length(file, length).file_name(file_name),
+    );
+  }
+  Ok(form)
+}
+
+impl JenkinsClient {
+  // Returns the link to the queue item.
+  //
+  // Jenkins refuses buildWithParameters for a job that defines no parameters,
+  // and build for one that does, so the job's definition decides the endpoint.
+  // File parameters go up as multipart form data, with the other parameters
+  // as text fields alongside them.
+  pub async fn build_enqueue(
+    &self,
+    job: &JobPath,
+    definition: &JenkinsJob,
+    params: &HashMap<String, String>,
+    files: &HashMap<String, PathBuf>,
+  ) -> Result<String, JenkinsError> {
+    let response = if files.is_empty() {
+      let url = if definition.is_parameterized() {
+        format!(
+          "{}{}/buildWithParameters?{}",
+          self.host_url,
+          job.url_path(),
+          params_to_query_params(params)?,
+        )
+      } else {
+        format!("{}{}/build", self.host_url, job.url_path())
+      };
+      debug!("Enqueueing at '{}'", url);
+      self
+        .request(
+          reqwest::Method::POST,
+          // I reckon this can't be borrowed because it's going into a Future.
+          url.clone(),
+        )
+        .await
+    } else {
+      let url =
+        format!("{}{}/buildWithParameters", self.host_url, job.url_path());
+      debug!("Enqueueing at '{}' with {} file(s)", url, files.len());
+      self
+        .request_builder(reqwest::Method::POST, url)
+        .multipart(params_form(params, files).await?)
+        .send()
+        .await
+    }
+    .map_err(JenkinsError::Enqueue)?;
+    let buffered_response = to_buffered_response(response).await?;
+    debug!(
+      "result? {}\n{}\n{}",
+      buffered_response.status,
+      headers_to_string(buffered_response.headers.clone())?,
+      buffered_response.text,
+    );
+    // A refusal, such as the 409 for a disabled job, explains itself in the
+    // body.
+    if !buffered_response.status.is_success() {
+      return Err(JenkinsError::EnqueueStatus(
+        buffered_response.status,
+        buffered_response.text.trim().to_string(),
+      ));
+    }
+    // TODO: Return a URL and make sure this is a URL.
+    // Unwrap it here so we can debug the output regardless of outcome.
+    let location = buffered_response
+      .headers
+      .get(reqwest::header::LOCATION)
+      .ok_or(JenkinsError::BuildNotFound)?
+      .to_str()
+      .map_err(JenkinsError::Header)?
+      .to_string();
+    Ok(location)
+  }
+}
+
+// Bounds on how long to wait between queue polls.  The lower bound keeps a
+// stale or skewed quiet-period timestamp from producing a busy loop; the upper
+// bound keeps jj responsive to items that start sooner than predicted.
+const QUEUE_POLL_MIN: std::time::Duration =
+  std::time::Duration::from_millis(250);
+const QUEUE_POLL_MAX: std::time::Duration = std::time::Duration::from_secs(2);
+
+fn queue_poll_delay(state: &QueueItemState) -> std::time::Duration {
+  match state {
+    QueueItemState::Waiting { until: Some(until) } => {
+      // The timestamp comes from the server's clock, so clock skew is
+      // absorbed by the clamp rather than trusted outright.
+      let now = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap_or(std::time::Duration::ZERO);
+      std::time::Duration::from_millis(*until)
+        .saturating_sub(now)
+        .clamp(QUEUE_POLL_MIN, QUEUE_POLL_MAX)
+    }
+    // "Finished waiting" is not quite the truth; the build URL takes a moment
+    // longer to appear.
+    QueueItemState::Leaving => QUEUE_POLL_MIN,
+    _ => QUEUE_POLL_MAX,
+  }
+}
+
+impl JenkinsClient {
+  // Polls the queue item until it leaves the queue.  Returns the build once it
+  // starts, or an error if it was cancelled.
+  pub async fn build_queue_item_poll(
+    &self,
+    url: String,
+  ) -> Result<BuildRef, JenkinsError> {
+    loop {
+      let item = self.build_queue_item_get(url.clone()).await?;
+      match item.state() {
+        QueueItemState::Started { url, number } => {
+          return Ok(BuildRef { number, url });
+        }
+        QueueItemState::Cancelled => {
+          return Err(JenkinsError::QueueItemCancelled(item.id));
+        }
+        state => {
+          info!(
+            id = item.id,
+            ?state,
+            why = item.why.as_deref().unwrap_or(""),
+            "Waiting in queue"
+          );
+          tokio::time::sleep(queue_poll_delay(&state)).await;
+        }
+      }
+    }
+  }
+
+  pub async fn queue_items(
+    &self,
+  ) -> Result<Vec<JenkinsQueueItem>, JenkinsError> {
+    let response = self
+      .request(
+        reqwest::Method::GET,
+        format!("{}/queue/api/json", self.host_url),
+      )
+      .await
+      .map_err(JenkinsError::QueueRequest)?;
+    let text = response
+      .text()
+      .await
+      .map_err(|e| JenkinsError::QueueRequest(e.into()))?;
+    serde_json::from_str::<JenkinsQueue>(&text)
+      .map(|queue| queue.items)
+      .map_err(JenkinsError::QueueDeserialize)
+  }
+
+  pub async fn build_queue_item_get(
+    &self,
+    url: String,
+  ) -> Result<JenkinsQueueItem, JenkinsError> {
+    let response = self
+      .request(
+        reqwest::Method::GET,
+        // See https://issues.jenkins.io/browse/JENKINS-45218 which indicates
+        // the URL needs an additional "/api/<format>" suffix to work.
+        format!("{}/api/json", url),
+      )
+      .await
+      .map_err(JenkinsError::Enqueue)?;
+    let buffered_response = to_buffered_response(response).await?;
+    debug!(
+      "result? {}\n{}\n{}",
+      buffered_response.status,
+      headers_to_string(buffered_response.headers)?,
+      buffered_response.text,
+    );
+    serde_json::from_str(&buffered_response.text)
+      .map_err(JenkinsError::Deserialize)
+  }
+
+  // Streams the build's log from byte `start_pos` on, following
+  // progressiveText for as long as Jenkins says there is more to come.  Each
+  // poll's text is yielded whole, so it need not end on a line boundary.
+  //
+  // A poll that fails transiently, even partway through its body, is repeated
+  // from the same offset after a backoff.  Offsets count bytes of Jenkins' raw
+  // log rather than of the text served, so a poll cannot be resumed partway;
+  // holding each poll back until its body is complete is what keeps a retry
+  // from repeating or dropping lines.
+  pub fn build_log_stream<'a>(
+    &'a self,
+    build_url: &'a str,
+    start_pos: u64,
+  ) -> impl Stream<Item = Result<Bytes, JenkinsError>> + 'a {
+    stream::try_unfold(Some(start_pos), move |start_pos| async move {
+      let Some(start_pos) = start_pos else {
+        return Ok(None);
+      };
+      let mut retry = 0;
+      let (text, offset, more) = loop {
+        match self.build_log_poll(build_url, start_pos).await {
+          Err(e)
+            if log_poll_transient(&e) && retry < self.retry.max_retries =>
+          {
+            let delay = self.retry.backoff(retry);
+            warn!(
+              start_pos,
+              ?delay,
+              error = %e,
+              "Build log stream interrupted; reconnecting."
+            );
+            tokio::time::sleep(delay).await;
+            retry += 1;
+          }
+          polled => break polled?,
+        }
+      };
+      Ok(Some((text, more.then_some(offset))))
+    })
+  }
+
+  // One progressiveText request: the text from `start_pos`, the offset to ask
+  // for next, and whether Jenkins expects more.
+  async fn build_log_poll(
+    &self,
+    build_url: &str,
+    start_pos: u64,
+  ) -> Result<(Bytes, u64, bool), JenkinsError> {
+    let response = self
+      .request(
+        reqwest::Method::GET,
+        format!("{}logText/progressiveText?start={}", build_url, start_pos),
+      )
+      .await
+      .map_err(JenkinsError::BuildStream)?;
+    debug!(
+      "Headers for stream: \n{}",
+      headers_to_string(response.headers().clone())?,
+    );
+    // Headers must be parsed before the response body is consumed.
+    let offset = header_parse::<_, u64>("x-text-size", "0", &response)?;
+    let more = header_parse::<_, bool>(
+      "x-more-data",
+      // It may stop appearing if the job is done.  Default to false.
+      "false",
+      &response,
+    )?;
+    debug!("Found offset of {}.", offset);
+    debug!("Need more? {}", more);
+    let text = response
+      .bytes()
+      .await
+      .map_err(JenkinsError::BuildResponseRead)?;
+    Ok((text, offset, more))
+  }
+
+  // Streams the build's log from byte `start_pos` on into `sink`, one whole
+  // line at a time, returning once Jenkins reports the log complete.  Lines
+  // are reassembled across chunks and polls, and a last line without a
+  // newline is delivered when the log ends.
+  pub async fn build_log_to<S: LogSink + ?Sized>(
+    &self,
+    build_url: &str,
+    start_pos: u64,
+    sink: &mut S,
+  ) -> Result<(), JenkinsError> {
+    let mut decoder = LineDecoder::new();
+    let log = self.build_log_stream(build_url, start_pos);
+    tokio::pin!(log);
+    while let Some(chunk) = log.try_next().await? {
+      decoder
+        .push(&chunk)
+        .iter()
+        .try_for_each(|line| sink.line_write(line))
+        .map_err(JenkinsError::BuildLogWrite)?;
+    }
+    decoder
+      .finish()
+      .map_or(Ok(()), |line| sink.line_write(&line))
+      .map_err(JenkinsError::BuildLogWrite)
+  }
+
+  pub async fn job_builds(
+    &self,
+    job: &JobPath,
+  ) -> Result<JenkinsJobBuilds, JenkinsError> {
+    let url = format!(
+      "{}{}/api/json?tree=builds[number,url,result,building]{{0,20}}",
+      self.host_url,
+      job.url_path(),
+    );
+    let response = self
+      .request(reqwest::Method::GET, url)
+      .await
+      .map_err(JenkinsError::JobBuildsRequest)?;
+    let text = response
+      .text()
+      .await
+      .map_err(|e| JenkinsError::JobBuildsRequest(e.into()))?;
+    serde_json::from_str(&text).map_err(JenkinsError::JobBuildsDeserialize)
+  }
+
+  pub async fn job_get(
+    &self,
+    job: &JobPath,
+  ) -> Result<JenkinsJob, JenkinsError> {
+    let url = format!(
+      "{}{}/api/json?tree=property[parameterDefinitions[name,type,description,choices,defaultParameterValue[value]]]",
+      self.host_url,
+      job.url_path(),
+    );
+    let response = self
+      .request(reqwest::Method::GET, url)
+      .await
+      .map_err(JenkinsError::JobRequest)?;
+    let text = response
+      .text()
+      .await
+      .map_err(|e| JenkinsError::JobRequest(e.into()))?;
+    serde_json::from_str(&text).map_err(JenkinsError::JobDeserialize)
+  }
+}
+
+// Whether a failed log poll is worth repeating.  The request itself has
+// already been retried by RetryMiddleware, but a long follow should outlast a
+// longer outage, and a body cut off partway is only caught here.
+fn log_poll_transient(error: &JenkinsError) -> bool {
+  match error {
+    JenkinsError::BuildStream(reqwest_middleware::Error::Reqwest(e)) => {
+      is_transient_error(e)
+    }
+    // Reading a whole body at once reports a connection cut partway through
+    // it as a decode error.
+    JenkinsError::BuildResponseRead(e) => {
+      is_transient_error(e) || e.is_decode()
+    }
+    _ => false,
+  }
+}
+
+// Jenkins silently ignores parameters a job does not define and falls back to
+// the defaults, which turns a typo into a build with the wrong settings.  Check
+// every supplied parameter against the job's definitions before enqueueing.
+pub fn params_validate(
+  job: &JobPath,
+  definitions: &[&JenkinsParameterDefinition],
+  params: &HashMap<String, String>,
+) -> Result<(), JenkinsError> {
+  params.iter().try_for_each(|(name, value)| {
+    let definition =
+      definitions
+        .iter()
+        .find(|d| &d.name == name)
+        .ok_or_else(|| JenkinsError::ParamUnknown {
+          job: job.to_string(),
+          name: name.clone(),
+          known: definitions
+            .iter()
+            .map(|d| d.name.as_str())
+            .collect::<Vec<&str>>()
+            .join(", "),
+        })?;
+    param_value_validate(
+      &definition.name,
+      &definition.kind,
+      definition.choices.as_deref(),
+      value,
+    )
+  })
+}
+
+// The same check for uploads, which only a file parameter accepts.  Jenkins
+// would otherwise drop a file sent under any other name without a word.
+pub fn file_params_validate(
+  job: &JobPath,
+  definitions: &[&JenkinsParameterDefinition],
+  files: &HashMap<String, PathBuf>,
+) -> Result<(), JenkinsError> {
+  files
+    .keys()
+    .find(|name| !definitions.iter().any(|d| &&d.name == name && d.is_file()))
+    .map_or(Ok(()), |name| {
+      Err(JenkinsError::FileParamUnknown {
+        job: job.to_string(),
+        name: name.clone(),
+        known: definitions
+          .iter()
+          .filter(|d| d.is_file())
+          .map(|d| d.name.as_str())
+          .collect::<Vec<&str>>()
+          .join(", "),
+      })
+    })
+}
+
+// Shared by job parameters and input step parameters, which are defined by
+// the same classes.
+fn param_value_validate(
+  name: &str,
+  kind: &str,
+  choices: Option<&[String]>,
+  value: &str,
+) -> Result<(), JenkinsError> {
+  match kind {
+    "BooleanParameterDefinition" if value != "true" && value != "false" => {
+      Err(JenkinsError::ParamBoolean {
+        name: name.to_string(),
+        value: value.to_string(),
+      })
+    }
+    "ChoiceParameterDefinition" => choices
+      .filter(|choices| !choices.iter().any(|c| c == value))
+      .map_or(Ok(()), |choices| {
+        Err(JenkinsError::ParamChoice {
+          name: name.to_string(),
+          value: value.to_string(),
+          choices: choices.join(", "),
+        })
+      }),
+    _ => Ok(()),
+  }
+}
+
+impl JenkinsClient {
+  // The URL of a build, with the trailing slash Jenkins itself reports, so it
+  // can be used anywhere a queue item or build list URL is.
+  pub fn build_url(&self, job: &JobPath, number: u64) -> String {
+    self.build_selector_url(job, &BuildSelector::Number(number))
+  }
+
+  pub fn build_selector_url(
+    &self,
+    job: &JobPath,
+    build: &BuildSelector,
+  ) -> String {
+    format!("{}{}/{}/", self.host_url, job.url_path(), build)
+  }
+
+  pub async fn build_detail_get(
+    &self,
+    job: &JobPath,
+    build_number: u64,
+  ) -> Result<JenkinsBuildDetail, JenkinsError> {
+    let url =
+      format!("{}{}/{}/api/json", self.host_url, job.url_path(), build_number,);
+    let response = self
+      .request(reqwest::Method::GET, url)
+      .await
+      .map_err(JenkinsError::BuildDetailRequest)?;
+    let text = response
+      .text()
+      .await
+      .map_err(|e| JenkinsError::BuildDetailRequest(e.into()))?;
+    serde_json::from_str(&text).map_err(JenkinsError::BuildDetailDeserialize)
+  }
+
+  pub async fn build_log_fetch(
+    &self,
+    job: &JobPath,
+    build_number: u64,
+  ) -> Result<String, JenkinsError> {
+    let url = format!(
+      "{}{}/{}/consoleText",
+      self.host_url,
+      job.url_path(),
+      build_number,
+    );
+    let response = self
+      .request(reqwest::Method::GET, url)
+      .await
+      .map_err(JenkinsError::BuildLogFetch)?;
+    response.text().await.map_err(JenkinsError::BuildLogRead)
+  }
+}
+
+// How often to re-check a build whose log has ended but whose result has not
+// yet been recorded.
+const RESULT_POLL_INTERVAL: std::time::Duration =
+  std::time::Duration::from_secs(1);
+
+impl JenkinsClient {
+  // The log can finish before Jenkins records the result: for a moment the
+  // build reports building=false with a null result.  Pipelines can also set a
+  // result (e.g. UNSTABLE) while still building.  Only a finished build with a
+  // result is final, so poll until both hold.
+  pub async fn build_result_wait(
+    &self,
+    build_url: &str,
+  ) -> Result<BuildStatus, JenkinsError> {
+    loop {
+      let build = self.build_result_get(build_url).await?;
+      match (build.building, build.result) {
+        (false, Some(result)) => {
+          return Ok(jenkins_result_to_status(Some(&result)));
+        }
+        (building, result) => {
+          debug!(building, ?result, "Build result not final yet; waiting.");
+          tokio::time::sleep(RESULT_POLL_INTERVAL).await;
+        }
+      }
+    }
+  }
+
+  pub async fn build_result_get(
+    &self,
+    build_url: &str,
+  ) -> Result<JenkinsBuildResult, JenkinsError> {
+    let response = self
+      .request(
+        reqwest::Method::GET,
+        format!("{}api/json?tree=building,result", build_url),
+      )
+      .await
+      .map_err(JenkinsError::BuildResultRequest)?;
+    let text = response
+      .text()
+      .await
+      .map_err(|e| JenkinsError::BuildResultRequest(e.into()))?;
+    serde_json::from_str(&text).map_err(JenkinsError::BuildResultDeserialize)
+  }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct JenkinsWorkflowRun {
+  pub start_time_millis: u64,
+  #[serde(default)]
+  pub stages: Vec<JenkinsWorkflowStage>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct JenkinsWorkflowStage {
+  pub id: String,
+  pub name: String,
+  // "SUCCESS" | "FAILED" | "UNSTABLE" | "ABORTED" | "NOT_EXECUTED" |
+  // "IN_PROGRESS" | "PAUSED_PENDING_INPUT".
+  pub status: String,
+  pub start_time_millis: u64,
+  pub duration_millis: u64,
+}
+
+impl JenkinsWorkflowStage {
+  pub fn is_active(&self) -> bool {
+    matches!(self.status.as_str(), "IN_PROGRESS" | "PAUSED_PENDING_INPUT")
+  }
+}
+
+impl JenkinsClient {
+  // Reads a Pipeline build's stages from the Pipeline Stage View plugin's
+  // wfapi.  Builds of other job types, and servers without the plugin, answer
+  // 404, which yields None.
+  pub async fn workflow_describe(
+    &self,
+    build_url: &str,
+  ) -> Result<Option<JenkinsWorkflowRun>, JenkinsError> {
+    let response = self
+      .request(reqwest::Method::GET, format!("{}wfapi/describe", build_url))
+      .await
+      .map_err(JenkinsError::WorkflowRequest)?;
+    if response.status() == reqwest::StatusCode::NOT_FOUND {
+      return Ok(None);
+    }
+    let text = response
+      .text()
+      .await
+      .map_err(|e| JenkinsError::WorkflowRequest(e.into()))?;
+    serde_json::from_str(&text)
+      .map(Some)
+      .map_err(JenkinsError::WorkflowDeserialize)
+  }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct JenkinsPendingInput {
+  pub id: String,
+  pub message: String,
+  // The label of the proceed button, "Proceed" unless the step sets `ok`.
+  pub proceed_text: Option<String>,
+  #[serde(default)]
+  pub inputs: Vec<JenkinsInputParameter>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct JenkinsInputParameter {
+  pub name: String,
+  // The definition's simple class name, as on job parameter definitions.
+  #[serde(rename = "type")]
+  pub kind: String,
+  pub description: Option<String>,
+  // The full parameter definition; only its default value and any choices are
+  // read.
+  pub definition: Option<serde_json::Value>,
+}
+
+impl JenkinsInputParameter {
+  pub fn kind_short(&self) -> String {
+    parameter_kind_short(&self.kind)
+  }
+
+  pub fn default_string(&self) -> Option<String> {
+    self
+      .definition
+      .as_ref()
+      .and_then(|d| d.pointer("/defaultParameterValue/value"))
+      .map(parameter_value_string)
+  }
+
+  // Refuses a value the parameter cannot take, as params_validate does for a
+  // job's parameters.
+  pub fn value_validate(&self, value: &str) -> Result<(), JenkinsError> {
+    let choices = self
+      .definition
+      .as_ref()
+      .and_then(|d| d.get("choices"))
+      .and_then(|choices| {
+        serde_json::from_value::<Vec<String>>(choices.clone()).ok()
+      });
+    param_value_validate(&self.name, &self.kind, choices.as_deref(), value)
+  }
+}
+
+pub enum InputAnswer {
+  // The value for each of the input's parameters.
+  Proceed(HashMap<String, String>),
+  Abort,
+}
+
+impl JenkinsClient {
+  // Lists the input steps a Pipeline build is waiting on.  Like
+  // workflow_describe, this is served by the Pipeline Stage View plugin, and a
+  // 404 yields None.
+  pub async fn inputs_pending(
+    &self,
+    build_url: &str,
+  ) -> Result<Option<Vec<JenkinsPendingInput>>, JenkinsError> {
+    let response = self
+      .request(
+        reqwest::Method::GET,
+        format!("{}wfapi/pendingInputActions", build_url),
+      )
+      .await
+      .map_err(JenkinsError::InputRequest)?;
+    if response.status() == reqwest::StatusCode::NOT_FOUND {
+      return Ok(None);
+    }
+    let text = response
+      .text()
+      .await
+      .map_err(|e| JenkinsError::InputRequest(e.into()))?;
+    serde_json::from_str(&text)
+      .map(Some)
+      .map_err(JenkinsError::InputDeserialize)
+  }
+
+  // Answers an input step.  An input with parameters is submitted as the same
+  // form the approval page posts: a "json" field holding the values, and the
+  // proceed button's label.
+  pub async fn input_submit(
+    &self,
+    build_url: &str,
+    input: &JenkinsPendingInput,
+    answer: &InputAnswer,
+  ) -> Result<(), JenkinsError> {
+    let input_url = format!("{}input/{}/", build_url, input.id);
+    let request = match answer {
+      InputAnswer::Abort => self
+        .request_builder(reqwest::Method::POST, format!("{}abort", input_url)),
+      InputAnswer::Proceed(_) if input.inputs.is_empty() => self
+        .request_builder(
+          reqwest::Method::POST,
+          format!("{}proceedEmpty", input_url),
+        ),
+      InputAnswer::Proceed(values) => {
+        let parameter = input
+          .inputs
+          .iter()
+          .map(|p| {
+            serde_json::json!({
+              "name": p.name,
+              "value": values.get(&p.name).cloned().unwrap_or_default(),
+            })
+          })
+          .collect::<Vec<serde_json::Value>>();
+        self
+          .request_builder(
+            reqwest::Method::POST,
+            format!("{}submit", input_url),
+          )
+          .form(&[
+            ("json", serde_json::json!({ "parameter": parameter }).to_string()),
+            (
+              "proceed",
+              input
+                .proceed_text
+                .clone()
+                .unwrap_or_else(|| "Proceed".to_string()),
+            ),
+          ])
+      }
+    };
+    let response = request.send().await.map_err(JenkinsError::InputSubmit)?;
+    if response.status().is_success() {
+      Ok(())
+    } else {
+      Err(JenkinsError::InputSubmitStatus(input.id.clone(), response.status()))
+    }
+  }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct JenkinsDownstreamProjects {
+  #[serde(default)]
+  downstream_projects: Vec<JenkinsDownstreamProject>,
+}
+
+#[derive(Debug, Deserialize)]
+struct JenkinsDownstreamProject {
+  url: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct JenkinsCausedBuilds {
+  #[serde(default)]
+  builds: Vec<JenkinsCausedBuild>,
+}
+
+#[derive(Debug, Deserialize)]
+struct JenkinsCausedBuild {
+  number: u64,
+  #[serde(default)]
+  actions: Vec<JenkinsBuildDetailAction>,
+}
+
+impl JenkinsClient {
+  // The builds a build has triggered, as (job, build number) pairs.  Pipelines
+  // record the builds their `build` steps start on the build itself.  Jobs that
+  // trigger others through "Build other projects" do not, so the projects they
+  // list as downstream are searched for builds whose UpstreamCause names this
+  // one.
+  pub async fn build_downstream(
+    &self,
+    job: &JobPath,
+    build_number: u64,
+  ) -> Result<Vec<(JobPath, u64)>, JenkinsError> {
+    let recorded: JenkinsBuildDetailActions = self
+      .downstream_get(format!(
+        "{}api/json?tree=actions[downstreamBuilds[jobFullName,buildNumber]]",
+        self.build_url(job, build_number),
+      ))
+      .await?;
+    let mut downstream = recorded
+      .actions
+      .iter()
+      .filter_map(|a| a.downstream_builds.as_ref())
+      .flatten()
+      .filter_map(|b| {
+        b.build_number.map(|number| {
+          b.job_full_name
+            .parse::<JobPath>()
+            .map(|job| (job, number))
+            .map_err(JenkinsError::DownstreamJob)
+        })
+      })
+      .collect::<Result<Vec<(JobPath, u64)>, JenkinsError>>()?;
+    let projects: JenkinsDownstreamProjects = self
+      .downstream_get(format!(
+        "{}{}/api/json?tree=downstreamProjects[url]",
+        self.host_url,
+        job.url_path(),
+      ))
+      .await?;
+    for project in projects.downstream_projects {
+      let project_job =
+        JobPath::from_url(&project.url).map_err(JenkinsError::DownstreamJob)?;
+      let builds: JenkinsCausedBuilds = self.downstream_get(format!(
+          "{}{}/api/json?tree=builds[number,actions[causes[upstreamProject,upstreamBuild]]]{{0,20}}",
+          self.host_url,
+          project_job.url_path(),
+        ),
+      )
+      .await?;
+      // The cause names the upstream job by its full name, which is compared
+      // as a path, segment by segment, rather than as text.
+      downstream.extend(
+        builds
+          .builds
+          .iter()
+          .filter(|b| {
+            b.actions
+              .iter()
+              .filter_map(|a| a.causes.as_ref())
+              .flatten()
+              .any(|c| {
+                c.upstream_build == Some(build_number)
+                  && c
+                    .upstream_project
+                    .as_deref()
+                    .and_then(|project| project.parse::<JobPath>().ok())
+                    .as_ref()
+                    == Some(job)
+              })
+          })
+          .map(|b| (project_job.clone(), b.number)),
+      );
+    }
+    // Both sources can report the same build.
+    let mut seen = std::collections::HashSet::new();
+    downstream.retain(|build| seen.insert(build.clone()));
+    Ok(downstream)
+  }
+}
+
+#[derive(Debug, Deserialize)]
+struct JenkinsBuildDetailActions {
+  #[serde(default)]
+  actions: Vec<JenkinsBuildDetailAction>,
+}
+
+impl JenkinsClient {
+  async fn downstream_get<T: serde::de::DeserializeOwned>(
+    &self,
+    url: String,
+  ) -> Result<T, JenkinsError> {
+    let response = self
+      .request(reqwest::Method::GET, url)
+      .await
+      .map_err(JenkinsError::DownstreamRequest)?;
+    let text = response
+      .text()
+      .await
+      .map_err(|e| JenkinsError::DownstreamRequest(e.into()))?;
+    serde_json::from_str(&text).map_err(JenkinsError::DownstreamDeserialize)
+  }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct JenkinsBuildArtifacts {
+  pub number: u64,
+  // Always the numbered build URL, even when the build was selected through a
+  // permalink such as lastSuccessfulBuild.
+  pub url: String,
+  #[serde(default)]
+  pub artifacts: Vec<JenkinsArtifact>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct JenkinsArtifact {
+  // Relative to the build's artifact root, separated by "/".
+  pub relative_path: String,
+}
+
+impl JenkinsClient {
+  pub async fn build_artifacts_get(
+    &self,
+    job: &JobPath,
+    build: &BuildSelector,
+  ) -> Result<JenkinsBuildArtifacts, JenkinsError> {
+    let response = self
+      .request(
+        reqwest::Method::GET,
+        format!(
+          "{}api/json?tree=number,url,artifacts[relativePath]",
+          self.build_selector_url(job, build),
+        ),
+      )
+      .await
+      .map_err(JenkinsError::ArtifactsRequest)?;
+    let text = response
+      .text()
+      .await
+      .map_err(|e| JenkinsError::ArtifactsRequest(e.into()))?;
+    serde_json::from_str(&text).map_err(JenkinsError::ArtifactsDeserialize)
+  }
+}
+
+// Each path segment is percent-encoded, since artifact names may hold spaces
+// and other characters a URL path cannot carry as they are.
+pub fn artifact_url(
+  build_url: &str,
+  relative_path: &str,
+) -> Result<url::Url, JenkinsError> {
+  let mut url = url::Url::parse(build_url)
+    .map_err(|e| JenkinsError::ArtifactUrl(build_url.to_string(), e))?;
+  url
+    .path_segments_mut()
+    .map_err(|()| {
+      JenkinsError::ArtifactUrl(
+        build_url.to_string(),
+        url::ParseError::RelativeUrlWithCannotBeABaseBase,
+      )
+    })?
+    .pop_if_empty()
+    .push("artifact")
+    .extend(relative_path.split('/'));
+  Ok(url)
+}
+
+impl JenkinsClient {
+  // The artifact's size and validator, from a HEAD request.
+  pub async fn artifact_head(
+    &self,
+    url: &url::Url,
+  ) -> Result<ArtifactHead, JenkinsError> {
+    let response =
+      self
+        .request(reqwest::Method::HEAD, url.to_string())
+        .await
+        .map_err(|e| JenkinsError::ArtifactRequest(url.to_string(), e))?;
+    artifact_status_check(url, &response)?;
+    let size = response
+      .headers()
+      .get(reqwest::header::CONTENT_LENGTH)
+      .map(|v| {
+        v.to_str().map_err(JenkinsError::Header).and_then(|v| {
+          v.parse::<u64>()
+            .map_err(|e| JenkinsError::ArtifactSize(url.to_string(), e))
+        })
+      })
+      .transpose()?;
+    Ok(ArtifactHead {
+      size,
+      validator: artifact_validator(&response),
+    })
+  }
+
+  // Requests the artifact's bytes from `start` on.  With a validator the range
+  // is sent with If-Range, so a file that has changed since comes back whole.
+  // The server may also ignore the range and send the whole file anyway, which
+  // the returned body says.
+  pub async fn artifact_get(
+    &self,
+    url: &url::Url,
+    start: u64,
+    validator: Option<&str>,
+  ) -> Result<ArtifactBody, JenkinsError> {
+    let mut request =
+      self.request_builder(reqwest::Method::GET, url.to_string());
+    if start > 0 {
+      request =
+        request.header(reqwest::header::RANGE, format!("bytes={}-", start));
+      if let Some(validator) = validator {
+        request = request.header(reqwest::header::IF_RANGE, validator);
+      }
+    }
+    let response = request
+      .send()
+      .await
+      .map_err(|e| JenkinsError::ArtifactRequest(url.to_string(), e))?;
+    artifact_status_check(url, &response)?;
+    Ok(ArtifactBody {
+      partial: response.status() == reqwest::StatusCode::PARTIAL_CONTENT,
+      validator: artifact_validator(&response),
+      url: url.to_string(),
+      response,
+    })
+  }
+}
+
+pub struct ArtifactHead {
+  // None when the server does not say.
+  pub size: Option<u64>,
+  // See artifact_validator.
+  pub validator: Option<String>,
+}
+
+pub struct ArtifactBody {
+  // Whether the server honoured the range, answering with a 206 rather than
+  // the whole file.
+  pub partial: bool,
+  // See artifact_validator.
+  pub validator: Option<String>,
+  url: String,
+  response: reqwest::Response,
+}
+
+impl ArtifactBody {
+  pub fn bytes_stream(self) -> impl Stream<Item = Result<Bytes, JenkinsError>> {
+    let url = self.url;
+    self
+      .response
+      .bytes_stream()
+      .map_err(move |e| JenkinsError::ArtifactRead(url.clone(), e))
+  }
+}
+
+// What identifies this version of the artifact, for If-Range: a strong ETag
+// if the server sends one, or else its Last-Modified date, which is what
+// Jenkins sends for archived files.  A weak ETag cannot be used in If-Range.
+fn artifact_validator(response: &reqwest::Response) -> Option<String> {
+  let headers = response.headers();
+  headers
+    .get(reqwest::header::ETAG)
+    .filter(|etag| !etag.as_bytes().starts_with(b"W/"))
+    .or_else(|| headers.get(reqwest::header::LAST_MODIFIED))
+    .and_then(|validator| validator.to_str().ok())
+    .map(str::to_string)
+}
+
+fn artifact_status_check(
+  url: &url::Url,
+  response: &reqwest::Response,
+) -> Result<(), JenkinsError> {
+  match response.status() {
+    status if status.is_success() => Ok(()),
+    reqwest::StatusCode::NOT_FOUND => {
+      Err(JenkinsError::ArtifactNotFound(url.to_string()))
+    }
+    status => Err(JenkinsError::ArtifactStatus(url.to_string(), status)),
+  }
+}
+
+// A build's test report.  Aggregated reports, from matrix builds and from jobs
+// that aggregate their downstream builds' results, are flattened into the same
+// shape, with every child's suites in one list.
+#[derive(Debug, Deserialize)]
+#[serde(from = "JenkinsTestReportRaw")]
+pub struct JenkinsTestReport {
+  pub fail_count: u64,
+  pub pass_count: u64,
+  pub skip_count: u64,
+  // Seconds.
+  pub duration: f64,
+  pub suites: Vec<JenkinsTestSuite>,
+}
+
+// testReport/api/json as either kind of report.  An aggregated one
+// (hudson.tasks.test.AggregatedTestResultAction) gives totalCount rather than
+// passCount and no duration, and keeps each child build's report under
+// childReports.
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct JenkinsTestReportRaw {
+  fail_count: u64,
+  pass_count: Option<u64>,
+  skip_count: u64,
+  total_count: Option<u64>,
+  #[serde(default)]
+  duration: f64,
+  #[serde(default)]
+  suites: Vec<JenkinsTestSuite>,
+  #[serde(default)]
+  child_reports: Vec<JenkinsTestChildReport>,
+}
+
+#[derive(Debug, Deserialize)]
+struct JenkinsTestChildReport {
+  result: JenkinsTestReportRaw,
+}
+
+impl From<JenkinsTestReportRaw> for JenkinsTestReport {
+  fn from(raw: JenkinsTestReportRaw) -> Self {
+    let children = raw
+      .child_reports
+      .into_iter()
+      .map(|child| JenkinsTestReport::from(child.result))
+      .collect::<Vec<JenkinsTestReport>>();
+    let pass_count = raw.pass_count.unwrap_or_else(|| {
+      raw
+        .total_count
+        .unwrap_or_default()
+        .saturating_sub(raw.fail_count + raw.skip_count)
+    });
+    let duration =
+      raw.duration + children.iter().map(|c| c.duration).sum::<f64>();
+    JenkinsTestReport {
+      fail_count: raw.fail_count,
+      pass_count,
+      skip_count: raw.skip_count,
+      duration,
+      suites: raw
+        .suites
+        .into_iter()
+        .chain(children.into_iter().flat_map(|c| c.suites))
+        .collect(),
+    }
+  }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct JenkinsTestSuite {
+  pub name: String,
+  pub duration: f64,
+  #[serde(default)]
+  pub cases: Vec<JenkinsTestCase>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct JenkinsTestCase {
+  pub class_name: String,
+  pub name: String,
+  // "PASSED" | "FIXED" | "FAILED" | "REGRESSION" | "SKIPPED".  FIXED and
+  // REGRESSION compare against the previous build.
+  pub status: String,
+  pub duration: f64,
+  pub error_details: Option<String>,
+  pub error_stack_trace: Option<String>,
+  pub skipped_message: Option<String>,
+}
+
+impl JenkinsTestCase {
+  pub fn is_failed(&self) -> bool {
+    matches!(self.status.as_str(), "FAILED" | "REGRESSION")
+  }
+
+  pub fn is_skipped(&self) -> bool {
+    self.status == "SKIPPED"
+  }
+}
+
+impl JenkinsClient {
+  // The build's test report, or None if it has none: builds that never
+  // recorded test results answer 404.
+  pub async fn build_test_report_get(
+    &self,
+    job: &JobPath,
+    build: &BuildSelector,
+  ) -> Result<Option<JenkinsTestReport>, JenkinsError> {
+    let response = self
+      .request(
+        reqwest::Method::GET,
+        format!("{}testReport/api/json", self.build_selector_url(job, build)),
+      )
+      .await
+      .map_err(JenkinsError::TestReportRequest)?;
+    if response.status() == reqwest::StatusCode::NOT_FOUND {
+      return Ok(None);
+    }
+    let text = response
+      .text()
+      .await
+      .map_err(|e| JenkinsError::TestReportRequest(e.into()))?;
+    serde_json::from_str(&text)
+      .map(Some)
+      .map_err(JenkinsError::TestReportDeserialize)
+  }
+}
+
+// The queue item URL from the enqueue Location header ends in the item's id,
+// e.g. "<host>/queue/item/123/".
+pub fn queue_item_id(url: &str) -> Result<u32, JenkinsError> {
+  url
+    .trim_end_matches('/')
+    .rsplit('/')
+    .next()
+    .unwrap_or_default()
+    .parse::<u32>()
+    .map_err(|e| JenkinsError::QueueItemId(url.to_string(), e))
+}
+
+impl JenkinsClient {
+  pub async fn queue_item_cancel(&self, id: u32) -> Result<(), JenkinsError> {
+    let response = self
+      .request(
+        reqwest::Method::POST,
+        format!("{}/queue/cancelItem?id={}", self.host_url, id),
+      )
+      .await
+      .map_err(JenkinsError::QueueCancel)?;
+    // Depending on the version, Jenkins answers with a redirect to the queue
+    // (which is followed) or 204.  Anything else means it was refused.
+    if response.status().is_success() {
+      Ok(())
+    } else {
+      Err(JenkinsError::QueueCancelStatus(id, response.status()))
+    }
+  }
+}
+
+/// The escalating requests Jenkins offers for ending a build.  `stop` asks the
+/// build to abort cleanly; `term` and `kill` forcibly end a Pipeline that
+/// ignores it.
+#[derive(Clone, Copy, Debug)]
+pub enum BuildStopRequest {
+  Stop,
+  Term,
+  Kill,
+}
+
+impl BuildStopRequest {
+  fn path(&self) -> &'static str {
+    match self {
+      BuildStopRequest::Stop => "stop",
+      BuildStopRequest::Term => "term",
+      BuildStopRequest::Kill => "kill",
+    }
+  }
+}
+
+// Time between checks on whether a stopping build has finished.
+const STOP_POLL_INTERVAL: std::time::Duration =
+  std::time::Duration::from_secs(1);
+
+impl JenkinsClient {
+  // Sends stop, then term, then kill, giving the build `timeout` to finish
+  // after each before escalating.  Returns the build's final status.
+  // `on_request` is told about each request as it is sent, so callers can
+  // report progress.
+  pub async fn build_stop<F, E>(
+    &self,
+    build_url: &str,
+    timeout: std::time::Duration,
+    mut on_request: F,
+  ) -> Result<BuildStatus, E>
+  where
+    F: FnMut(BuildStopRequest) -> Result<(), E>,
+    E: From<JenkinsError>,
+  {
+    for request in [
+      BuildStopRequest::Stop,
+      BuildStopRequest::Term,
+      BuildStopRequest::Kill,
+    ] {
+      on_request(request)?;
+      self.build_stop_request(build_url, request).await?;
+      if self.build_finished_wait(build_url, timeout).await? {
+        return Ok(self.build_result_wait(build_url).await?);
+      }
+    }
+    Err(JenkinsError::BuildStopTimeout(build_url.to_string()).into())
+  }
+
+  async fn build_stop_request(
+    &self,
+    build_url: &str,
+    request: BuildStopRequest,
+  ) -> Result<(), JenkinsError> {
+    let response = self
+      .request(
+        reqwest::Method::POST,
+        format!("{}{}", build_url, request.path()),
+      )
+      .await
+      .map_err(JenkinsError::BuildStop)?;
+    // term and kill only exist on Pipeline builds; a 404 for those just means
+    // there is nothing further to escalate to, which the caller's timeout
+    // reports.
+    match response.status() {
+      status if status.is_success() => Ok(()),
+      reqwest::StatusCode::NOT_FOUND
+        if !matches!(request, BuildStopRequest::Stop) =>
+      {
+        Ok(())
+      }
+      status => Err(JenkinsError::BuildStopStatus(
+        request.path(),
+        build_url.to_string(),
+        status,
+      )),
+    }
+  }
+
+  // Returns whether the build stopped building within the timeout.
+  async fn build_finished_wait(
+    &self,
+    build_url: &str,
+    timeout: std::time::Duration,
+  ) -> Result<bool, JenkinsError> {
+    let deadline = tokio::time::Instant::now() + timeout;
+    loop {
+      if !self.build_result_get(build_url).await?.building {
+        return Ok(true);
+      }
+      if tokio::time::Instant::now() >= deadline {
+        return Ok(false);
+      }
+      tokio::time::sleep(STOP_POLL_INTERVAL).await;
+    }
+  }
+}
+
+pub fn jenkins_result_to_status(result: Option<&str>) -> BuildStatus {
+  match result {
+    Some("SUCCESS") => BuildStatus::Success,
+    Some("FAILURE") => BuildStatus::Failure,
+    Some("ABORTED") => BuildStatus::Aborted,
+    Some("UNSTABLE") => BuildStatus::Unstable,
+    None => BuildStatus::Running,
+    Some(other) => BuildStatus::Unknown(other.to_string()),
+  }
+}
+
+fn header_parse<K, V>(
+  header_name: K,
+  default: &str,
+  response: &reqwest::Response,
+) -> Result<V, JenkinsError>
+where
+  K: reqwest::header::AsHeaderName,
+  V: core::str::FromStr,
+{
+  response
+    .headers()
+    .get(header_name)
+    .map_or(default, |s| s.to_str().unwrap_or(default))
+    .parse::<V>()
+    .map_err(|_| JenkinsError::BuildParseTextSize)
+}
+
+impl JenkinsClient {
+  async fn request(
+    &self,
+    method: reqwest::Method,
+    url: String,
+  ) -> Result<reqwest::Response, reqwest_middleware::Error> {
+    self.request_builder(method, url).send().await
+  }
+
+  // For requests that carry a body or headers of their own.  Every request
+  // goes out through the middleware chain, authenticated as the client's user.
+  fn request_builder(
+    &self,
+    method: reqwest::Method,
+    url: String,
+  ) -> reqwest_middleware::RequestBuilder {
+    self
+      .client
+      .request(method, url)
+      .basic_auth(&self.username, Some(&self.token))
+  }
+}
+
+fn headers_to_string(
+  headers: reqwest::header::HeaderMap,
+) -> Result<String, JenkinsError> {
+  headers
+    .into_iter()
+    .map(|(k, v)| {
+      Ok(format!(
+        "{}: {}",
+        k.as_ref().map_or_else(|| "UnknownHeader", |h| h.as_str()),
+        v.to_str()?,
+      ))
+    })
+    .collect::<Result<Vec<String>, reqwest::header::ToStrError>>()
+    .map_err(JenkinsError::Header)
+    .map(|xs| xs.join("\n"))
+}
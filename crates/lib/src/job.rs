@@ -0,0 +1,141 @@
+use std::fmt;
+use std::str::FromStr;
+use thiserror::Error;
+
+/// A job's location in the Jenkins item tree.
+///
+/// Jobs inside folders and multibranch projects are addressed as a
+/// slash-separated path (`platform/deploy`, `service/PR-42`), which Jenkins
+/// exposes as nested `/job/<name>` URL segments.  Each segment is kept exactly
+/// as Jenkins names the item, so a multibranch branch `feature/x` is written
+/// the way Jenkins displays it: `service/feature%2Fx`.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct JobPath {
+  segments: Vec<String>,
+}
+
+#[derive(Debug, Error)]
+pub enum JobPathError {
+  #[error("Job path is empty")]
+  Empty,
+  #[error("Job path '{0}' contains an empty segment")]
+  EmptySegment(String),
+  #[error("URL '{0}' does not name a job")]
+  NotJobUrl(String),
+}
+
+impl JobPath {
+  pub fn segments(&self) -> &[String] {
+    &self.segments
+  }
+
+  /// The job's name without its folders, e.g. `deploy` for `platform/deploy`.
+  pub fn name(&self) -> &str {
+    self.segments.last().map_or("", String::as_str)
+  }
+
+  /// Recovers the job path from a job or build URL that Jenkins reported,
+  /// such as a queue item's task URL `<base>/job/a/job/b/`.  Any path prefix
+  /// the server is mounted under is skipped, as is anything after the last
+  /// job, such as a build number.
+  pub fn from_url(url: &str) -> Result<JobPath, JobPathError> {
+    let segments = url
+      .find("/job/")
+      .map(|start| url[start..].split('/').filter(|s| !s.is_empty()))
+      .into_iter()
+      .flatten()
+      .collect::<Vec<&str>>()
+      .chunks(2)
+      .take_while(|pair| pair.len() == 2 && pair[0] == "job")
+      .map(|pair| percent_decode(pair[1]))
+      .collect::<Vec<String>>();
+    if segments.is_empty() {
+      Err(JobPathError::NotJobUrl(url.to_string()))
+    } else {
+      Ok(JobPath { segments })
+    }
+  }
+
+  /// The URL path below the server root, e.g. `/job/a/job/b` for `a/b`.
+  /// Segments are percent-encoded so branch names such as `feature%2Fx`
+  /// survive the trip as `feature%252Fx`, which is how Jenkins serves them.
+  pub fn url_path(&self) -> String {
+    self
+      .segments
+      .iter()
+      .map(|s| format!("/job/{}", percent_encode(s)))
+      .collect()
+  }
+}
+
+impl FromStr for JobPath {
+  type Err = JobPathError;
+
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    // A leading or trailing slash is a common copy/paste artifact and carries
+    // no meaning, so it is tolerated.  Interior empty segments are not.
+    let trimmed = s.trim_matches('/');
+    if trimmed.is_empty() {
+      return Err(JobPathError::Empty);
+    }
+    trimmed
+      .split('/')
+      .map(|segment| {
+        if segment.is_empty() {
+          Err(JobPathError::EmptySegment(s.to_string()))
+        } else {
+          Ok(segment.to_string())
+        }
+      })
+      .collect::<Result<Vec<String>, JobPathError>>()
+      .map(|segments| JobPath { segments })
+  }
+}
+
+impl fmt::Display for JobPath {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    write!(f, "{}", self.segments.join("/"))
+  }
+}
+
+// Inverse of percent_encode.  A malformed escape is kept literally rather than
+// rejected, since the input comes from Jenkins and is shown, not trusted.
+pub(crate) fn percent_decode(segment: &str) -> String {
+  let bytes = segment.as_bytes();
+  let mut decoded = Vec::with_capacity(bytes.len());
+  let mut i = 0;
+  while i < bytes.len() {
+    let (byte, width) =
+      percent_escape_at(bytes, i).map_or((bytes[i], 1), |byte| (byte, 3));
+    decoded.push(byte);
+    i += width;
+  }
+  String::from_utf8_lossy(&decoded).into_owned()
+}
+
+fn percent_escape_at(bytes: &[u8], i: usize) -> Option<u8> {
+  match bytes.get(i..i + 3)? {
+    [b'%', high, low] => {
+      let high = char::from(*high).to_digit(16)?;
+      let low = char::from(*low).to_digit(16)?;
+      // Two hex digits always fit in a byte.
+      Some((high * 16 + low) as u8)
+    }
+    _ => None,
+  }
+}
+
+// RFC 3986 path-segment encoding: everything outside the unreserved set is
+// escaped.  Escaping more than strictly necessary is harmless to Jenkins and
+// keeps the rule simple.
+fn percent_encode(segment: &str) -> String {
+  segment
+    .bytes()
+    .map(|b| match b {
+      b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => {
+        (b as char).to_string()
+      }
+      _ => format!("%{:02X}", b),
+    })
+    .collect()
+}
@@ -1,5 +1,25 @@
 pub mod build;
+mod crumb;
+pub mod error;
+pub mod glob;
+pub mod jenkins;
+pub mod job;
+pub mod lines;
 pub mod logging;
+pub mod retry;
+mod session;
+pub mod sink;
+#[cfg(feature = "testing")]
+pub mod testing;
 
-pub use build::{BuildExitCode, BuildRef, BuildStatus};
+pub use build::{
+  BuildExitCode, BuildRef, BuildSelector, BuildSelectorError, BuildStatus,
+};
+pub use error::JenkinsError;
+pub use glob::glob_match;
+pub use jenkins::JenkinsClient;
+pub use job::{JobPath, JobPathError};
+pub use lines::LineDecoder;
 pub use logging::{LogFormat, LogLevel};
+pub use retry::RetryPolicy;
+pub use sink::{ChannelSink, FileSink, LogSink, StdoutSink, TeeSink};
@@ -0,0 +1,45 @@
+// Reassembles lines from a log that arrives in arbitrary chunks.  Jenkins
+// serves progressiveText in pieces that end wherever the network or the poll
+// happened to stop, which may be mid-line or even mid-character, so bytes are
+// held back until their line is complete.  A newline byte never occurs inside
+// a multi-byte UTF-8 sequence, so decoding whole lines never splits one.
+
+/// Buffers log bytes and yields each line once its newline has arrived.
+#[derive(Debug, Default)]
+pub struct LineDecoder {
+  pending: Vec<u8>,
+}
+
+impl LineDecoder {
+  pub fn new() -> Self {
+    LineDecoder::default()
+  }
+
+  /// Appends a chunk and returns every line it completes, in order, without
+  /// their terminators.  Empty lines are kept.
+  pub fn push(&mut self, chunk: &[u8]) -> Vec<String> {
+    self.pending.extend_from_slice(chunk);
+    let Some(end) = self.pending.iter().rposition(|b| *b == b'\n') else {
+      return Vec::new();
+    };
+    let rest = self.pending.split_off(end + 1);
+    let complete = std::mem::replace(&mut self.pending, rest);
+    complete[..end]
+      .split(|b| *b == b'\n')
+      .map(line_decode)
+      .collect()
+  }
+
+  /// The last line, if the log ended without a newline.
+  pub fn finish(self) -> Option<String> {
+    (!self.pending.is_empty()).then(|| line_decode(&self.pending))
+  }
+}
+
+// Invalid UTF-8 still becomes U+FFFD, but only where the log itself is
+// invalid.  A carriage return before the newline is dropped so it cannot
+// return the cursor over the prefix of the line.
+fn line_decode(bytes: &[u8]) -> String {
+  let bytes = bytes.strip_suffix(b"\r").unwrap_or(bytes);
+  String::from_utf8_lossy(bytes).into_owned()
+}
@@ -0,0 +1,132 @@
+// Retries for requests that fail for reasons unrelated to the request itself:
+// a connection reset, a timeout, or a proxy in front of Jenkins answering 502
+// while the controller restarts.  Only idempotent requests are retried, since
+// repeating a POST could enqueue a second build or stop a build twice.  Each
+// retry waits twice as long as the one before, up to a ceiling.
+//
+// This lives in middleware so every read gets it for free, the same way
+// crumb.rs handles CSRF for every write.
+use std::time::Duration;
+
+use async_trait::async_trait;
+use http::Extensions;
+use reqwest::{Method, Request, Response, StatusCode};
+use reqwest_middleware::{Middleware, Next};
+use tracing::*;
+
+/// How many times to retry a request that failed transiently, and how long to
+/// wait between attempts.
+#[derive(Clone, Copy, Debug)]
+pub struct RetryPolicy {
+  /// Retries after the first attempt.  Zero disables retrying.
+  pub max_retries: u32,
+  /// The wait before the first retry, doubled for each retry after it.
+  pub initial_backoff: Duration,
+  /// The longest wait between attempts.
+  pub max_backoff: Duration,
+}
+
+impl Default for RetryPolicy {
+  fn default() -> Self {
+    RetryPolicy {
+      max_retries: 3,
+      initial_backoff: Duration::from_millis(500),
+      max_backoff: Duration::from_secs(30),
+    }
+  }
+}
+
+impl RetryPolicy {
+  /// The wait before retry number `retry`, counting from zero.
+  pub fn backoff(&self, retry: u32) -> Duration {
+    self
+      .initial_backoff
+      .saturating_mul(2u32.saturating_pow(retry))
+      .min(self.max_backoff)
+  }
+}
+
+pub(crate) struct RetryMiddleware {
+  policy: RetryPolicy,
+}
+
+impl RetryMiddleware {
+  pub(crate) fn new(policy: RetryPolicy) -> Self {
+    RetryMiddleware { policy }
+  }
+}
+
+#[async_trait]
+impl Middleware for RetryMiddleware {
+  async fn handle(
+    &self,
+    request: Request,
+    extensions: &mut Extensions,
+    next: Next<'_>,
+  ) -> reqwest_middleware::Result<Response> {
+    if !is_idempotent(request.method()) {
+      return next.run(request, extensions).await;
+    }
+    let mut retry = 0;
+    loop {
+      // A request whose body cannot be cloned cannot be sent twice.
+      let Some(attempt) = request.try_clone() else {
+        return next.run(request, extensions).await;
+      };
+      let result = next.clone().run(attempt, extensions).await;
+      let transient = match &result {
+        Ok(response) => is_transient_status(response.status()),
+        Err(reqwest_middleware::Error::Reqwest(e)) => is_transient_error(e),
+        Err(reqwest_middleware::Error::Middleware(_)) => false,
+      };
+      if !transient || retry >= self.policy.max_retries {
+        return result;
+      }
+      let delay = self.policy.backoff(retry);
+      match &result {
+        Ok(response) => warn!(
+          url = %request.url(),
+          status = %response.status(),
+          "Request failed; retrying in {:?}.",
+          delay,
+        ),
+        Err(e) => warn!(
+          url = %request.url(),
+          error = %e,
+          "Request failed; retrying in {:?}.",
+          delay,
+        ),
+      }
+      tokio::time::sleep(delay).await;
+      retry += 1;
+    }
+  }
+}
+
+fn is_idempotent(method: &Method) -> bool {
+  matches!(
+    *method,
+    Method::GET | Method::HEAD | Method::OPTIONS | Method::PUT | Method::DELETE
+  )
+}
+
+// What a proxy or a restarting controller answers with.  429 asks for the
+// request to be repeated later.
+fn is_transient_status(status: StatusCode) -> bool {
+  matches!(
+    status,
+    StatusCode::TOO_MANY_REQUESTS
+      | StatusCode::BAD_GATEWAY
+      | StatusCode::SERVICE_UNAVAILABLE
+      | StatusCode::GATEWAY_TIMEOUT
+  )
+}
+
+// Failures to connect or to get a response at all.  Errors building the
+// request, redirect loops and the like would fail the same way again.
+pub(crate) fn is_transient_error(error: &reqwest::Error) -> bool {
+  error.is_connect()
+    || error.is_timeout()
+    || error.is_request()
+    || error.is_body()
+}
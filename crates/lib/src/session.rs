@@ -0,0 +1,280 @@
+// Recording and replaying the HTTP traffic between jj and Jenkins.  A
+// recording captures what a server actually said, queue messages and payloads
+// included, so a session that misbehaved against production can be attached
+// to a bug report and played back later with no server at all.
+//
+// A recording is a file of newline-delimited JSON, one exchange per line in
+// the order the requests were sent.  Credentials are redacted before anything
+// is written: the Authorization header, session cookies in both directions,
+// and the CSRF crumb, both as issued by crumbIssuer and as sent back in its
+// request header.  Build parameters, logs and artifacts are kept as they were.
+//
+// Both directions live in the innermost middleware, beneath crumbs and
+// retries, so a recording holds every request that really went out (crumb
+// fetches and retried attempts included) and a replay answers those same
+// requests.  Replay matches a request to the recording by method, path and
+// query, ignoring the host and the order of the query's parameters, so a
+// recording made against one server replays with any server configured.
+// Repeated requests, such as polls, are answered in recorded order, and once
+// a request's recorded answers run out the last one is repeated, since
+// polling on replay can run more often than it did live.
+use std::collections::{HashMap, VecDeque};
+use std::fs::File;
+use std::io::{BufRead, BufReader, LineWriter, Write};
+use std::path::Path;
+use std::sync::{Mutex, MutexGuard, PoisonError};
+
+use async_trait::async_trait;
+use http::Extensions;
+use reqwest::header::HeaderMap;
+use reqwest::{Request, Response, ResponseBuilderExt};
+use reqwest_middleware::{Middleware, Next};
+use serde::{Deserialize, Serialize};
+use tracing::*;
+use url::Url;
+
+use crate::error::JenkinsError;
+
+const REDACTED: &str = "[redacted]";
+
+// Headers that carry credentials, by lowercase name.
+const REDACTED_HEADERS: [&str; 4] = [
+  "authorization",
+  "proxy-authorization",
+  "cookie",
+  "set-cookie",
+];
+
+// The crumb's request header is named by the server's crumbRequestField,
+// usually "Jenkins-Crumb", so it is recognized by name rather than listed.
+const CRUMB_HEADER_PART: &str = "crumb";
+
+const CRUMB_ISSUER_PATH: &str = "/crumbIssuer/api/json";
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+struct Exchange {
+  method: String,
+  url: String,
+  request_headers: Vec<(String, String)>,
+  request_body: Option<RecordedBody>,
+  status: u16,
+  headers: Vec<(String, String)>,
+  body: RecordedBody,
+}
+
+// Text bodies are kept readable.  Anything else, such as an artifact, is kept
+// as its bytes.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(untagged)]
+enum RecordedBody {
+  Text(String),
+  Bytes(Vec<u8>),
+}
+
+impl RecordedBody {
+  fn new(bytes: &[u8]) -> Self {
+    std::str::from_utf8(bytes).map_or_else(
+      |_| RecordedBody::Bytes(bytes.to_vec()),
+      |text| RecordedBody::Text(text.to_string()),
+    )
+  }
+
+  fn to_bytes(&self) -> Vec<u8> {
+    match self {
+      RecordedBody::Text(text) => text.as_bytes().to_vec(),
+      RecordedBody::Bytes(bytes) => bytes.clone(),
+    }
+  }
+}
+
+pub(crate) struct RecordMiddleware {
+  writer: Mutex<LineWriter<File>>,
+}
+
+impl RecordMiddleware {
+  // Creates (or truncates) the recording at `path`.
+  pub(crate) fn create(path: &Path) -> Result<Self, JenkinsError> {
+    File::create(path)
+      .map(|file| RecordMiddleware {
+        writer: Mutex::new(LineWriter::new(file)),
+      })
+      .map_err(|e| JenkinsError::RecordCreate(path.display().to_string(), e))
+  }
+
+  fn exchange_write(&self, exchange: &Exchange) -> Result<(), JenkinsError> {
+    let line =
+      serde_json::to_string(exchange).map_err(JenkinsError::RecordSerialize)?;
+    writeln!(lock(&self.writer), "{}", line).map_err(JenkinsError::RecordWrite)
+  }
+}
+
+#[async_trait]
+impl Middleware for RecordMiddleware {
+  // The response body is read in full so it can be both written down and
+  // handed on, which holds streamed logs and artifacts in memory while
+  // recording.
+  async fn handle(
+    &self,
+    request: Request,
+    extensions: &mut Extensions,
+    next: Next<'_>,
+  ) -> reqwest_middleware::Result<Response> {
+    let method = request.method().to_string();
+    let url = request.url().clone();
+    let request_headers = headers_redact(request.headers());
+    let request_body = request
+      .body()
+      .and_then(reqwest::Body::as_bytes)
+      .map(RecordedBody::new);
+    let response = next.run(request, extensions).await?;
+    let status = response.status();
+    let headers = response.headers().clone();
+    let body = response.bytes().await?;
+    let recorded_body = if url.path().ends_with(CRUMB_ISSUER_PATH) {
+      crumb_redact(&body)
+    } else {
+      RecordedBody::new(&body)
+    };
+    self
+      .exchange_write(&Exchange {
+        method,
+        url: url.to_string(),
+        request_headers,
+        request_body,
+        status: status.as_u16(),
+        headers: headers_redact(&headers),
+        body: recorded_body,
+      })
+      .map_err(reqwest_middleware::Error::middleware)?;
+    let mut rebuilt = http::Response::builder()
+      .status(status)
+      .url(url)
+      .body(body)
+      .map_err(|e| {
+        reqwest_middleware::Error::middleware(JenkinsError::ResponseBuild(e))
+      })?;
+    *rebuilt.headers_mut() = headers;
+    Ok(Response::from(rebuilt))
+  }
+}
+
+pub(crate) struct ReplayMiddleware {
+  // Recorded exchanges by replay key, each in the order it was recorded.
+  exchanges: Mutex<HashMap<String, VecDeque<Exchange>>>,
+}
+
+impl ReplayMiddleware {
+  // Reads the whole recording at `path` up front, so a malformed one fails
+  // before any command starts.
+  pub(crate) fn open(path: &Path) -> Result<Self, JenkinsError> {
+    let name = path.display().to_string();
+    let file = File::open(path)
+      .map_err(|e| JenkinsError::ReplayOpen(name.clone(), e))?;
+    let mut exchanges = HashMap::<String, VecDeque<Exchange>>::new();
+    for (number, line) in BufReader::new(file).lines().enumerate() {
+      let line = line.map_err(|e| JenkinsError::ReplayOpen(name.clone(), e))?;
+      if line.trim().is_empty() {
+        continue;
+      }
+      let exchange = serde_json::from_str::<Exchange>(&line)
+        .map_err(|e| JenkinsError::ReplayParse(name.clone(), number + 1, e))?;
+      let key = Url::parse(&exchange.url)
+        .map(|url| replay_key(&exchange.method, &url))
+        .map_err(|e| JenkinsError::ReplayUrl(exchange.url.clone(), e))?;
+      exchanges.entry(key).or_default().push_back(exchange);
+    }
+    Ok(ReplayMiddleware {
+      exchanges: Mutex::new(exchanges),
+    })
+  }
+
+  fn response(&self, request: &Request) -> Result<Response, JenkinsError> {
+    let key = replay_key(request.method().as_str(), request.url());
+    let exchange = lock(&self.exchanges)
+      .get_mut(&key)
+      .and_then(|recorded| {
+        if recorded.len() > 1 {
+          recorded.pop_front()
+        } else {
+          recorded.front().cloned()
+        }
+      })
+      .ok_or(JenkinsError::ReplayMissing(key))?;
+    debug!(url = %request.url(), status = exchange.status, "Replaying");
+    exchange
+      .headers
+      .iter()
+      .fold(
+        http::Response::builder()
+          .status(exchange.status)
+          .url(request.url().clone()),
+        |builder, (name, value)| builder.header(name, value),
+      )
+      .body(exchange.body.to_bytes())
+      .map(Response::from)
+      .map_err(JenkinsError::ResponseBuild)
+  }
+}
+
+#[async_trait]
+impl Middleware for ReplayMiddleware {
+  async fn handle(
+    &self,
+    request: Request,
+    _extensions: &mut Extensions,
+    _next: Next<'_>,
+  ) -> reqwest_middleware::Result<Response> {
+    self
+      .response(&request)
+      .map_err(reqwest_middleware::Error::middleware)
+  }
+}
+
+// The method, path and query, e.g. "GET /job/a/api/json".  The query's
+// parameters are sorted, so the same parameters match in any order.
+fn replay_key(method: &str, url: &Url) -> String {
+  let mut pairs = url.query_pairs().collect::<Vec<_>>();
+  pairs.sort();
+  let query = url::form_urlencoded::Serializer::new(String::new())
+    .extend_pairs(pairs)
+    .finish();
+  if query.is_empty() {
+    format!("{} {}", method, url.path())
+  } else {
+    format!("{} {}?{}", method, url.path(), query)
+  }
+}
+
+// The crumb field of a crumbIssuer answer.  A body that is not the expected
+// JSON is kept as it was, since it carries no crumb to leak.
+fn crumb_redact(body: &[u8]) -> RecordedBody {
+  let Ok(mut json) = serde_json::from_slice::<serde_json::Value>(body) else {
+    return RecordedBody::new(body);
+  };
+  if let Some(crumb) = json.get_mut("crumb") {
+    *crumb = serde_json::Value::from(REDACTED);
+  }
+  RecordedBody::Text(json.to_string())
+}
+
+fn headers_redact(headers: &HeaderMap) -> Vec<(String, String)> {
+  headers
+    .iter()
+    .map(|(name, value)| {
+      let value = if REDACTED_HEADERS.contains(&name.as_str())
+        || name.as_str().contains(CRUMB_HEADER_PART)
+      {
+        REDACTED.to_string()
+      } else {
+        String::from_utf8_lossy(value.as_bytes()).into_owned()
+      };
+      (name.to_string(), value)
+    })
+    .collect()
+}
+
+// Nothing is left half-written by a panic while the lock is held, so a
+// poisoned lock is taken over.
+fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+  mutex.lock().unwrap_or_else(PoisonError::into_inner)
+}
@@ -0,0 +1,116 @@
+// Destinations for streamed build log lines.  JenkinsClient::build_log_to
+// hands every line of a build's log to a LogSink, so a log can go to the
+// terminal, a file, a channel another task reads from, or several of these at
+// once through TeeSink.
+use std::fs::File;
+use std::io::{self, LineWriter, Write};
+use std::path::{Path, PathBuf};
+
+use tokio::sync::mpsc;
+
+/// Receives a build's log one line at a time, without the line terminator.
+pub trait LogSink: Send {
+  fn line_write(&mut self, line: &str) -> io::Result<()>;
+}
+
+impl<S: LogSink + ?Sized> LogSink for Box<S> {
+  fn line_write(&mut self, line: &str) -> io::Result<()> {
+    (**self).line_write(line)
+  }
+}
+
+/// Writes each line to stdout behind a prefix, such as `[42] `.  Each line is
+/// written under a single stdout lock so builds streamed side by side never
+/// interleave mid-line.
+#[derive(Clone, Debug)]
+pub struct StdoutSink {
+  prefix: String,
+}
+
+impl StdoutSink {
+  pub fn new(prefix: String) -> Self {
+    StdoutSink { prefix }
+  }
+}
+
+impl LogSink for StdoutSink {
+  fn line_write(&mut self, line: &str) -> io::Result<()> {
+    writeln!(io::stdout().lock(), "{}{}", self.prefix, line)
+  }
+}
+
+/// Writes the log, unprefixed, to a file.  Lines are flushed as they are
+/// written so the file can be tailed while the build runs.
+#[derive(Debug)]
+pub struct FileSink {
+  path: PathBuf,
+  writer: LineWriter<File>,
+}
+
+impl FileSink {
+  /// Creates (or truncates) the file at `path`, along with any missing parent
+  /// directories.
+  pub fn create(path: PathBuf) -> io::Result<Self> {
+    if let Some(parent) = path.parent() {
+      std::fs::create_dir_all(parent)?;
+    }
+    let writer = LineWriter::new(File::create(&path)?);
+    Ok(FileSink { path, writer })
+  }
+
+  pub fn path(&self) -> &Path {
+    &self.path
+  }
+}
+
+impl LogSink for FileSink {
+  fn line_write(&mut self, line: &str) -> io::Result<()> {
+    writeln!(self.writer, "{}", line)
+  }
+}
+
+/// Writes every line to both sinks, the first before the second.  Nest tees
+/// to reach more than two.
+#[derive(Debug)]
+pub struct TeeSink<A, B> {
+  first: A,
+  second: B,
+}
+
+impl<A: LogSink, B: LogSink> TeeSink<A, B> {
+  pub fn new(first: A, second: B) -> Self {
+    TeeSink { first, second }
+  }
+}
+
+impl<A: LogSink, B: LogSink> LogSink for TeeSink<A, B> {
+  fn line_write(&mut self, line: &str) -> io::Result<()> {
+    self.first.line_write(line)?;
+    self.second.line_write(line)
+  }
+}
+
+/// Sends each line over an unbounded channel, for callers that want to
+/// process the log on another task or keep it in memory.
+#[derive(Clone, Debug)]
+pub struct ChannelSink {
+  sender: mpsc::UnboundedSender<String>,
+}
+
+impl ChannelSink {
+  /// A sink and the receiving end of its channel.
+  pub fn channel() -> (Self, mpsc::UnboundedReceiver<String>) {
+    let (sender, receiver) = mpsc::unbounded_channel();
+    (ChannelSink { sender }, receiver)
+  }
+}
+
+impl LogSink for ChannelSink {
+  // A dropped receiver means nobody is listening any more, which is reported
+  // the way a closed pipe would be.
+  fn line_write(&mut self, line: &str) -> io::Result<()> {
+    self.sender.send(line.to_string()).map_err(|_| {
+      io::Error::new(io::ErrorKind::BrokenPipe, "log channel receiver dropped")
+    })
+  }
+}
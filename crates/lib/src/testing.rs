@@ -0,0 +1,1930 @@
+// An in-process stand-in for a Jenkins controller, for exercising jj, and
+// tools built on jj-lib, without a live server.  It answers the REST endpoints
+// JenkinsClient uses over real HTTP on a local port, so anything that talks to
+// Jenkins can be pointed at FakeJenkins::url unchanged.
+//
+// Jobs are scripted rather than run: a FakeScript logs lines, sleeps, sets a
+// result, archives artifacts and triggers other jobs, in real time, much like
+// the Pipelines under jenkins/jobs.  Around them the fake models what jj has
+// to cope with on a real server: queue items that sit out a quiet period or
+// wait for a free executor, progressiveText served in pieces with
+// x-text-size/x-more-data, results that appear before a build has finished,
+// permalinks, parameters, disabled jobs, cancellation and aborts.
+//
+// Stages are described as the Pipeline Stage View plugin's wfapi describes
+// them.  Input steps pause a build until they are answered, through the same
+// pendingInputActions and input/<id> endpoints the Stage View and Pipeline
+// Input Step plugins serve.  Test results are reported as the JUnit plugin
+// reports them, or as a matrix build aggregates them.  CSRF crumbs are off
+// unless asked for, as on a server where every client uses an API token; with
+// them on, a POST without the crumb and the session cookie it was issued with
+// is refused.  Authentication is not modelled: any credentials are accepted.
+//
+// Faults can be injected to stand in for a flaky network or a proxy in front
+// of a restarting controller: 502s, 503s, and connections dropped partway
+// through a response.
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use axum::extract::{FromRequest, Multipart, Request, State};
+use axum::http::{header, HeaderMap, Method, StatusCode, Uri};
+use axum::response::{IntoResponse, Response};
+use axum::{Json, Router};
+use serde_json::{json, Value};
+use tokio::sync::oneshot;
+use tracing::*;
+
+use crate::job::{percent_decode, JobPath};
+
+// The user every build and queue item is attributed to.
+const FAKE_USER: &str = "admin";
+
+// Whoever answers an input through the web UI rather than through a client.
+const FAKE_OTHER_USER: &str = "someone-else";
+
+const CRUMB_FIELD: &str = "Jenkins-Crumb";
+const SESSION_COOKIE: &str = "JSESSIONID.fake";
+
+type SharedState = Arc<Mutex<FakeState>>;
+type ScriptFn = dyn Fn(&HashMap<String, String>) -> FakeScript + Send + Sync;
+
+/// A fake Jenkins controller serving on a local port until dropped.
+pub struct FakeJenkins {
+  url: String,
+  state: SharedState,
+  shutdown: Option<oneshot::Sender<()>>,
+}
+
+impl FakeJenkins {
+  /// Starts serving on a free local port with two executors, a quiet period
+  /// of zero and no jobs, like the test server in jenkins/casc.
+  pub fn start() -> std::io::Result<Self> {
+    let listener = std::net::TcpListener::bind("127.0.0.1:0")?;
+    listener.set_nonblocking(true)?;
+    let url = format!("http://{}", listener.local_addr()?);
+    let state = Arc::new(Mutex::new(FakeState::new(url.clone())));
+    let app = Router::new().fallback(respond).with_state(state.clone());
+    let (shutdown, shutdown_received) = oneshot::channel::<()>();
+    // The server gets a runtime of its own so the fake works the same from
+    // synchronous tests and from inside another runtime.
+    let runtime = tokio::runtime::Builder::new_current_thread()
+      .enable_all()
+      .build()?;
+    std::thread::spawn(move || {
+      let served = runtime.block_on(async move {
+        axum::serve(tokio::net::TcpListener::from_std(listener)?, app)
+          .with_graceful_shutdown(async move {
+            // A dropped sender means the same as a sent one: stop.
+            shutdown_received.await.unwrap_or_default();
+          })
+          .await
+      });
+      if let Err(e) = served {
+        warn!("Fake Jenkins stopped serving: {}", e);
+      }
+    });
+    Ok(FakeJenkins {
+      url,
+      state,
+      shutdown: Some(shutdown),
+    })
+  }
+
+  /// The server's root URL without a trailing slash, as JenkinsClient::new
+  /// and the config file's `host_url` expect it.
+  pub fn url(&self) -> &str {
+    &self.url
+  }
+
+  /// Serves `job` at `path`, a path through any folders.
+  pub fn job_add(&self, path: JobPath, job: FakeJob) {
+    self.state().jobs.insert(path, job);
+  }
+
+  /// How many builds may run at once.  Queue items beyond that wait as
+  /// BuildableItems until a build finishes.
+  pub fn executors_set(&self, executors: usize) {
+    self.state().executors = executors;
+  }
+
+  /// Answers the input build `number` of `job` is waiting on, as someone in
+  /// the web UI would: proceeding with its parameters' defaults, or aborting.
+  /// Returns false if the build is not waiting on an input.
+  pub fn input_answer(
+    &self,
+    job: &JobPath,
+    number: u64,
+    proceed: bool,
+  ) -> bool {
+    let now = now_ms();
+    let mut state = self.state();
+    state.advance(now);
+    let Some(build) = state
+      .builds
+      .get_mut(job)
+      .and_then(|builds| builds.iter_mut().find(|b| b.number == number))
+    else {
+      return false;
+    };
+    let Some(input) = build.progress(now).pending_input.cloned() else {
+      return false;
+    };
+    build.answers.push(FakeAnswer {
+      at_ms: now,
+      by: FAKE_OTHER_USER.to_string(),
+      values: proceed.then(|| input.values_resolve(&HashMap::new())),
+    });
+    true
+  }
+
+  /// When set, every input is answered in the web UI just as a client submits
+  /// its own answer, so the submit is refused the way Jenkins refuses an
+  /// input that is no longer pending.  The input proceeds with its defaults.
+  pub fn input_race_set(&self, race: bool) {
+    self.state().input_race = race;
+  }
+
+  /// Serves `fault` in place of the next `times` responses to requests whose
+  /// path ends with `path_end`, such as "logText/progressiveText".  A HEAD
+  /// request has no body to cut, so it is never dropped.
+  pub fn fault_inject(&self, path_end: &str, fault: FakeFault, times: usize) {
+    self.state().faults.push(FakeFaultRule {
+      path_end: path_end.to_string(),
+      fault,
+      remaining: times,
+    });
+  }
+
+  /// How many requests, faulted or not, have had a path ending with
+  /// `path_end`.
+  pub fn requests_count(&self, path_end: &str) -> usize {
+    self
+      .state()
+      .requests
+      .iter()
+      .filter(|path| path.ends_with(path_end))
+      .count()
+  }
+
+  /// Requires a CSRF crumb on every POST, as Jenkins does of users who log in
+  /// with a password.  The crumb is issued by /crumbIssuer/api/json and must
+  /// come back in its header along with the session cookie it was issued
+  /// with; a POST without them is refused with a 403.
+  pub fn crumbs_require(&self) {
+    self.state().crumbs.get_or_insert_with(FakeCrumbs::default);
+  }
+
+  /// Expires the crumb, as a session timing out would, the next time a POST
+  /// carries it: that POST is refused, and a fresh crumb must be fetched.
+  pub fn crumb_expire(&self) {
+    if let Some(crumbs) = self.state().crumbs.as_mut() {
+      crumbs.expiring = true;
+    }
+  }
+
+  fn state(&self) -> MutexGuard<'_, FakeState> {
+    state_lock(&self.state)
+  }
+}
+
+/// A failure served in place of a response.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum FakeFault {
+  /// 502 Bad Gateway, as a proxy answers while the controller is down.
+  BadGateway,
+  /// 503 Service Unavailable, as Jenkins answers while starting up.
+  Unavailable,
+  /// The response's headers and the first half of its body, then the
+  /// connection closes.  The request itself still takes effect.
+  Dropped,
+}
+
+struct FakeFaultRule {
+  path_end: String,
+  fault: FakeFault,
+  remaining: usize,
+}
+
+impl Drop for FakeJenkins {
+  fn drop(&mut self) {
+    if let Some(shutdown) = self.shutdown.take() {
+      if shutdown.send(()).is_err() {
+        debug!("Fake Jenkins had already stopped.");
+      }
+    }
+  }
+}
+
+// A request that panicked mid-update leaves nothing half-written that later
+// requests cannot cope with, so a poisoned lock is taken over.
+fn state_lock(state: &SharedState) -> MutexGuard<'_, FakeState> {
+  state.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+/// A job's parameters, quiet period, and what its builds do.
+#[derive(Clone)]
+pub struct FakeJob {
+  parameters: Vec<FakeParameter>,
+  quiet_period: Duration,
+  tests_aggregated: bool,
+  disabled: bool,
+  downstream_projects: Vec<JobPath>,
+  script: Arc<ScriptFn>,
+}
+
+impl FakeJob {
+  /// A job whose builds follow the script `script` returns.  It is given the
+  /// build's parameters, with defaults filled in for any not supplied, and
+  /// the contents of any file uploaded to a file parameter.
+  pub fn new<F>(script: F) -> Self
+  where
+    F: Fn(&HashMap<String, String>) -> FakeScript + Send + Sync + 'static,
+  {
+    FakeJob {
+      parameters: Vec::new(),
+      quiet_period: Duration::ZERO,
+      tests_aggregated: false,
+      disabled: false,
+      downstream_projects: Vec::new(),
+      script: Arc::new(script),
+    }
+  }
+
+  pub fn parameter(mut self, parameter: FakeParameter) -> Self {
+    self.parameters.push(parameter);
+    self
+  }
+
+  /// How long a queued build waits before it may start, as set by a
+  /// Pipeline's `quietPeriod` option.
+  pub fn quiet_period(mut self, quiet_period: Duration) -> Self {
+    self.quiet_period = quiet_period;
+    self
+  }
+
+  /// Reports test results the way a matrix job does, aggregated over its
+  /// configurations: each suite its script records becomes the report of one
+  /// configuration.
+  pub fn tests_aggregated(mut self) -> Self {
+    self.tests_aggregated = true;
+    self
+  }
+
+  /// Disables the job, so that it refuses to be built.
+  pub fn disabled(mut self) -> Self {
+    self.disabled = true;
+    self
+  }
+
+  /// Builds `job` after every successful build of this one, as a freestyle
+  /// job's "Build other projects" does.  Unlike a script's triggers, these
+  /// builds are not recorded on the upstream build: they are found through
+  /// the job's downstreamProjects and their own upstream cause.
+  pub fn downstream_project(mut self, job: JobPath) -> Self {
+    self.downstream_projects.push(job);
+    self
+  }
+
+  // Every defined parameter's value: as supplied, or its default.  Like
+  // Jenkins, parameters the job does not define are dropped.
+  fn parameters_resolve(
+    &self,
+    supplied: &HashMap<String, String>,
+  ) -> Vec<(String, String)> {
+    self
+      .parameters
+      .iter()
+      .map(|p| {
+        let value = supplied
+          .get(&p.name)
+          .cloned()
+          .unwrap_or_else(|| p.default_string());
+        (p.name.clone(), value)
+      })
+      .collect()
+  }
+}
+
+/// A parameter definition, as a Pipeline's `parameters {}` block declares.
+#[derive(Clone, Debug)]
+pub struct FakeParameter {
+  name: String,
+  kind: &'static str,
+  default: Value,
+  choices: Option<Vec<String>>,
+}
+
+impl FakeParameter {
+  pub fn string(name: &str, default: &str) -> Self {
+    FakeParameter {
+      name: name.to_string(),
+      kind: "StringParameterDefinition",
+      default: Value::from(default),
+      choices: None,
+    }
+  }
+
+  pub fn boolean(name: &str, default: bool) -> Self {
+    FakeParameter {
+      name: name.to_string(),
+      kind: "BooleanParameterDefinition",
+      default: Value::from(default),
+      choices: None,
+    }
+  }
+
+  /// A choice parameter, defaulting to its first choice as Jenkins does.
+  pub fn choice(name: &str, choices: &[&str]) -> Self {
+    FakeParameter {
+      name: name.to_string(),
+      kind: "ChoiceParameterDefinition",
+      default: Value::from(choices.first().copied().unwrap_or_default()),
+      choices: Some(choices.iter().map(|c| c.to_string()).collect()),
+    }
+  }
+
+  /// A file parameter, which takes an upload and has no default.
+  pub fn file(name: &str) -> Self {
+    FakeParameter {
+      name: name.to_string(),
+      kind: "FileParameterDefinition",
+      default: Value::Null,
+      choices: None,
+    }
+  }
+
+  fn default_string(&self) -> String {
+    match &self.default {
+      Value::Null => String::new(),
+      Value::String(s) => s.clone(),
+      other => other.to_string(),
+    }
+  }
+
+  // Jenkins reports boolean values as JSON booleans and everything else as
+  // strings, except for files, whose value it does not export.
+  fn value_json(&self, value: &str) -> Value {
+    match self.kind {
+      "BooleanParameterDefinition" => Value::from(value == "true"),
+      "FileParameterDefinition" => Value::Null,
+      _ => Value::from(value),
+    }
+  }
+
+  fn definition_json(&self) -> Value {
+    json!({
+      "_class": format!("hudson.model.{}", self.kind),
+      "name": self.name,
+      "type": self.kind,
+      "description": "",
+      "defaultParameterValue": { "value": self.default },
+      "choices": self.choices,
+    })
+  }
+}
+
+/// What a build does, one step after another, in real time.  A build whose
+/// script never sets a result succeeds.
+#[derive(Clone, Debug, Default)]
+pub struct FakeScript {
+  steps: Vec<FakeStep>,
+}
+
+#[derive(Clone, Debug)]
+enum FakeStep {
+  Log(String),
+  Sleep(Duration),
+  Result(String),
+  Trigger(JobPath),
+  Artifact(String, Vec<u8>),
+  Input(FakeInput),
+  Tests(FakeTestSuite),
+  Stage(String),
+}
+
+impl FakeScript {
+  pub fn new() -> Self {
+    FakeScript::default()
+  }
+
+  /// Starts the stage `name`, ending any stage before it.  A stage in which
+  /// the build's result is set ends with that result.
+  pub fn stage(mut self, name: &str) -> Self {
+    self.steps.push(FakeStep::Stage(name.to_string()));
+    self
+  }
+
+  /// Logs `text`, which may span several lines, like an `echo` step.
+  pub fn log(mut self, text: &str) -> Self {
+    self.steps.push(FakeStep::Log(text.to_string()));
+    self
+  }
+
+  pub fn sleep(mut self, duration: Duration) -> Self {
+    self.steps.push(FakeStep::Sleep(duration));
+    self
+  }
+
+  /// Sets the build's result.  Like `currentBuild.result`, it is reported at
+  /// once, while the build goes on running any steps after it.
+  pub fn result(mut self, result: &str) -> Self {
+    self.steps.push(FakeStep::Result(result.to_string()));
+    self
+  }
+
+  /// Queues a build of `job` as a downstream of this one, like a `build job:`
+  /// step with `wait: false`.
+  pub fn trigger(mut self, job: JobPath) -> Self {
+    self.steps.push(FakeStep::Trigger(job));
+    self
+  }
+
+  /// Archives `contents` as the artifact at `relative_path`.
+  pub fn artifact(mut self, relative_path: &str, contents: &[u8]) -> Self {
+    self
+      .steps
+      .push(FakeStep::Artifact(relative_path.to_string(), contents.to_vec()));
+    self
+  }
+
+  /// Waits until `input` is answered.  Proceeding logs each of the input's
+  /// values as name=value, as a script echoing the step's result would;
+  /// aborting ends the build as ABORTED.
+  pub fn input(mut self, input: FakeInput) -> Self {
+    self.steps.push(FakeStep::Input(input));
+    self
+  }
+
+  /// Records the suite `name` of `cases`, like a `junit` step reading one
+  /// report file.  As with `junit`, any failed case makes a successful build
+  /// UNSTABLE.
+  pub fn tests(mut self, name: &str, cases: Vec<FakeTestCase>) -> Self {
+    self.steps.push(FakeStep::Tests(FakeTestSuite {
+      name: name.to_string(),
+      cases,
+    }));
+    self
+  }
+}
+
+#[derive(Clone, Debug)]
+struct FakeTestSuite {
+  name: String,
+  cases: Vec<FakeTestCase>,
+}
+
+impl FakeTestSuite {
+  fn duration(&self) -> f64 {
+    self.cases.iter().map(|c| c.duration.as_secs_f64()).sum()
+  }
+
+  fn count(&self, status: &str) -> usize {
+    self.cases.iter().filter(|c| c.status() == status).count()
+  }
+}
+
+/// A test case as a JUnit report describes it.
+#[derive(Clone, Debug)]
+pub struct FakeTestCase {
+  class_name: String,
+  name: String,
+  duration: Duration,
+  outcome: FakeTestOutcome,
+}
+
+#[derive(Clone, Debug)]
+enum FakeTestOutcome {
+  Passed,
+  Failed {
+    details: String,
+    stack_trace: String,
+  },
+  Skipped {
+    message: String,
+  },
+}
+
+impl FakeTestCase {
+  pub fn passed(class_name: &str, name: &str) -> Self {
+    FakeTestCase::new(class_name, name, FakeTestOutcome::Passed)
+  }
+
+  pub fn failed(
+    class_name: &str,
+    name: &str,
+    details: &str,
+    stack_trace: &str,
+  ) -> Self {
+    FakeTestCase::new(
+      class_name,
+      name,
+      FakeTestOutcome::Failed {
+        details: details.to_string(),
+        stack_trace: stack_trace.to_string(),
+      },
+    )
+  }
+
+  pub fn skipped(class_name: &str, name: &str, message: &str) -> Self {
+    FakeTestCase::new(
+      class_name,
+      name,
+      FakeTestOutcome::Skipped {
+        message: message.to_string(),
+      },
+    )
+  }
+
+  pub fn duration(mut self, duration: Duration) -> Self {
+    self.duration = duration;
+    self
+  }
+
+  fn new(class_name: &str, name: &str, outcome: FakeTestOutcome) -> Self {
+    FakeTestCase {
+      class_name: class_name.to_string(),
+      name: name.to_string(),
+      duration: Duration::ZERO,
+      outcome,
+    }
+  }
+
+  // Every case has run once, so none is a regression or a fix.
+  fn status(&self) -> &'static str {
+    match self.outcome {
+      FakeTestOutcome::Passed => "PASSED",
+      FakeTestOutcome::Failed { .. } => "FAILED",
+      FakeTestOutcome::Skipped { .. } => "SKIPPED",
+    }
+  }
+
+  fn json(&self) -> Value {
+    let (details, stack_trace, skipped) = match &self.outcome {
+      FakeTestOutcome::Passed => (None, None, None),
+      FakeTestOutcome::Failed {
+        details,
+        stack_trace,
+      } => (Some(details), Some(stack_trace), None),
+      FakeTestOutcome::Skipped { message } => (None, None, Some(message)),
+    };
+    json!({
+      "className": self.class_name,
+      "name": self.name,
+      "status": self.status(),
+      "duration": self.duration.as_secs_f64(),
+      "errorDetails": details,
+      "errorStackTrace": stack_trace,
+      "skipped": skipped.is_some(),
+      "skippedMessage": skipped,
+    })
+  }
+}
+
+/// A Pipeline `input` step.
+#[derive(Clone, Debug)]
+pub struct FakeInput {
+  id: String,
+  message: String,
+  parameters: Vec<FakeParameter>,
+}
+
+impl FakeInput {
+  /// An input with the id Jenkins would give it, such as "Approve" for
+  /// `input id: 'approve'`.
+  pub fn new(id: &str, message: &str) -> Self {
+    FakeInput {
+      id: id.to_string(),
+      message: message.to_string(),
+      parameters: Vec::new(),
+    }
+  }
+
+  pub fn parameter(mut self, parameter: FakeParameter) -> Self {
+    self.parameters.push(parameter);
+    self
+  }
+
+  // Every parameter's value: as submitted, or its default.
+  fn values_resolve(
+    &self,
+    submitted: &HashMap<String, String>,
+  ) -> Vec<(String, String)> {
+    self
+      .parameters
+      .iter()
+      .map(|p| {
+        let value = submitted
+          .get(&p.name)
+          .cloned()
+          .unwrap_or_else(|| p.default_string());
+        (p.name.clone(), value)
+      })
+      .collect()
+  }
+
+  fn json(&self, build_url: &str) -> Value {
+    let input_url = format!("{}input/{}/", build_url, self.id);
+    json!({
+      "id": self.id,
+      "proceedText": "Proceed",
+      "message": self.message,
+      "inputs": self
+        .parameters
+        .iter()
+        .map(|p| json!({
+          "type": p.kind,
+          "name": p.name,
+          "description": "",
+          "definition": p.definition_json(),
+        }))
+        .collect::<Vec<Value>>(),
+      "proceedUrl": format!("{}submit", input_url),
+      "abortUrl": format!("{}abort", input_url),
+    })
+  }
+}
+
+// An answer to a build's input, in the order its inputs came up.
+struct FakeAnswer {
+  at_ms: u64,
+  by: String,
+  // The values proceeded with, or None for an abort.
+  values: Option<Vec<(String, String)>>,
+}
+
+#[derive(Clone, Debug)]
+enum FakeCause {
+  User,
+  Upstream { job: JobPath, build: u64 },
+}
+
+impl FakeCause {
+  fn json(&self) -> Value {
+    match self {
+      FakeCause::User => json!({
+        "_class": "hudson.model.Cause$UserIdCause",
+        "shortDescription": format!("Started by user {}", FAKE_USER),
+        "userId": FAKE_USER,
+        "userName": FAKE_USER,
+      }),
+      FakeCause::Upstream { job, build } => json!({
+        "_class": "hudson.model.Cause$UpstreamCause",
+        "shortDescription": format!(
+          "Started by upstream project \"{}\" build number {}",
+          job, build,
+        ),
+        "upstreamProject": job.to_string(),
+        "upstreamBuild": build,
+      }),
+    }
+  }
+
+  fn action_json(&self) -> Value {
+    json!({
+      "_class": "hudson.model.CauseAction",
+      "causes": [self.json()],
+    })
+  }
+}
+
+struct FakeQueueItem {
+  id: u32,
+  job: JobPath,
+  parameters: Vec<(String, String)>,
+  cause: FakeCause,
+  queued_ms: u64,
+  // When the quiet period ends.
+  until_ms: u64,
+  cancelled: bool,
+  // Set once the item has left the queue as a build.
+  build: Option<u64>,
+}
+
+struct FakeBuild {
+  number: u64,
+  parameters: Vec<(String, String)>,
+  cause: FakeCause,
+  started_ms: u64,
+  steps: Vec<FakeStep>,
+  aborted_ms: Option<u64>,
+  // How many of the script's triggers have been queued so far, and the queue
+  // items they became.
+  triggers_fired: usize,
+  downstream: Vec<u32>,
+  // Whether the job's downstream projects have been queued, once it ended.
+  projects_triggered: bool,
+  answers: Vec<FakeAnswer>,
+}
+
+// How far a build has got at some moment: everything its script has done by
+// then.
+struct FakeProgress<'a> {
+  log: String,
+  building: bool,
+  result: Option<String>,
+  duration_ms: u64,
+  triggers: Vec<&'a JobPath>,
+  artifacts: Vec<(&'a str, &'a [u8])>,
+  pending_input: Option<&'a FakeInput>,
+  tests: Vec<&'a FakeTestSuite>,
+  stages: Vec<FakeStageProgress<'a>>,
+}
+
+struct FakeStageProgress<'a> {
+  name: &'a str,
+  // From the start of the build.
+  start_ms: u64,
+  duration_ms: u64,
+  // As wfapi reports it, e.g. IN_PROGRESS or FAILED.
+  status: String,
+  // The build's result when the stage started.
+  result_before: Option<String>,
+}
+
+impl FakeStageProgress<'_> {
+  // Ends the stage at `end_ms`.  It takes the build's result only if that
+  // was set while it ran.
+  fn end(&mut self, end_ms: u64, result: Option<&str>) {
+    self.duration_ms = end_ms.saturating_sub(self.start_ms);
+    let changed = result.filter(|r| Some(*r) != self.result_before.as_deref());
+    self.status = match changed {
+      Some("FAILURE") => "FAILED",
+      Some(result @ ("UNSTABLE" | "ABORTED")) => result,
+      _ => "SUCCESS",
+    }
+    .to_string();
+  }
+}
+
+impl FakeBuild {
+  fn progress(&self, now: u64) -> FakeProgress<'_> {
+    let stopped = self.aborted_ms.map_or(now, |aborted| aborted.min(now));
+    let elapsed = stopped.saturating_sub(self.started_ms);
+    let mut progress = FakeProgress {
+      log: format!("Started by user {}\n", FAKE_USER),
+      building: true,
+      result: None,
+      duration_ms: elapsed,
+      triggers: Vec::new(),
+      artifacts: Vec::new(),
+      pending_input: None,
+      tests: Vec::new(),
+      stages: Vec::new(),
+    };
+    let mut offset = 0;
+    let mut finished = true;
+    let mut answers = self.answers.iter();
+    for step in &self.steps {
+      match step {
+        FakeStep::Sleep(duration) => {
+          let end = offset + duration.as_millis() as u64;
+          if end > elapsed {
+            finished = false;
+            break;
+          }
+          offset = end;
+        }
+        FakeStep::Log(text) => {
+          progress.log.push_str(text);
+          progress.log.push('\n');
+        }
+        FakeStep::Result(result) => progress.result = Some(result.clone()),
+        FakeStep::Trigger(job) => progress.triggers.push(job),
+        FakeStep::Artifact(path, contents) => {
+          progress.artifacts.push((path, contents))
+        }
+        FakeStep::Stage(name) => {
+          let result = progress.result.as_deref();
+          if let Some(stage) = progress.stages.last_mut() {
+            stage.end(offset, result);
+          }
+          progress.stages.push(FakeStageProgress {
+            name,
+            start_ms: offset,
+            duration_ms: 0,
+            status: "IN_PROGRESS".to_string(),
+            result_before: progress.result.clone(),
+          });
+        }
+        FakeStep::Tests(suite) => {
+          progress.log.push_str("Recording test results\n");
+          if suite.count("FAILED") > 0
+            && progress.result.as_deref().is_none_or(|r| r == "SUCCESS")
+          {
+            progress.result = Some("UNSTABLE".to_string());
+          }
+          progress.tests.push(suite);
+        }
+        // The build resumes when the input is answered, so everything after
+        // it runs that much later.
+        FakeStep::Input(input) => {
+          progress
+            .log
+            .push_str(&format!("{}\nProceed or Abort\n", input.message));
+          let Some(answer) = answers.next() else {
+            progress.pending_input = Some(input);
+            finished = false;
+            break;
+          };
+          offset = offset.max(answer.at_ms.saturating_sub(self.started_ms));
+          let Some(values) = &answer.values else {
+            progress
+              .log
+              .push_str(&format!("Rejected by {}\n", answer.by));
+            progress.result = Some("ABORTED".to_string());
+            break;
+          };
+          progress
+            .log
+            .push_str(&format!("Approved by {}\n", answer.by));
+          for (name, value) in values {
+            progress.log.push_str(&format!("{}={}\n", name, value));
+          }
+        }
+      }
+    }
+    if !finished && self.aborted_ms.is_some() {
+      progress.pending_input = None;
+      progress
+        .log
+        .push_str(&format!("Aborted by {}\n", FAKE_USER));
+      progress.result = Some("ABORTED".to_string());
+      finished = true;
+    } else if finished {
+      progress.duration_ms = offset;
+    }
+    let stage_end = if finished {
+      progress.duration_ms
+    } else {
+      elapsed
+    };
+    let result = progress.result.as_deref();
+    if let Some(stage) = progress.stages.last_mut() {
+      if finished {
+        stage.end(stage_end, result);
+      } else {
+        stage.duration_ms = stage_end.saturating_sub(stage.start_ms);
+        if progress.pending_input.is_some() {
+          stage.status = "PAUSED_PENDING_INPUT".to_string();
+        }
+      }
+    }
+    if finished {
+      let result = progress.result.get_or_insert_with(|| "SUCCESS".to_string());
+      progress.log.push_str(&format!("Finished: {}\n", result));
+      progress.building = false;
+    }
+    progress
+  }
+}
+
+struct FakeState {
+  url: String,
+  executors: usize,
+  jobs: HashMap<JobPath, FakeJob>,
+  queue: Vec<FakeQueueItem>,
+  queue_id_next: u32,
+  builds: HashMap<JobPath, Vec<FakeBuild>>,
+  input_race: bool,
+  faults: Vec<FakeFaultRule>,
+  // Every request's path, in the order they came.
+  requests: Vec<String>,
+  crumbs: Option<FakeCrumbs>,
+}
+
+// The crumb issuer, when crumbs are required.
+#[derive(Default)]
+struct FakeCrumbs {
+  issued: u32,
+  // The crumb and session cookie value last issued, until it expires.
+  valid: Option<(String, String)>,
+  expiring: bool,
+}
+
+impl FakeCrumbs {
+  fn issue(&mut self) -> Response {
+    self.issued += 1;
+    let crumb = format!("fake-crumb-{}", self.issued);
+    let session = format!("fake-session-{}", self.issued);
+    let cookie = format!("{}={}; Path=/; HttpOnly", SESSION_COOKIE, session);
+    self.valid = Some((crumb.clone(), session));
+    (
+      [(header::SET_COOKIE, cookie)],
+      Json(json!({
+        "_class": "hudson.security.csrf.DefaultCrumbIssuer",
+        "crumb": crumb,
+        "crumbRequestField": CRUMB_FIELD,
+      })),
+    )
+      .into_response()
+  }
+
+  // Whether a POST carries the valid crumb in its header and the session it
+  // belongs to in its cookie.  An expiring crumb is refused once and dropped.
+  fn accepts(&mut self, headers: &HeaderMap) -> bool {
+    let Some((crumb, session)) = &self.valid else {
+      return false;
+    };
+    let crumb_sent = headers
+      .get(CRUMB_FIELD)
+      .is_some_and(|value| value.as_bytes() == crumb.as_bytes());
+    let session_pair = format!("{}={}", SESSION_COOKIE, session);
+    let session_sent = headers
+      .get_all(header::COOKIE)
+      .iter()
+      .filter_map(|value| value.to_str().ok())
+      .flat_map(|value| value.split(';'))
+      .any(|pair| pair.trim() == session_pair);
+    if !(crumb_sent && session_sent) {
+      return false;
+    }
+    if self.expiring {
+      self.expiring = false;
+      self.valid = None;
+      return false;
+    }
+    true
+  }
+}
+
+impl FakeState {
+  fn new(url: String) -> Self {
+    FakeState {
+      url,
+      executors: 2,
+      jobs: HashMap::new(),
+      queue: Vec::new(),
+      queue_id_next: 1,
+      builds: HashMap::new(),
+      input_race: false,
+      faults: Vec::new(),
+      requests: Vec::new(),
+      crumbs: None,
+    }
+  }
+
+  // The fault to serve for a request to `path`, if one is still due.
+  fn fault_take(&mut self, method: &Method, path: &str) -> Option<FakeFault> {
+    let rule = self.faults.iter_mut().find(|rule| {
+      rule.remaining > 0
+        && path.ends_with(&rule.path_end)
+        && !(rule.fault == FakeFault::Dropped && method == Method::HEAD)
+    })?;
+    rule.remaining -= 1;
+    Some(rule.fault)
+  }
+
+  fn job_url(&self, job: &JobPath) -> String {
+    format!("{}{}/", self.url, job.url_path())
+  }
+
+  fn build_url(&self, job: &JobPath, number: u64) -> String {
+    format!("{}{}/", self.job_url(job), number)
+  }
+
+  fn build(&self, job: &JobPath, number: u64) -> Option<&FakeBuild> {
+    self
+      .builds
+      .get(job)
+      .and_then(|builds| builds.iter().find(|b| b.number == number))
+  }
+
+  fn running(&self, now: u64) -> usize {
+    self
+      .builds
+      .values()
+      .flatten()
+      .filter(|b| b.progress(now).building)
+      .count()
+  }
+
+  // Queues a build of `child` caused by build `number` of `job`.
+  fn trigger(
+    &mut self,
+    child: &JobPath,
+    job: &JobPath,
+    number: u64,
+    now: u64,
+  ) -> Option<u32> {
+    let cause = FakeCause::Upstream {
+      job: job.clone(),
+      build: number,
+    };
+    let id = self.enqueue(child, &HashMap::new(), cause, now);
+    if id.is_none() {
+      warn!("Fake Jenkins has no job '{}' to trigger.", child);
+    }
+    id
+  }
+
+  fn enqueue(
+    &mut self,
+    job: &JobPath,
+    supplied: &HashMap<String, String>,
+    cause: FakeCause,
+    now: u64,
+  ) -> Option<u32> {
+    let definition = self.jobs.get(job)?;
+    let id = self.queue_id_next;
+    self.queue_id_next += 1;
+    self.queue.push(FakeQueueItem {
+      id,
+      job: job.clone(),
+      parameters: definition.parameters_resolve(supplied),
+      cause,
+      queued_ms: now,
+      until_ms: now + definition.quiet_period.as_millis() as u64,
+      cancelled: false,
+      build: None,
+    });
+    Some(id)
+  }
+
+  // Brings everything up to `now`: queues the builds running builds have
+  // triggered, and those of the downstream projects of builds that have
+  // succeeded, then starts queued items whose quiet period is over, for as
+  // long as executors are free.  Called before every request, so the fake
+  // needs no clock of its own.
+  fn advance(&mut self, now: u64) {
+    let mut triggered = Vec::new();
+    let mut projects = Vec::new();
+    for (job, builds) in &mut self.builds {
+      let downstream_projects = self
+        .jobs
+        .get(job)
+        .map_or(&[][..], |d| d.downstream_projects.as_slice());
+      for build in builds {
+        let progress = build.progress(now);
+        let succeeded =
+          !progress.building && progress.result.as_deref() == Some("SUCCESS");
+        let ended = !progress.building;
+        let due = progress
+          .triggers
+          .into_iter()
+          .skip(build.triggers_fired)
+          .cloned()
+          .collect::<Vec<JobPath>>();
+        build.triggers_fired += due.len();
+        triggered.extend(
+          due
+            .into_iter()
+            .map(|child| (child, job.clone(), build.number)),
+        );
+        if ended && !build.projects_triggered {
+          build.projects_triggered = true;
+          projects.extend(
+            downstream_projects
+              .iter()
+              .filter(|_| succeeded)
+              .map(|child| (child.clone(), job.clone(), build.number)),
+          );
+        }
+      }
+    }
+    for (child, job, number) in triggered {
+      let Some(id) = self.trigger(&child, &job, number, now) else {
+        continue;
+      };
+      self
+        .builds
+        .get_mut(&job)
+        .and_then(|builds| builds.iter_mut().find(|b| b.number == number))
+        .into_iter()
+        .for_each(|build| build.downstream.push(id));
+    }
+    for (child, job, number) in projects {
+      self.trigger(&child, &job, number, now);
+    }
+
+    let mut free = self.executors.saturating_sub(self.running(now));
+    let FakeState {
+      queue,
+      jobs,
+      builds,
+      ..
+    } = self;
+    for item in queue.iter_mut() {
+      if free == 0 {
+        break;
+      }
+      if item.cancelled || item.build.is_some() || now < item.until_ms {
+        continue;
+      }
+      let Some(job) = jobs.get(&item.job) else {
+        continue;
+      };
+      let job_builds = builds.entry(item.job.clone()).or_default();
+      let number = job_builds.last().map_or(1, |b| b.number + 1);
+      let parameters = item.parameters.iter().cloned().collect();
+      job_builds.push(FakeBuild {
+        number,
+        parameters: item.parameters.clone(),
+        cause: item.cause.clone(),
+        started_ms: now,
+        steps: (job.script)(&parameters).steps,
+        aborted_ms: None,
+        triggers_fired: 0,
+        downstream: Vec::new(),
+        projects_triggered: false,
+        answers: Vec::new(),
+      });
+      item.build = Some(number);
+      free -= 1;
+    }
+  }
+
+  fn respond(
+    &mut self,
+    method: &Method,
+    uri: &Uri,
+    headers: &HeaderMap,
+    form: HashMap<String, String>,
+    now: u64,
+  ) -> Response {
+    let segments = uri
+      .path()
+      .split('/')
+      .filter(|s| !s.is_empty())
+      .collect::<Vec<&str>>();
+    // Like Jenkins, form fields are read alongside the query string.
+    let mut query: HashMap<String, String> = uri
+      .query()
+      .map(|q| {
+        url::form_urlencoded::parse(q.as_bytes())
+          .into_owned()
+          .collect()
+      })
+      .unwrap_or_default();
+    query.extend(form);
+    if let Some(crumbs) = self.crumbs.as_mut() {
+      if method == Method::POST && !crumbs.accepts(headers) {
+        return (
+          StatusCode::FORBIDDEN,
+          "No valid crumb was included in the request",
+        )
+          .into_response();
+      }
+    }
+    match (method, segments.as_slice()) {
+      (&Method::GET, ["crumbIssuer", "api", "json"]) => self
+        .crumbs
+        .as_mut()
+        .map_or_else(not_found, FakeCrumbs::issue),
+      (&Method::GET, ["queue", "api", "json"]) => Json(json!({
+        "items": self
+          .queue
+          .iter()
+          .filter(|item| !item.cancelled && item.build.is_none())
+          .map(|item| self.queue_item_json(item, now))
+          .collect::<Vec<Value>>(),
+      }))
+      .into_response(),
+      (&Method::GET, ["queue", "item", id, "api", "json"]) => id
+        .parse::<u32>()
+        .ok()
+        .and_then(|id| self.queue.iter().find(|item| item.id == id))
+        .map_or_else(not_found, |item| {
+          Json(self.queue_item_json(item, now)).into_response()
+        }),
+      (&Method::POST, ["queue", "cancelItem"]) => {
+        let item = query
+          .get("id")
+          .and_then(|id| id.parse::<u32>().ok())
+          .and_then(|id| self.queue.iter_mut().find(|item| item.id == id))
+          .filter(|item| !item.cancelled && item.build.is_none());
+        match item {
+          Some(item) => {
+            item.cancelled = true;
+            StatusCode::NO_CONTENT.into_response()
+          }
+          None => not_found(),
+        }
+      }
+      (_, ["job", ..]) => {
+        self.job_respond(method, uri, &segments, &query, headers, now)
+      }
+      _ => not_found(),
+    }
+  }
+
+  fn job_respond(
+    &mut self,
+    method: &Method,
+    uri: &Uri,
+    segments: &[&str],
+    query: &HashMap<String, String>,
+    headers: &HeaderMap,
+    now: u64,
+  ) -> Response {
+    let Some(job) = JobPath::from_url(uri.path())
+      .ok()
+      .filter(|job| self.jobs.contains_key(job))
+    else {
+      return not_found();
+    };
+    let parameterized = self
+      .jobs
+      .get(&job)
+      .is_some_and(|definition| !definition.parameters.is_empty());
+    let disabled = self.jobs.get(&job).is_some_and(|d| d.disabled);
+    let rest = segments.get(job.segments().len() * 2..).unwrap_or_default();
+    match (method, rest) {
+      (&Method::GET, [] | ["api", "json"]) => {
+        Json(self.job_json(&job, now)).into_response()
+      }
+      (&Method::POST, ["build" | "buildWithParameters"]) if disabled => {
+        (StatusCode::CONFLICT, format!("{} is not buildable", job))
+          .into_response()
+      }
+      // Jenkins refuses a build request that does not match whether the job
+      // takes parameters.
+      (&Method::POST, ["build"]) if !parameterized => {
+        self.enqueue_respond(&job, &HashMap::new(), now)
+      }
+      (&Method::POST, ["buildWithParameters"]) if parameterized => {
+        self.enqueue_respond(&job, query, now)
+      }
+      (&Method::POST, ["build" | "buildWithParameters"]) => {
+        StatusCode::BAD_REQUEST.into_response()
+      }
+      (_, [selector, tail @ ..]) => self
+        .build_select(&job, selector, now)
+        .map_or_else(not_found, |number| {
+          self.build_respond(method, &job, number, tail, query, headers, now)
+        }),
+      _ => not_found(),
+    }
+  }
+
+  fn enqueue_respond(
+    &mut self,
+    job: &JobPath,
+    supplied: &HashMap<String, String>,
+    now: u64,
+  ) -> Response {
+    match self.enqueue(job, supplied, FakeCause::User, now) {
+      Some(id) => {
+        // Like Jenkins, the item may leave the queue before its creator
+        // first asks about it.
+        self.advance(now);
+        (
+          StatusCode::CREATED,
+          [(header::LOCATION, format!("{}/queue/item/{}/", self.url, id))],
+        )
+          .into_response()
+      }
+      None => not_found(),
+    }
+  }
+
+  // A build number, or one of the permalinks jj offers.
+  fn build_select(
+    &self,
+    job: &JobPath,
+    selector: &str,
+    now: u64,
+  ) -> Option<u64> {
+    let builds = self.builds.get(job)?;
+    if let Ok(number) = selector.parse::<u64>() {
+      return builds.iter().any(|b| b.number == number).then_some(number);
+    }
+    let finished_with = |wanted: Option<&str>| {
+      builds
+        .iter()
+        .rev()
+        .find(|b| {
+          let progress = b.progress(now);
+          !progress.building
+            && wanted.is_none_or(|w| progress.result.as_deref() == Some(w))
+        })
+        .map(|b| b.number)
+    };
+    match selector {
+      "lastBuild" => builds.last().map(|b| b.number),
+      "lastCompletedBuild" => finished_with(None),
+      "lastSuccessfulBuild" | "lastStableBuild" => {
+        finished_with(Some("SUCCESS"))
+      }
+      "lastFailedBuild" => finished_with(Some("FAILURE")),
+      "lastUnstableBuild" => finished_with(Some("UNSTABLE")),
+      _ => None,
+    }
+  }
+
+  #[allow(clippy::too_many_arguments)]
+  fn build_respond(
+    &mut self,
+    method: &Method,
+    job: &JobPath,
+    number: u64,
+    tail: &[&str],
+    query: &HashMap<String, String>,
+    headers: &HeaderMap,
+    now: u64,
+  ) -> Response {
+    let Some(build) = self.build(job, number) else {
+      return not_found();
+    };
+    let progress = build.progress(now);
+    match (method, tail) {
+      (&Method::GET, [] | ["api", "json"]) => {
+        Json(self.build_json(job, build, &progress)).into_response()
+      }
+      (&Method::GET, ["logText", "progressiveText"]) => {
+        let log = progress.log.as_bytes();
+        let start = query
+          .get("start")
+          .and_then(|s| s.parse::<usize>().ok())
+          .unwrap_or(0)
+          .min(log.len());
+        let mut response = (
+          [(header::CONTENT_TYPE, "text/plain;charset=UTF-8")],
+          log[start..].to_vec(),
+        )
+          .into_response();
+        let response_headers = response.headers_mut();
+        response_headers.insert("x-text-size", log.len().into());
+        // Jenkins leaves the header out once the log is complete.
+        if progress.building {
+          response_headers
+            .insert("x-more-data", header::HeaderValue::from_static("true"));
+        }
+        response
+      }
+      (&Method::GET, ["consoleText"]) => {
+        ([(header::CONTENT_TYPE, "text/plain;charset=UTF-8")], progress.log)
+          .into_response()
+      }
+      (&Method::POST, ["stop" | "term" | "kill"]) => {
+        if progress.building {
+          self
+            .builds
+            .get_mut(job)
+            .and_then(|builds| builds.iter_mut().find(|b| b.number == number))
+            .into_iter()
+            .for_each(|build| build.aborted_ms = Some(now));
+        }
+        StatusCode::OK.into_response()
+      }
+      (&Method::GET, ["wfapi", "describe"]) => {
+        Json(self.workflow_json(build, &progress)).into_response()
+      }
+      (&Method::GET, ["wfapi", "pendingInputActions"]) => Json(
+        progress
+          .pending_input
+          .map(|input| vec![input.json(&self.build_url(job, number))])
+          .unwrap_or_default(),
+      )
+      .into_response(),
+      // Jenkins answers 404 for an input that is no longer pending, as when
+      // someone else got there first.
+      (&Method::POST, ["input", id, action]) => {
+        let Some(input) = progress.pending_input.filter(|i| i.id == *id) else {
+          return not_found();
+        };
+        let values = match *action {
+          "proceedEmpty" => Some(Vec::new()),
+          "submit" => Some(input.values_resolve(&input_submitted(query))),
+          "abort" => None,
+          _ => return not_found(),
+        };
+        let race = self.input_race;
+        let answer = if race {
+          FakeAnswer {
+            at_ms: now,
+            by: FAKE_OTHER_USER.to_string(),
+            values: Some(input.values_resolve(&HashMap::new())),
+          }
+        } else {
+          FakeAnswer {
+            at_ms: now,
+            by: FAKE_USER.to_string(),
+            values,
+          }
+        };
+        if let Some(build) = self
+          .builds
+          .get_mut(job)
+          .and_then(|builds| builds.iter_mut().find(|b| b.number == number))
+        {
+          build.answers.push(answer);
+        }
+        if race {
+          not_found()
+        } else {
+          StatusCode::OK.into_response()
+        }
+      }
+      // Like the JUnit plugin, a build that recorded no tests has no report.
+      (&Method::GET, ["testReport", "api", "json"]) => {
+        if progress.tests.is_empty() {
+          return not_found();
+        }
+        Json(self.test_report_json(job, number, &progress.tests))
+          .into_response()
+      }
+      (&Method::GET | &Method::HEAD, ["artifact", path @ ..]) => {
+        let path = path
+          .iter()
+          .map(|s| percent_decode(s))
+          .collect::<Vec<String>>()
+          .join("/");
+        progress
+          .artifacts
+          .iter()
+          .find(|(relative, _)| *relative == path)
+          .map_or_else(not_found, |(_, contents)| {
+            artifact_response(contents, &http_date(build.started_ms), headers)
+          })
+      }
+      _ => not_found(),
+    }
+  }
+
+  fn job_json(&self, job: &JobPath, now: u64) -> Value {
+    let definitions = self
+      .jobs
+      .get(job)
+      .map(|definition| {
+        definition
+          .parameters
+          .iter()
+          .map(FakeParameter::definition_json)
+          .collect::<Vec<Value>>()
+      })
+      .unwrap_or_default();
+    let property = if definitions.is_empty() {
+      Vec::new()
+    } else {
+      vec![json!({
+        "_class": "hudson.model.ParametersDefinitionProperty",
+        "parameterDefinitions": definitions,
+      })]
+    };
+    let downstream_projects = self
+      .jobs
+      .get(job)
+      .into_iter()
+      .flat_map(|definition| &definition.downstream_projects)
+      .map(|project| json!({ "name": project.name(), "url": self.job_url(project) }))
+      .collect::<Vec<Value>>();
+    let builds = self
+      .builds
+      .get(job)
+      .into_iter()
+      .flatten()
+      .rev()
+      .map(|build| {
+        let progress = build.progress(now);
+        json!({
+          "_class": "org.jenkinsci.plugins.workflow.job.WorkflowRun",
+          "number": build.number,
+          "url": self.build_url(job, build.number),
+          "building": progress.building,
+          "result": progress.result,
+          "actions": [build.cause.action_json()],
+        })
+      })
+      .collect::<Vec<Value>>();
+    json!({
+      "_class": "org.jenkinsci.plugins.workflow.job.WorkflowJob",
+      "name": job.name(),
+      "fullName": job.to_string(),
+      "url": self.job_url(job),
+      "builds": builds,
+      "property": property,
+      "downstreamProjects": downstream_projects,
+    })
+  }
+
+  fn build_json(
+    &self,
+    job: &JobPath,
+    build: &FakeBuild,
+    progress: &FakeProgress,
+  ) -> Value {
+    let definitions = self.jobs.get(job).map(|d| d.parameters.as_slice());
+    let parameters = build
+      .parameters
+      .iter()
+      .map(|(name, value)| {
+        let value = definitions
+          .into_iter()
+          .flatten()
+          .find(|d| &d.name == name)
+          .map_or_else(|| Value::from(value.as_str()), |d| d.value_json(value));
+        json!({ "name": name, "value": value })
+      })
+      .collect::<Vec<Value>>();
+    let downstream = build
+      .downstream
+      .iter()
+      .filter_map(|id| self.queue.iter().find(|item| item.id == *id))
+      .filter_map(|item| {
+        item.build.map(|number| {
+          json!({ "jobFullName": item.job.to_string(), "buildNumber": number })
+        })
+      })
+      .collect::<Vec<Value>>();
+    let artifacts = progress
+      .artifacts
+      .iter()
+      .map(|(path, _)| {
+        json!({
+          "fileName": path.rsplit('/').next().unwrap_or(path),
+          "relativePath": path,
+        })
+      })
+      .collect::<Vec<Value>>();
+    let mut actions = vec![build.cause.action_json()];
+    if !parameters.is_empty() {
+      actions.push(json!({
+        "_class": "hudson.model.ParametersAction",
+        "parameters": parameters,
+      }));
+    }
+    if !progress.tests.is_empty() {
+      actions.push(self.test_action_json(job, &progress.tests));
+    }
+    if !downstream.is_empty() {
+      actions.push(json!({
+        "_class":
+          "org.jenkinsci.plugins.workflow.support.steps.build.DownstreamBuildAction",
+        "downstreamBuilds": downstream,
+      }));
+    }
+    json!({
+      "_class": "org.jenkinsci.plugins.workflow.job.WorkflowRun",
+      "number": build.number,
+      "url": self.build_url(job, build.number),
+      "building": progress.building,
+      "result": progress.result,
+      "timestamp": build.started_ms,
+      "duration": if progress.building { 0 } else { progress.duration_ms },
+      "displayName": format!("#{}", build.number),
+      "actions": actions,
+      "artifacts": artifacts,
+    })
+  }
+
+  // wfapi/describe.  Stage ids are made up; Jenkins uses flow node ids.
+  fn workflow_json(&self, build: &FakeBuild, progress: &FakeProgress) -> Value {
+    let status = match progress.result.as_deref() {
+      _ if progress.building => "IN_PROGRESS",
+      Some("FAILURE") => "FAILED",
+      Some(result) => result,
+      None => "SUCCESS",
+    };
+    json!({
+      "id": build.number.to_string(),
+      "name": format!("#{}", build.number),
+      "status": status,
+      "startTimeMillis": build.started_ms,
+      "durationMillis": progress.duration_ms,
+      "stages": progress
+        .stages
+        .iter()
+        .enumerate()
+        .map(|(i, stage)| json!({
+          "id": (i + 1).to_string(),
+          "name": stage.name,
+          "status": stage.status,
+          "startTimeMillis": build.started_ms + stage.start_ms,
+          "durationMillis": stage.duration_ms,
+        }))
+        .collect::<Vec<Value>>(),
+    })
+  }
+
+  fn tests_aggregated(&self, job: &JobPath) -> bool {
+    self.jobs.get(job).is_some_and(|d| d.tests_aggregated)
+  }
+
+  // The summary a build's actions carry.
+  fn test_action_json(
+    &self,
+    job: &JobPath,
+    suites: &[&FakeTestSuite],
+  ) -> Value {
+    let count = |status| suites.iter().map(|s| s.count(status)).sum::<usize>();
+    let class = if self.tests_aggregated(job) {
+      "hudson.matrix.MatrixTestResult"
+    } else {
+      "hudson.tasks.junit.TestResultAction"
+    };
+    json!({
+      "_class": class,
+      "failCount": count("FAILED"),
+      "skipCount": count("SKIPPED"),
+      "totalCount": suites.iter().map(|s| s.cases.len()).sum::<usize>(),
+      "urlName": "testReport",
+    })
+  }
+
+  // testReport/api/json.  An aggregated report gives totals rather than a
+  // pass count, and each configuration's own report under childReports.
+  fn test_report_json(
+    &self,
+    job: &JobPath,
+    number: u64,
+    suites: &[&FakeTestSuite],
+  ) -> Value {
+    let report = |suites: &[&FakeTestSuite]| {
+      let count =
+        |status| suites.iter().map(|s| s.count(status)).sum::<usize>();
+      json!({
+        "_class": "hudson.tasks.junit.TestResult",
+        "failCount": count("FAILED"),
+        "passCount": count("PASSED"),
+        "skipCount": count("SKIPPED"),
+        "duration": suites.iter().map(|s| s.duration()).sum::<f64>(),
+        "empty": false,
+        "suites": suites
+          .iter()
+          .map(|s| json!({
+            "name": s.name,
+            "duration": s.duration(),
+            "cases": s.cases.iter().map(FakeTestCase::json).collect::<Vec<Value>>(),
+          }))
+          .collect::<Vec<Value>>(),
+      })
+    };
+    if !self.tests_aggregated(job) {
+      return report(suites);
+    }
+    let mut aggregated = self.test_action_json(job, suites);
+    aggregated["childReports"] = suites
+      .iter()
+      .enumerate()
+      .map(|(i, suite)| {
+        json!({
+          "child": {
+            "_class": "hudson.matrix.MatrixRun",
+            "number": number,
+            "url": format!("{}config={}/{}/", self.job_url(job), i, number),
+          },
+          "result": report(&[suite]),
+        })
+      })
+      .collect::<Vec<Value>>()
+      .into();
+    aggregated
+  }
+
+  fn queue_item_json(&self, item: &FakeQueueItem, now: u64) -> Value {
+    let params = item
+      .parameters
+      .iter()
+      .map(|(name, value)| format!("\n{}={}", name, value))
+      .collect::<String>();
+    let mut json = json!({
+      "actions": [item.cause.action_json()],
+      "blocked": false,
+      "buildable": false,
+      "id": item.id,
+      "inQueueSince": item.queued_ms,
+      "params": params,
+      "stuck": false,
+      "task": {
+        "_class": "org.jenkinsci.plugins.workflow.job.WorkflowJob",
+        "name": item.job.name(),
+        "url": self.job_url(&item.job),
+        "color": "blue",
+      },
+      "url": format!("queue/item/{}/", item.id),
+      "why": Value::Null,
+    });
+    let state = match (item.cancelled, item.build) {
+      (true, _) => json!({
+        "_class": "hudson.model.Queue$LeftItem",
+        "cancelled": true,
+      }),
+      (false, Some(number)) => json!({
+        "_class": "hudson.model.Queue$LeftItem",
+        "cancelled": false,
+        "executable": {
+          "_class": "org.jenkinsci.plugins.workflow.job.WorkflowRun",
+          "number": number,
+          "url": self.build_url(&item.job, number),
+        },
+      }),
+      (false, None) if now < item.until_ms => json!({
+        "_class": "hudson.model.Queue$WaitingItem",
+        "timestamp": item.until_ms,
+        "why": format!(
+          "In the quiet period. Expires in {} ms",
+          item.until_ms - now,
+        ),
+      }),
+      (false, None) => json!({
+        "_class": "hudson.model.Queue$BuildableItem",
+        "buildable": true,
+        "why": "Waiting for next available executor",
+      }),
+    };
+    if let (Value::Object(json), Value::Object(state)) = (&mut json, state) {
+      json.extend(state);
+    }
+    json
+  }
+}
+
+async fn respond(
+  State(state): State<SharedState>,
+  request: Request,
+) -> Response {
+  let method = request.method().clone();
+  let uri = request.uri().clone();
+  let headers = request.headers().clone();
+  // The body is read before taking the lock, which is not held across an
+  // await.
+  let form = match form_read(request).await {
+    Ok(form) => form,
+    Err(response) => return response,
+  };
+  // The lock is released before a cut response is sent, which awaits.
+  let (response, cut) = {
+    let mut state = state_lock(&state);
+    let now = now_ms();
+    state.advance(now);
+    state.requests.push(uri.path().to_string());
+    match state.fault_take(&method, uri.path()) {
+      Some(FakeFault::BadGateway) => {
+        (StatusCode::BAD_GATEWAY.into_response(), false)
+      }
+      Some(FakeFault::Unavailable) => {
+        (StatusCode::SERVICE_UNAVAILABLE.into_response(), false)
+      }
+      fault => (
+        state.respond(&method, &uri, &headers, form, now),
+        fault == Some(FakeFault::Dropped),
+      ),
+    }
+  };
+  if cut {
+    response_cut(response).await
+  } else {
+    response
+  }
+}
+
+// Sends `response`'s headers and the first half of its body, then fails the
+// body so the server closes the connection mid-response.  The failure waits a
+// moment, or the server would give up before writing anything and the client
+// would see a connection closed before any response, which it retries.
+async fn response_cut(response: Response) -> Response {
+  let (mut parts, body) = response.into_parts();
+  let Ok(body) = axum::body::to_bytes(body, usize::MAX).await else {
+    return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+  };
+  parts.headers.remove(header::CONTENT_LENGTH);
+  let head =
+    futures::stream::once(std::future::ready(Ok(body.slice(..body.len() / 2))));
+  let cut = futures::stream::once(async {
+    tokio::time::sleep(Duration::from_millis(50)).await;
+    Err(std::io::Error::new(
+      std::io::ErrorKind::ConnectionReset,
+      "fault injected: connection dropped",
+    ))
+  });
+  Response::from_parts(
+    parts,
+    axum::body::Body::from_stream(futures::StreamExt::chain(head, cut)),
+  )
+}
+
+// Every field of a posted form by name.  Of a multipart form, text fields are
+// taken as they are and files as their contents.  Any other body is ignored.
+async fn form_read(
+  request: Request,
+) -> Result<HashMap<String, String>, Response> {
+  let content_type = request
+    .headers()
+    .get(header::CONTENT_TYPE)
+    .and_then(|value| value.to_str().ok())
+    .unwrap_or_default()
+    .to_string();
+  if content_type.starts_with("application/x-www-form-urlencoded") {
+    let body = axum::body::to_bytes(request.into_body(), usize::MAX)
+      .await
+      .map_err(|_| StatusCode::BAD_REQUEST.into_response())?;
+    return Ok(url::form_urlencoded::parse(&body).into_owned().collect());
+  }
+  if !content_type.starts_with("multipart/form-data") {
+    return Ok(HashMap::new());
+  }
+  let mut multipart = Multipart::from_request(request, &())
+    .await
+    .map_err(IntoResponse::into_response)?;
+  let mut form = HashMap::new();
+  while let Some(field) = multipart
+    .next_field()
+    .await
+    .map_err(IntoResponse::into_response)?
+  {
+    let name = field.name().unwrap_or_default().to_string();
+    let contents = field.bytes().await.map_err(IntoResponse::into_response)?;
+    form.insert(name, String::from_utf8_lossy(&contents).into_owned());
+  }
+  Ok(form)
+}
+
+// The values in an input's submit form, which carries them as a "json" field
+// of the shape {"parameter": [{"name": ..., "value": ...}]}.
+fn input_submitted(form: &HashMap<String, String>) -> HashMap<String, String> {
+  form
+    .get("json")
+    .and_then(|json| serde_json::from_str::<Value>(json).ok())
+    .and_then(|json| json.get("parameter").cloned())
+    .and_then(|parameter| parameter.as_array().cloned())
+    .unwrap_or_default()
+    .iter()
+    .filter_map(|p| {
+      let name = p.get("name")?.as_str()?.to_string();
+      let value = match p.get("value")? {
+        Value::String(s) => s.clone(),
+        other => other.to_string(),
+      };
+      Some((name, value))
+    })
+    .collect()
+}
+
+// Like Jenkins, dates the artifact with Last-Modified.  Honours an open-ended
+// "bytes=<start>-" range, the only kind jj sends, unless an If-Range says it
+// was meant for another version of the file.
+fn artifact_response(
+  contents: &[u8],
+  last_modified: &str,
+  headers: &HeaderMap,
+) -> Response {
+  let current = headers
+    .get(header::IF_RANGE)
+    .is_none_or(|validator| validator.as_bytes() == last_modified.as_bytes());
+  let start = headers
+    .get(header::RANGE)
+    .filter(|_| current)
+    .and_then(|range| range.to_str().ok())
+    .and_then(|range| range.strip_prefix("bytes="))
+    .and_then(|range| range.strip_suffix('-'))
+    .and_then(|start| start.parse::<usize>().ok())
+    .filter(|start| *start < contents.len());
+  let last_modified = [(header::LAST_MODIFIED, last_modified.to_string())];
+  start.map_or_else(
+    || (last_modified.clone(), contents.to_vec()).into_response(),
+    |start| {
+      (
+        StatusCode::PARTIAL_CONTENT,
+        last_modified.clone(),
+        [(
+          header::CONTENT_RANGE,
+          format!("bytes {}-{}/{}", start, contents.len() - 1, contents.len()),
+        )],
+        contents[start..].to_vec(),
+      )
+        .into_response()
+    },
+  )
+}
+
+// An HTTP date, such as "Thu, 01 Jan 1970 00:00:00 GMT".  The calendar date
+// is worked out as in Howard Hinnant's days-from-civil algorithm.
+fn http_date(ms: u64) -> String {
+  const WEEKDAYS: [&str; 7] = ["Thu", "Fri", "Sat", "Sun", "Mon", "Tue", "Wed"];
+  const MONTHS: [&str; 12] = [
+    "Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct",
+    "Nov", "Dec",
+  ];
+  let secs = ms / 1000;
+  let days = secs / 86_400;
+  let z = days + 719_468;
+  let era = z / 146_097;
+  let doe = z % 146_097;
+  let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+  let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+  let mp = (5 * doy + 2) / 153;
+  let day = doy - (153 * mp + 2) / 5 + 1;
+  let month = if mp < 10 { mp + 3 } else { mp - 9 };
+  let year = era * 400 + yoe + u64::from(month <= 2);
+  format!(
+    "{}, {:02} {} {} {:02}:{:02}:{:02} GMT",
+    WEEKDAYS[(days % 7) as usize],
+    day,
+    MONTHS[(month - 1) as usize],
+    year,
+    secs / 3600 % 24,
+    secs / 60 % 60,
+    secs % 60,
+  )
+}
+
+fn not_found() -> Response {
+  StatusCode::NOT_FOUND.into_response()
+}
+
+fn now_ms() -> u64 {
+  SystemTime::now()
+    .duration_since(UNIX_EPOCH)
+    .map_or(0, |since| since.as_millis() as u64)
+}
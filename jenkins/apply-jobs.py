@@ -2,8 +2,10 @@
 """Generate Pipeline job config.xml files from Groovy scripts.
 
 Each .groovy file in jenkins/jobs/ becomes a Pipeline job in
-runner-homes/jenkins/jobs/<name>/config.xml.  Run this before starting
-Jenkins, or after editing a Groovy file, to keep the two in sync.
+runner-homes/jenkins/jobs/<name>/config.xml.  Subdirectories become Folders,
+so jenkins/jobs/folder-test/nested-job.groovy is served as the job
+folder-test/nested-job.  Run this before starting Jenkins, or after editing a
+Groovy file, to keep the two in sync.
 
 Jenkins reads config.xml on startup and on a job reload; it does not need
 a full restart when only job configuration changes.
@@ -35,6 +37,18 @@ TEMPLATE = """\
 </flow-definition>
 """
 
+# Folders come from the cloudbees-folder plugin and hold their children under a
+# nested jobs/ directory, mirroring the layout of JENKINS_HOME/jobs itself.
+FOLDER_TEMPLATE = """\
+<?xml version='1.1' encoding='UTF-8'?>
+<com.cloudbees.hudson.plugins.folder.Folder plugin="cloudbees-folder">
+  <description></description>
+  <properties/>
+  <folderViews/>
+  <healthMetrics/>
+</com.cloudbees.hudson.plugins.folder.Folder>
+"""
+
 def first_comment(text: str) -> str:
     """Return the first // comment line as a description, or empty string."""
     for line in text.splitlines():
@@ -45,18 +59,33 @@ def first_comment(text: str) -> str:
             break
     return ""
 
+def job_dir(relative: pathlib.Path) -> pathlib.Path:
+    """Map a path relative to jenkins/jobs/ onto its JENKINS_HOME location."""
+    out = JOBS_OUT
+    for folder in relative.parts[:-1]:
+        out = out / folder / "jobs"
+    return out / relative.stem
+
+def apply_folder(relative: pathlib.Path) -> None:
+    out_dir = job_dir(relative)
+    out_dir.mkdir(parents=True, exist_ok=True)
+    (out_dir / "config.xml").write_text(FOLDER_TEMPLATE)
+    print(f"applied folder: {relative.as_posix()}")
+
 def apply(groovy_path: pathlib.Path) -> None:
-    name = groovy_path.stem
+    relative = groovy_path.relative_to(JOBS_SRC)
+    for parent in reversed(relative.parents[:-1]):
+        apply_folder(parent)
     script = groovy_path.read_text()
     description = first_comment(script)
     xml = TEMPLATE.format(description=description, script=script)
-    out_dir = JOBS_OUT / name
+    out_dir = job_dir(relative)
     out_dir.mkdir(parents=True, exist_ok=True)
     (out_dir / "config.xml").write_text(xml)
-    print(f"applied: {name}")
+    print(f"applied: {relative.with_suffix('').as_posix()}")
 
 def main() -> None:
-    groovy_files = sorted(JOBS_SRC.glob("*.groovy"))
+    groovy_files = sorted(JOBS_SRC.rglob("*.groovy"))
     if not groovy_files:
         print("no .groovy files found in jenkins/jobs/", file=sys.stderr)
         sys.exit(1)
@@ -19,6 +19,8 @@ import json
 # Top-level plugins.  Transitive required deps are pulled in automatically.
 WANTED = [
     "configuration-as-code",
+    "file-parameters",
+    "junit",
     "workflow-aggregator",
 ]
 
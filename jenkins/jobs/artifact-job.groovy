@@ -0,0 +1,14 @@
+// Archives a small tree of files for exercising `build artifacts`.
+pipeline {
+  agent any
+  stages {
+    stage('Archive') {
+      steps {
+        writeFile file: 'dist/app.txt', text: 'app contents\n'
+        writeFile file: 'dist/nested/notes.txt', text: 'nested notes\n'
+        writeFile file: 'dist/skip.log', text: 'not matched\n'
+        archiveArtifacts artifacts: 'dist/**'
+      }
+    }
+  }
+}
@@ -11,12 +11,18 @@ pipeline {
       defaultValue: false,
       description: 'A boolean parameter.',
     )
+    choice(
+      name: 'baz',
+      choices: ['one', 'two'],
+      description: 'A choice parameter.',
+    )
   }
   stages {
     stage('Echo') {
       steps {
         echo "foo=${params.foo}"
         echo "bar=${params.bar}"
+        echo "baz=${params.baz}"
       }
     }
   }
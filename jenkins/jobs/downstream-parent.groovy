@@ -0,0 +1,12 @@
+// Triggers no-params-job as a downstream build, for exercising
+// --follow-downstream.
+pipeline {
+  agent any
+  stages {
+    stage('Trigger') {
+      steps {
+        build job: 'no-params-job'
+      }
+    }
+  }
+}
@@ -0,0 +1,25 @@
+// Takes an uploaded file alongside a string parameter and prints both.  Used
+// to verify that jj uploads file parameters as multipart form data.
+pipeline {
+  agent any
+  parameters {
+    stashedFile(
+      name: 'bundle',
+      description: 'A file parameter.',
+    )
+    string(
+      name: 'label',
+      defaultValue: 'default-label',
+      description: 'A string parameter.',
+    )
+  }
+  stages {
+    stage('Echo') {
+      steps {
+        unstash 'bundle'
+        echo "label=${params.label}"
+        echo "bundle=${readFile('bundle').trim()}"
+      }
+    }
+  }
+}
@@ -0,0 +1,19 @@
+// Lives inside the folder-test Folder.  Used to verify that jj addresses jobs
+// by their full folder path.
+pipeline {
+  agent any
+  parameters {
+    string(
+      name: 'greeting',
+      defaultValue: 'hello',
+      description: 'Echoed back so tests can confirm the parameter arrived.',
+    )
+  }
+  stages {
+    stage('Greet') {
+      steps {
+        echo "nested says ${params.greeting}"
+      }
+    }
+  }
+}
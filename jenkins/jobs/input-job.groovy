@@ -0,0 +1,21 @@
+// Waits on an input step with a parameter, then echoes the answer.  Used to
+// verify that jj answers input steps and survives ones answered elsewhere.
+pipeline {
+  agent any
+  stages {
+    stage('Approve') {
+      steps {
+        script {
+          def target = input(
+            id: 'approve',
+            message: 'Deploy?',
+            parameters: [
+              string(name: 'target', defaultValue: 'staging', description: ''),
+            ],
+          )
+          echo "target=${target}"
+        }
+      }
+    }
+  }
+}
@@ -0,0 +1,15 @@
+// Logs the lines a naive chunk-by-chunk reader gets wrong: a blank line, text
+// with multi-byte characters, and a line long enough to span several chunks of
+// progressiveText.  Used to verify that jj streams whole lines intact.
+pipeline {
+  agent any
+  stages {
+    stage('Echo') {
+      steps {
+        echo "before blank\n\nafter blank"
+        echo 'multi-byte: ünïcödé ✓ 日本語'
+        echo 'x' * 20000
+      }
+    }
+  }
+}
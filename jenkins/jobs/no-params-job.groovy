@@ -0,0 +1,12 @@
+// Defines no parameters at all.  Used to verify that jj enqueues plain jobs
+// through /build, since Jenkins rejects /buildWithParameters for them.
+pipeline {
+  agent any
+  stages {
+    stage('Echo') {
+      steps {
+        echo 'no parameters needed'
+      }
+    }
+  }
+}
@@ -0,0 +1,15 @@
+// Waits out a quiet period in the queue before it starts.  Used to verify
+// that jj reports and waits out a WaitingItem rather than rejecting it.
+pipeline {
+  agent any
+  options {
+    quietPeriod(3)
+  }
+  stages {
+    stage('Echo') {
+      steps {
+        echo 'quiet period over'
+      }
+    }
+  }
+}
@@ -0,0 +1,27 @@
+// Records a JUnit report with a pass, a failure and a skip, for exercising
+// `build tests`.  The failure carries a terminal colour code and a BEL, as
+// test output often does.  XML 1.0 allows neither, even as character
+// references, so the report is XML 1.1.
+pipeline {
+  agent any
+  stages {
+    stage('Test') {
+      steps {
+        writeFile file: 'results/unit.xml', text: '''\
+<?xml version="1.1" encoding="UTF-8"?>
+<testsuite name="unit" tests="3" failures="1" skipped="1">
+  <testcase classname="com.example.MathTest" name="adds" time="0.5"/>
+  <testcase classname="com.example.MathTest" name="divides" time="0.25">
+    <failure message="&#27;[31mexpected 2 but was 3&#27;[0m">java.lang.AssertionError: expected 2 but was 3&#7;
+	at com.example.MathTest.divides</failure>
+  </testcase>
+  <testcase classname="com.example.MathTest" name="rounds" time="0">
+    <skipped message="not ready"/>
+  </testcase>
+</testsuite>
+'''
+        junit 'results/*.xml'
+      }
+    }
+  }
+}